@@ -0,0 +1,5 @@
+use std::{io, sync::{Arc, Mutex}};
+
+fn main() {
+    chess::engine::run(io::stdin().lock(), Arc::new(Mutex::new(io::stdout())));
+}
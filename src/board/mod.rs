@@ -1,8 +1,6 @@
 use std::fmt::Display;
 
-use colored::Colorize;
-
-use crate::{piece::{Piece, pieces::{rook::Rook, king::King, knight::Knight, bishop::Bishop, queen::Queen, pawn::Pawn}, piece_factory}, color::Color, game::{movement::Movement, Game}, game::castle_rights::CastleRights};
+use crate::{piece::{Piece, pieces::{rook::Rook, king::King, knight::Knight, bishop::Bishop, queen::Queen, pawn::Pawn}, piece_factory}, color::Color, game::movement::Movement, game::castle_rights::CastleRights};
 
 use self::{tile::Tile, position::Position, relative_position::RelativePosition};
 
@@ -18,7 +16,7 @@ pub struct Board{
 }
 
 impl Board {
-    fn new(white_castle_rights: CastleRights, black_castle_rights: CastleRights) -> Board {
+    pub(crate) fn new(white_castle_rights: CastleRights, black_castle_rights: CastleRights) -> Board {
         let mut tiles = Vec::new();
         for x in 1..=8 {
             for y in 1..=8 {
@@ -65,52 +63,126 @@ impl Board {
         return board;
     }
 
+    /// Replays the movements on a copy of this board.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use chess::game::{Game, movement::Movement};
+    /// use chess::board::position::Position;
+    /// use chess::piece::piece_factory;
+    /// use chess::color::Color;
+    ///
+    /// let game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+    ///
+    /// let a1 = Position::from_string("a1").unwrap();
+    /// let a8 = Position::from_string("a8").unwrap();
+    /// let movement = Movement::new_move(piece_factory("R", Color::White), a1, a8, None).unwrap();
+    ///
+    /// let board = game.board().from_movements(&vec![movement]).unwrap();
+    ///
+    /// assert!(board.get_piece_at(&a1).is_none());
+    /// assert_eq!(board.get_piece_at(&a8).as_ref().map(|piece| piece.prefix()), Some("R"));
+    /// assert_eq!(board.pieces().len(), 3);
+    /// ```
     pub fn from_movements(&self, movements : &Vec<Movement>) -> Result<Board, String> {
-        let mut board = Board::new_classical();
-
-        fn make_move(from : &Position, to: &Position, movement: &Movement, board: &mut Board) -> Result<(), String> {
-            match board.move_piece(from.clone(),to.clone()){
-                Ok(_) => {
-                    board.check_for_en_passante(&movement);
-                },
-                Err(e) => {
-                    println!("{}", e);
-                    return Err(e)
-                },
-            };
-
-            match movement.promotion() {
-                Some(promotion) => {
-                    board.set_piece_at(to.clone(), piece_factory(promotion.prefix(), promotion.color().clone()));
-                },
-                _ => {},
-            }
+        let mut board = self.clone();
 
-            return Ok(())
-        }
-        
         for movement in movements {
-            match movement{
-                Movement::Move(_, from, to, _) => make_move(from, to, movement, &mut board)?,
-                Movement::Capture(_, from, to, _) => make_move(from, to, movement, &mut board)?,
-                Movement::CastleKingSide(_) => board.castle_king_side(&movement)?,
-                Movement::CastleQueenSide(_) => board.castle_queen_side(&movement)?,
-            }
+            board.apply(movement)?;
         }
 
         return Ok(board);
     }
 
+    /// Moves the pieces for a movement without checking if it is legal.
+    pub fn apply(&mut self, movement: &Movement) -> Result<(), String> {
+        match movement{
+            Movement::Move(_, from, to, _) => self.make_move(from, to, movement)?,
+            Movement::Capture(_, from, to, _) => self.make_move(from, to, movement)?,
+            Movement::CastleKingSide(_) => self.castle_king_side(movement)?,
+            Movement::CastleQueenSide(_) => self.castle_queen_side(movement)?,
+        }
+
+        return Ok(());
+    }
+
     pub fn get_piece_at(&self, position: &Position) -> &Option<Box<dyn Piece>> {
-        if let Some((shadow_location, piece_location)) = self.pawn_shadow {
-            if shadow_location == *position {
-                return self.get_piece_at(&piece_location);
+        return self.tiles[Board::tile_index(position)].piece();
+    }
+
+    /// Every piece on the board along with its position.
+    pub fn pieces(&self) -> Vec<(Position, &dyn Piece)> {
+        return self.tiles.iter().filter_map(|tile| tile.piece().as_ref().map(|piece| (*tile.position(), piece.as_ref()))).collect();
+    }
+
+    /// The tile a pawn skipped over with a double move on the last turn, if any.
+    pub fn en_passant(&self) -> Option<&Position> {
+        return self.pawn_shadow.as_ref().map(|(shadow_location, _)| shadow_location);
+    }
+
+    pub(crate) fn set_en_passant(&mut self, shadow_location: Option<Position>) -> Result<(), String> {
+        let shadow_location = match shadow_location {
+            Some(shadow_location) => shadow_location,
+            None => {
+                self.pawn_shadow = None;
+                return Ok(());
+            },
+        };
+
+        let piece_rank = match shadow_location.rank() {
+            3 => 4,
+            6 => 5,
+            _ => return Err(format!("Invalid en passant tile {}! Only tiles on rank 3 or 6 can be skipped by a pawn", shadow_location)),
+        };
+
+        self.pawn_shadow = Some((shadow_location, Position::new(shadow_location.file(), piece_rank)?));
+
+        return Ok(());
+    }
+
+    pub fn king_position(&self, color: &Color) -> Option<Position> {
+        return self.tiles.iter().find(|tile| {
+            match tile.piece() {
+                Some(piece) => piece.prefix() == King::prefix() && piece.color() == color,
+                None => false,
+            }
+        }).map(|tile| *tile.position());
+    }
+
+    /// Returns true if any piece of the given color could capture on the position.
+    pub fn is_attacked(&self, position: &Position, by: &Color) -> bool {
+        for tile in self.tiles.iter() {
+            let piece = match tile.piece() {
+                Some(piece) => piece,
+                None => continue,
+            };
+
+            if piece.color() != by {
+                continue;
+            }
+
+            let relative_position = match RelativePosition::from_absolute(tile.position(), position){
+                Ok(relative_position) => relative_position,
+                Err(_) => continue,
+            };
+
+            if !piece.is_valid_capture(&relative_position) {
+                continue;
+            }
+
+            if piece.will_colide(self, tile.position(), position).is_ok() {
+                return true;
             }
         }
 
-        return match &self.tiles.iter().find(|tile| tile.position() == position){
-            Some(tile) => tile.piece(),
-            None => &None,
+        return false;
+    }
+
+    pub fn is_in_check(&self, color: &Color) -> bool {
+        return match self.king_position(color) {
+            Some(position) => self.is_attacked(&position, &color.opposite()),
+            None => false,
         }
     }
 
@@ -128,112 +200,28 @@ impl Board {
         }
     }
 
-    pub fn get_position_by_movement(&self, game: &Game, piece: &Box<dyn Piece>, to: &Position, is_capture : bool, rank_or_file : Option<char>, movement_string : &str) -> Position{
+    fn make_move(&mut self, from : &Position, to: &Position, movement: &Movement) -> Result<(), String> {
+        self.move_piece(*from, *to)?;
+        self.check_for_en_passante(movement);
 
-        enum AmbiguityChar{
-            File(char),
-            Rank(u8),
+        if let Some(promotion) = movement.promotion() {
+            self.set_piece_at(*to, piece_factory(promotion.prefix(), *promotion.color()));
         }
 
-        let ambiguity_char : Option<AmbiguityChar> = match rank_or_file{
-            Some(c) => {
-                match c.is_digit(10){
-                    true => Some(AmbiguityChar::Rank(c.to_digit(10).unwrap() as u8)),
-                    false => Some(AmbiguityChar::File(c)),
-                }
-            },
-            _ => None,
-        };
-
-        let pieces = self.tiles.iter().filter(|tile| {
-            match tile.piece() {
-                Some(p) => {
-                    if p.prefix() == piece.prefix() && p.color() == piece.color() {
-                        return true;
-                    }
-                },
-                None => {},
-            }
-            return false;
-        });
-
-        let mut deduced_from = None;
-
-        for tile in pieces {
-            let found_piece = match tile.piece(){
-                Some(piece) => {
-                    if piece.prefix() == piece.prefix() && piece.color() == piece.color() {
-                        piece
-                    }else{
-                        continue;
-                    }
-                },
-                None => continue,
-            };
-
-            let possible_moves = match is_capture{
-                true => found_piece.possible_captures(),
-                false => found_piece.possible_moves(),
-            };
+        self.check_castle_rights(from, to);
 
-            for relative_position in possible_moves {
-                let position = match Position::from_relative(tile.position().clone(), relative_position.clone()){
-                    Ok(position) => position,
-                    Err(_) => continue,
-                };
-  
-                if position == *to {
-                    let would_be_movement = match is_capture{
-                        true => Movement::Capture(piece_factory(found_piece.prefix(), found_piece.color().clone()), tile.position().clone(), position.clone(), None),
-                        false => Movement::Move(piece_factory(found_piece.prefix(), found_piece.color().clone()), tile.position().clone(), position.clone(), None),
-                    };
-
-
-                    if !game.is_legal(would_be_movement){
-                        continue;
-                    }
-
-                    let currently_deduced_from = tile.position().clone();
-
-                    match ambiguity_char{
-                        Some(AmbiguityChar::File(file)) => {
-                            if currently_deduced_from.file_char() != file {
-                                continue;
-                            }
-                        },
-                        Some(AmbiguityChar::Rank(rank)) => {
-                            if currently_deduced_from.rank() != rank {
-                                continue;
-                            }
-                        },
-                        None => {},
-                    }
-
-                    if deduced_from.is_some() {
-                        panic!("Ambiguous move: {}! Both {} and {} can move to {}", movement_string, deduced_from.unwrap(), currently_deduced_from, to);
-                    }
-
-                    deduced_from = Some(currently_deduced_from);
-                }
-            }
-        }
-
-        return match deduced_from{
-            Some(from) => from,
-            None => panic!("No piece found for move"),
-        }
+        return Ok(())
     }
 
     fn move_piece(&mut self, from: Position, to: Position) -> Result<(), String> {
-        let piece = match self.remove_piece_at(from){
-            Ok(piece) => piece,
-            Err(e) => return Err(e),
-        };
+        let piece = self.remove_piece_at(from)?;
+
+        let is_pawn = piece.prefix() == Pawn::prefix();
 
         self.set_piece_at(to, piece);
 
         if let Some((shadow_location, piece_location)) = self.pawn_shadow {
-            if shadow_location == to {
+            if is_pawn && shadow_location == to {
                 match self.remove_piece_at(piece_location){
                     Ok(_) => {},
                     Err(e) => return Err(e),
@@ -244,9 +232,24 @@ impl Board {
         return Ok(());
     }
 
+    /// Revokes the castle rights of a King or Rook that left, or was captured on, its starting tile.
+    fn check_castle_rights(&mut self, from: &Position, to: &Position) {
+        for position in [from, to] {
+            match (position.file(), position.rank()) {
+                (1, 1) => self.revoke_castle_right(&Color::White, CastleRights::QueenSide),
+                (8, 1) => self.revoke_castle_right(&Color::White, CastleRights::KingSide),
+                (5, 1) => self.revoke_castle_right(&Color::White, CastleRights::Both),
+                (1, 8) => self.revoke_castle_right(&Color::Black, CastleRights::QueenSide),
+                (8, 8) => self.revoke_castle_right(&Color::Black, CastleRights::KingSide),
+                (5, 8) => self.revoke_castle_right(&Color::Black, CastleRights::Both),
+                _ => {},
+            }
+        }
+    }
+
 
     fn castle_king_side(&mut self, movement: &Movement) -> Result<(), String> {
-        self.has_castle_rights(&movement.piece().color(), &CastleRights::KingSide)?;
+        self.has_castle_rights(movement.piece().color(), &CastleRights::KingSide)?;
 
         let rank = match movement.piece().color() {
             Color::White => 1,
@@ -256,25 +259,22 @@ impl Board {
         let king_from = Position::new(5, rank).unwrap();
         let king_to = Position::new(7, rank).unwrap();
 
-        match self.move_piece(king_from, king_to){
-            Ok(king) => king,
-            Err(e) => return Err(e),
-        };
+        self.move_piece(king_from, king_to)?;
 
 
         let rook_from = Position::new(8, rank).unwrap();
         let rook_to = Position::new(6, rank).unwrap();
 
-        match self.move_piece(rook_from, rook_to){
-            Ok(rook) => rook,
-            Err(e) => return Err(e),
-        };
+        self.move_piece(rook_from, rook_to)?;
+
+        self.revoke_castle_right(movement.piece().color(), CastleRights::Both);
+        self.pawn_shadow = None;
 
         return Ok(());
     }
 
     fn castle_queen_side(&mut self, movement: &Movement) -> Result<(), String> {
-        self.has_castle_rights(&movement.piece().color(), &CastleRights::QueenSide)?;
+        self.has_castle_rights(movement.piece().color(), &CastleRights::QueenSide)?;
 
         let rank = match movement.piece().color() {
             Color::White => 1,
@@ -284,49 +284,41 @@ impl Board {
         let king_from = Position::new(5, rank).unwrap();
         let king_to = Position::new(3, rank).unwrap();
 
-        match self.move_piece(king_from, king_to){
-            Ok(king) => king,
-            Err(e) => return Err(e),
-        };
+        self.move_piece(king_from, king_to)?;
 
         let rook_from = Position::new(1, rank).unwrap();
         let rook_to = Position::new(4, rank).unwrap();
 
-        match self.move_piece(rook_from, rook_to){
-            Ok(rook) => rook,
-            Err(e) => return Err(e),
-        };
+        self.move_piece(rook_from, rook_to)?;
+
+        self.revoke_castle_right(movement.piece().color(), CastleRights::Both);
+        self.pawn_shadow = None;
 
         return Ok(()); 
     }
 
-    fn set_piece_at(&mut self, position: Position, piece: Box<dyn Piece>){
-        let tile = match self.tiles.iter_mut().find(|tile| tile.position() == &position){
-            Some(tile) => tile,
-            None => panic!("Cannot set piece on a non existing tile! Could not find tile at {}", position),
-        };
-
-        tile.set_piece(piece);
+    pub(crate) fn set_piece_at(&mut self, position: Position, piece: Box<dyn Piece>){
+        self.tiles[Board::tile_index(&position)].set_piece(piece);
     }
 
     fn remove_piece_at(&mut self, position: Position) -> Result<Box<dyn Piece>, String> {
-        let tile = match self.tiles.iter_mut().find(|tile| tile.position() == &position){
-            Some(tile) => tile,
-            None => panic!("Cannot remove piece on a non existing tile! Could not find tile at {}", position),
-        };
+        return self.tiles[Board::tile_index(&position)].remove_piece();
+    }
 
-        return tile.remove_piece();
+    /// Tiles are stored file by file, from a1 to h8.
+    fn tile_index(position: &Position) -> usize {
+        return (position.file() as usize - 1) * 8 + position.rank() as usize - 1;
     }
 
-    fn has_castle_rights(&self, color: &Color, castle_right: &CastleRights) -> Result<(), String> {
+    pub fn has_castle_rights(&self, color: &Color, castle_right: &CastleRights) -> Result<(), String> {
         let rights = self.get_castle_rights(color);
 
         if rights == &CastleRights::None {
-            return Err(String::from(format!("No castle rights for {}", color)));
+            return Err(format!("No castle rights for {}", color));
         }
 
         if rights != castle_right  && rights != &CastleRights::Both {
-            return Err(String::from(format!("{} does not have castle rights for {}! Only {} castle rights.", color, castle_right, rights)));
+            return Err(format!("{} does not have castle rights for {}! Only {} castle rights.", color, castle_right, rights));
         }
 
         return Ok(());
@@ -348,7 +340,7 @@ impl Board {
             None => return,
         };
 
-        let relative_position = match RelativePosition::from_absolute(&from, &to){
+        let relative_position = match RelativePosition::from_absolute(from, to){
             Ok(relative_position) => relative_position,
             Err(_) => return,
         };
@@ -365,7 +357,7 @@ impl Board {
                 Err(_) => return,
             };
 
-            self.pawn_shadow = Some((shadow_position, to.clone()));
+            self.pawn_shadow = Some((shadow_position, *to));
         } else {
             self.pawn_shadow = None;
         }
@@ -373,6 +365,17 @@ impl Board {
 }
 
 
+impl Clone for Board {
+    fn clone(&self) -> Board {
+        Board {
+            tiles: self.tiles.clone(),
+            white_castle_rights: self.white_castle_rights,
+            black_castle_rights: self.black_castle_rights,
+            pawn_shadow: self.pawn_shadow,
+        }
+    }
+}
+
 impl Display for Board {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut board = String::new();
@@ -393,13 +396,9 @@ impl Display for Board {
                     None => continue,
                 };
 
-                if (rank + file) % 2 == 0 {
-                    board.push_str(&format!("{}", tile));
-                } else {
-                    board.push_str(&format!("{}", tile));
-                }
+                board.push_str(&format!("{}", tile));
             }
-            board.push_str("\n");
+            board.push('\n');
         }
         return write!(f, "{}", board);
     }
@@ -12,11 +12,11 @@ impl Position {
     pub fn new(file: u8, rank: u8) -> Result<Position, String> {
         //file and rank have to be between 1 and 8
 
-        if file > 8 || file < 1 {
+        if !(1..=8).contains(&file) {
             return Err(format!("File out of bounds: {}", file));
         }
 
-        if rank > 8 || file < 1 {
+        if !(1..=8).contains(&rank) {
             return Err(format!("Rank out of bounds: {}", rank));
         }
         
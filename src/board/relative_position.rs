@@ -11,11 +11,11 @@ pub struct RelativePosition{
 impl RelativePosition {
     pub fn new(file: i8, rank: i8) -> Result<RelativePosition, String> {
         // min is -7 and max is 7
-        if file > 7 || file < -7 {
+        if !(-7..=7).contains(&file) {
             return Err(format!("Invalid position: File out of bounds: {}", file));
         }
 
-        if rank > 7 || rank < -7 {
+        if !(-7..=7).contains(&rank) {
             return Err(format!("Invalid position: Rank out of bounds: {}", rank));
         }
 
@@ -31,7 +31,7 @@ impl RelativePosition {
     }
 
     pub fn from_absolute(from: &Position, to: &Position) -> Result<RelativePosition, String> {
-        return RelativePosition::new((to.file() as i8 - from.file() as i8) as i8, (to.rank() as i8 - from.rank() as i8) as i8);
+        return RelativePosition::new(to.file() as i8 - from.file() as i8, to.rank() as i8 - from.rank() as i8);
     }
 }
 
@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use crate::piece::Piece;
+use crate::piece::{Piece, piece_factory};
 
 use super::position::Position;
 
@@ -32,7 +32,16 @@ impl Tile {
     pub fn remove_piece(&mut self) -> Result<Box<dyn Piece>, String> {
         return match self.piece.take() {
             Some(piece) => Ok(piece),
-            None => Err(String::from(format!("Cannot remove piece from empty tile at position {}", self.position))),
+            None => Err(format!("Cannot remove piece from empty tile at position {}", self.position)),
+        }
+    }
+}
+
+impl Clone for Tile {
+    fn clone(&self) -> Tile {
+        Tile {
+            position: self.position,
+            piece: self.piece.as_ref().map(|piece| piece_factory(piece.prefix(), *piece.color())),
         }
     }
 }
@@ -0,0 +1,288 @@
+use std::{collections::HashMap, fs::File, io::{BufRead, BufReader, BufWriter, Write}};
+
+use crate::{color::Color, game::Game, parser::pgn_reader::PgnReader};
+
+use super::{BookEntry, encode_move, zobrist::polyglot_key};
+
+/// What a movement scored over the games it was played in.
+#[derive(Default, Clone, Copy)]
+struct MoveStats {
+    games: u32,
+    /// Half points: 2 for a win and 1 for a draw.
+    points: u64,
+}
+
+/// Builds a Polyglot opening book from a collection of games.
+///
+/// Every movement played in the first plies of a finished game is counted, and its weight is the score of the side
+/// who played it: 2 points for a win and 1 for a draw. Movements that never scored are left out, as well as the ones
+/// played in fewer games than required.
+///
+/// ## Examples
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use chess::{book::{Book, builder::BookBuilder}, game::Game};
+///
+/// let pgn = "[Result \"1-0\"]\n\n1.e4 e5 2.Nf3 1-0\n\n[Result \"1/2-1/2\"]\n\n1.d4 d5 1/2-1/2\n";
+///
+/// let mut builder = BookBuilder::new().max_ply(2);
+/// assert_eq!(builder.add_pgn(pgn.as_bytes()), 2);
+///
+/// let mut bytes = Vec::new();
+/// builder.write(&mut bytes).unwrap();
+///
+/// let mut book = Book::new(Cursor::new(bytes)).unwrap();
+/// assert_eq!(book.movements(&Game::new_classical()).unwrap().len(), 2);
+/// ```
+pub struct BookBuilder {
+    max_ply: usize,
+    min_games: u32,
+    min_rating: Option<u32>,
+    stats: HashMap<(u64, u16), MoveStats>,
+}
+
+impl BookBuilder {
+    pub fn new() -> BookBuilder {
+        BookBuilder {
+            max_ply: 20,
+            min_games: 1,
+            min_rating: None,
+            stats: HashMap::new(),
+        }
+    }
+
+    /// Only counts the movements of the first plies of each game, 20 by default.
+    pub fn max_ply(mut self, max_ply: usize) -> BookBuilder {
+        self.max_ply = max_ply;
+        self
+    }
+
+    /// Leaves out the movements played in fewer games.
+    pub fn min_games(mut self, min_games: u32) -> BookBuilder {
+        self.min_games = min_games;
+        self
+    }
+
+    /// Only counts the games where both players have at least this rating, read from the `WhiteElo` and `BlackElo`
+    /// tags.
+    pub fn min_rating(mut self, min_rating: u32) -> BookBuilder {
+        self.min_rating = Some(min_rating);
+        self
+    }
+
+    /// Counts the movements of a game. Returns false when the game is skipped because it is not finished or its
+    /// players are not rated high enough.
+    pub fn add_game(&mut self, game: &Game) -> Result<bool, String> {
+        let result = match game.tag("Result") {
+            Some("1-0") => Some(Color::White),
+            Some("0-1") => Some(Color::Black),
+            Some("1/2-1/2") => None,
+            _ => return Ok(false),
+        };
+
+        if !self.is_rated_enough(|name| game.tag(name)) {
+            return Ok(false);
+        }
+
+        let mut replay = Game::from_fen(game.start_fen())?;
+
+        for movement in game.movements().iter().take(self.max_ply) {
+            let points = match &result {
+                Some(winner) if winner == replay.turn() => 2,
+                Some(_) => 0,
+                None => 1,
+            };
+
+            let stats = self.stats.entry((polyglot_key(&replay), encode_move(movement))).or_default();
+            stats.games += 1;
+            stats.points += points;
+
+            replay.play(movement.clone())?;
+        }
+
+        return Ok(true);
+    }
+
+    /// Counts every game of a PGN source and returns how many were used. Games that cannot be read are skipped.
+    pub fn add_pgn<R: BufRead>(&mut self, reader: R) -> usize {
+        let mut count = 0;
+
+        for pgn in PgnReader::new(reader).flatten() {
+            // The rating is checked first so the movetext of filtered games is never parsed.
+            if !self.is_rated_enough(|name| pgn.tag(name)) {
+                continue;
+            }
+
+            if let Ok(true) = pgn.game().and_then(|game| self.add_game(&game)) {
+                count += 1;
+            }
+        }
+
+        return count;
+    }
+
+    pub fn add_pgn_file(&mut self, path: &str) -> Result<usize, String> {
+        let file = File::open(path).map_err(|e| format!("Cannot open PGN file {}: {}", path, e))?;
+
+        return Ok(self.add_pgn(BufReader::new(file)));
+    }
+
+    fn is_rated_enough<'a, F: Fn(&str) -> Option<&'a str>>(&self, tag: F) -> bool {
+        let min_rating = match self.min_rating {
+            Some(min_rating) => min_rating,
+            None => return true,
+        };
+
+        return ["WhiteElo", "BlackElo"].iter().all(|name| {
+            match tag(name).and_then(|rating| rating.trim().parse::<u32>().ok()) {
+                Some(rating) => rating >= min_rating,
+                None => false,
+            }
+        });
+    }
+
+    /// The book entries sorted by key, then by decreasing weight.
+    ///
+    /// Weights are scaled down per position when the best movement scored more than a weight can hold.
+    pub fn entries(&self) -> Vec<BookEntry> {
+        let mut by_key : HashMap<u64, Vec<(u16, u64)>> = HashMap::new();
+
+        for ((key, raw_move), stats) in &self.stats {
+            if stats.games >= self.min_games && stats.points > 0 {
+                by_key.entry(*key).or_default().push((*raw_move, stats.points));
+            }
+        }
+
+        let mut entries = Vec::new();
+
+        for (key, movements) in by_key {
+            let best = movements.iter().map(|(_, points)| *points).max().unwrap_or(0);
+            let divisor = best.div_ceil(u16::MAX as u64).max(1);
+
+            for (raw_move, points) in movements {
+                let weight = (points / divisor).max(1) as u16;
+
+                entries.push(BookEntry::new(key, raw_move, weight, 0));
+            }
+        }
+
+        entries.sort_by_key(|entry| (entry.key(), u16::MAX - entry.weight(), entry.raw_move()));
+
+        return entries;
+    }
+
+    /// Writes the book in the Polyglot format.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), String> {
+        for entry in self.entries() {
+            if let Err(e) = writer.write_all(&entry.to_bytes()) {
+                return Err(format!("Cannot write book: {}", e));
+            }
+        }
+
+        return writer.flush().map_err(|e| format!("Cannot write book: {}", e));
+    }
+
+    pub fn write_file(&self, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("Cannot create book {}: {}", path, e))?;
+
+        return self.write(&mut BufWriter::new(file));
+    }
+}
+
+impl Default for BookBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{book::Book, parser::pgn::from_movetext};
+
+    use super::*;
+
+    const GAMES : &str = "[White \"A\"]
+[Black \"B\"]
+[WhiteElo \"2400\"]
+[BlackElo \"2300\"]
+[Result \"1-0\"]
+
+1.e4 e5 2.Nf3 Nc6 3.Bb5 1-0
+
+[White \"C\"]
+[Black \"D\"]
+[WhiteElo \"2500\"]
+[BlackElo \"2450\"]
+[Result \"0-1\"]
+
+1.e4 c5 2.Nf3 d6 0-1
+
+[White \"E\"]
+[Black \"F\"]
+[WhiteElo \"1500\"]
+[Result \"1/2-1/2\"]
+
+1.e4 e5 2.Nf3 Nf6 1/2-1/2
+
+[White \"G\"]
+[Black \"H\"]
+[Result \"*\"]
+
+1.d4 d5 *
+";
+
+    fn load(builder: &BookBuilder) -> Book<Cursor<Vec<u8>>> {
+        let mut bytes = Vec::new();
+        builder.write(&mut bytes).unwrap();
+
+        return Book::new(Cursor::new(bytes)).unwrap();
+    }
+
+    fn weights(book: &mut Book<Cursor<Vec<u8>>>, game: &Game) -> Vec<(String, u16)> {
+        return book.movements(game).unwrap().iter().map(|(movement, weight)| (movement.to_uci(false), *weight)).collect();
+    }
+
+    #[test]
+    fn weights_movements_by_result(){
+        let mut builder = BookBuilder::new().max_ply(3);
+
+        assert_eq!(builder.add_pgn(GAMES.as_bytes()), 3);
+
+        let entries = builder.entries();
+        assert!(entries.windows(2).all(|pair| pair[0].key() <= pair[1].key()));
+
+        let mut book = load(&builder);
+
+        // Two points for the win, none for the loss and one for the draw.
+        assert_eq!(weights(&mut book, &Game::new_classical()), vec![(String::from("e2e4"), 3)]);
+        assert_eq!(weights(&mut book, &from_movetext("1.e4").unwrap()), vec![(String::from("c7c5"), 2), (String::from("e7e5"), 1)]);
+        assert_eq!(weights(&mut book, &from_movetext("1.e4 e5").unwrap()), vec![(String::from("g1f3"), 3)]);
+
+        // Beyond the third ply.
+        assert!(weights(&mut book, &from_movetext("1.e4 e5 2.Nf3").unwrap()).is_empty());
+        assert!(weights(&mut book, &from_movetext("1.d4").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn filters_games_and_movements(){
+        let mut builder = BookBuilder::new().min_rating(2300);
+
+        assert_eq!(builder.add_pgn(GAMES.as_bytes()), 2);
+
+        let mut book = load(&builder);
+        // Black lost the only rated game with 2...Nc6.
+        assert!(weights(&mut book, &from_movetext("1.e4 e5 2.Nf3").unwrap()).is_empty());
+        assert_eq!(weights(&mut book, &from_movetext("1.e4 e5 2.Nf3 Nc6").unwrap()), vec![(String::from("f1b5"), 2)]);
+
+        let mut builder = BookBuilder::new().min_games(2);
+        builder.add_pgn(GAMES.as_bytes());
+
+        let mut book = load(&builder);
+        assert_eq!(weights(&mut book, &Game::new_classical()), vec![(String::from("e2e4"), 3)]);
+        assert!(weights(&mut book, &from_movetext("1.e4 e5 2.Nf3").unwrap()).is_empty());
+    }
+}
@@ -0,0 +1,399 @@
+use std::{fs::File, io::{Read, Seek, SeekFrom}, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::game::{Game, movement::Movement};
+
+use self::zobrist::polyglot_key;
+
+pub mod builder;
+pub mod zobrist;
+
+/// Size in bytes of a Polyglot book entry.
+pub const ENTRY_SIZE : usize = 16;
+
+/// A Polyglot book entry: a movement for the position with the given key.
+///
+/// On disk an entry is 16 big-endian bytes: the key on 8 bytes, the movement and the weight on 2 bytes each and the
+/// learning data on 4 bytes. Entries are sorted by key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookEntry {
+    key: u64,
+    raw_move: u16,
+    weight: u16,
+    learn: u32,
+}
+
+impl BookEntry {
+    pub fn new(key: u64, raw_move: u16, weight: u16, learn: u32) -> BookEntry {
+        BookEntry {
+            key,
+            raw_move,
+            weight,
+            learn,
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8; ENTRY_SIZE]) -> BookEntry {
+        BookEntry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+
+        return bytes;
+    }
+
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    pub fn raw_move(&self) -> u16 {
+        self.raw_move
+    }
+
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    pub fn learn(&self) -> u32 {
+        self.learn
+    }
+
+    /// The movement of the entry in the given position.
+    pub fn movement(&self, game: &Game) -> Result<Movement, String> {
+        return decode_move(self.raw_move, game);
+    }
+}
+
+/// Decodes a Polyglot movement.
+///
+/// From the lowest bit, the movement holds the destination file and row, the origin file and row on 3 bits each and
+/// the promotion (1 for a Knight up to 4 for a Queen). Castles are written as the King taking its own Rook, such as
+/// `e1h1`.
+pub fn decode_move(raw_move: u16, game: &Game) -> Result<Movement, String> {
+    let tile = |shift: u16| {
+        let file = (b'a' + ((raw_move >> shift) & 7) as u8) as char;
+        let rank = (b'1' + ((raw_move >> (shift + 3)) & 7) as u8) as char;
+
+        format!("{}{}", file, rank)
+    };
+
+    let promotion = match (raw_move >> 12) & 7 {
+        0 => "",
+        1 => "n",
+        2 => "b",
+        3 => "r",
+        4 => "q",
+        promotion => return Err(format!("Invalid Polyglot movement {:#06x}! Unknown promotion {}", raw_move, promotion)),
+    };
+
+    let uci = format!("{}{}{}", tile(6), tile(0), promotion);
+
+    return Movement::from_uci(&uci, game).map_err(|_| format!("Illegal Polyglot movement {}", uci));
+}
+
+/// Encodes a movement the way Polyglot books store it.
+pub fn encode_move(movement: &Movement) -> u16 {
+    let uci = movement.to_uci(true).into_bytes();
+
+    let mut raw_move = ((uci[2] - b'a') as u16)
+        | ((uci[3] - b'1') as u16) << 3
+        | ((uci[0] - b'a') as u16) << 6
+        | ((uci[1] - b'1') as u16) << 9;
+
+    if let Some(promotion) = uci.get(4) {
+        raw_move |= match promotion {
+            b'n' => 1,
+            b'b' => 2,
+            b'r' => 3,
+            _ => 4,
+        } << 12;
+    }
+
+    return raw_move;
+}
+
+/// How a movement is chosen among the book entries of a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// The entry with the highest weight.
+    Best,
+    /// A random entry, with chances proportional to the weights. Entries with no weight are never picked.
+    Weighted,
+    /// Any entry with the same chances.
+    Uniform,
+}
+
+/// A Polyglot opening book, searched directly in its source without loading it.
+///
+/// ## Examples
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use chess::{book::{Book, BookEntry, Selection, encode_move, zobrist::polyglot_key}, game::{Game, movement::Movement}};
+///
+/// let game = Game::new_classical();
+/// let e4 = Movement::from_uci("e2e4", &game).unwrap();
+///
+/// let bytes = BookEntry::new(polyglot_key(&game), encode_move(&e4), 1, 0).to_bytes();
+/// let mut book = Book::new(Cursor::new(bytes.to_vec())).unwrap();
+///
+/// assert_eq!(book.pick(&game, Selection::Weighted).unwrap().unwrap().to_uci(false), "e2e4");
+/// ```
+pub struct Book<R: Read + Seek> {
+    reader: R,
+    length: u64,
+    random: u64,
+}
+
+impl Book<File> {
+    pub fn open(path: &str) -> Result<Book<File>, String> {
+        let file = File::open(path).map_err(|e| format!("Cannot open book {}: {}", path, e))?;
+
+        return Book::new(file);
+    }
+}
+
+impl<R: Read + Seek> Book<R> {
+    pub fn new(reader: R) -> Result<Book<R>, String> {
+        let mut reader = reader;
+
+        let size = reader.seek(SeekFrom::End(0)).map_err(|e| format!("Cannot read book: {}", e))?;
+
+        if size % ENTRY_SIZE as u64 != 0 {
+            return Err(format!("Invalid book of {} bytes! Expected a multiple of {}", size, ENTRY_SIZE));
+        }
+
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+
+        return Ok(Book {
+            reader,
+            length: size / ENTRY_SIZE as u64,
+            random: seed | 1,
+        });
+    }
+
+    /// Seeds the random picks, so the same movements are chosen on every run.
+    pub fn with_seed(mut self, seed: u64) -> Book<R> {
+        // Xorshift never leaves zero.
+        self.random = seed | 1;
+        self
+    }
+
+    /// Number of entries in the book.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    fn entry_at(&mut self, index: u64) -> Result<BookEntry, String> {
+        let mut bytes = [0; ENTRY_SIZE];
+
+        if let Err(e) = self.reader.seek(SeekFrom::Start(index * ENTRY_SIZE as u64)).and_then(|_| self.reader.read_exact(&mut bytes)) {
+            return Err(format!("Cannot read book entry {}: {}", index, e));
+        }
+
+        return Ok(BookEntry::from_bytes(&bytes));
+    }
+
+    /// Every entry with the given key, found with a binary search.
+    pub fn entries_for_key(&mut self, key: u64) -> Result<Vec<BookEntry>, String> {
+        let mut low = 0;
+        let mut high = self.length;
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+
+            match self.entry_at(middle)?.key < key {
+                true => low = middle + 1,
+                false => high = middle,
+            }
+        }
+
+        let mut entries = Vec::new();
+
+        for index in low..self.length {
+            let entry = self.entry_at(index)?;
+
+            if entry.key != key {
+                break;
+            }
+
+            entries.push(entry);
+        }
+
+        return Ok(entries);
+    }
+
+    /// The book movements of the position along with their weights.
+    ///
+    /// Entries which are not legal in the position are skipped, since they can only come from another position
+    /// sharing the same key.
+    pub fn movements(&mut self, game: &Game) -> Result<Vec<(Movement, u16)>, String> {
+        let entries = self.entries_for_key(polyglot_key(game))?;
+
+        return Ok(entries.iter().filter_map(|entry| entry.movement(game).ok().map(|movement| (movement, entry.weight))).collect());
+    }
+
+    /// Chooses a book movement for the position, if the book knows it.
+    pub fn pick(&mut self, game: &Game, selection: Selection) -> Result<Option<Movement>, String> {
+        let mut movements = self.movements(game)?;
+
+        if movements.is_empty() {
+            return Ok(None);
+        }
+
+        let index = match selection {
+            Selection::Best => {
+                let best = movements.iter().map(|(_, weight)| *weight).max().unwrap_or(0);
+                movements.iter().position(|(_, weight)| *weight == best)
+            },
+            Selection::Weighted => {
+                let total : u64 = movements.iter().map(|(_, weight)| *weight as u64).sum();
+
+                match total {
+                    0 => None,
+                    _ => {
+                        let mut target = self.next_random() % total;
+
+                        movements.iter().position(|(_, weight)| {
+                            if target < *weight as u64 {
+                                return true;
+                            }
+
+                            target -= *weight as u64;
+                            return false;
+                        })
+                    },
+                }
+            },
+            Selection::Uniform => Some((self.next_random() % movements.len() as u64) as usize),
+        };
+
+        return Ok(index.map(|index| movements.swap_remove(index).0));
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+
+        return self.random;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::parser::pgn::from_movetext;
+
+    use super::*;
+
+    fn book(entries: &mut [BookEntry]) -> Book<Cursor<Vec<u8>>> {
+        entries.sort_by_key(|entry| entry.key());
+
+        let bytes = entries.iter().flat_map(|entry| entry.to_bytes()).collect();
+
+        return Book::new(Cursor::new(bytes)).unwrap().with_seed(42);
+    }
+
+    fn entry(game: &Game, uci: &str, weight: u16) -> BookEntry {
+        let movement = Movement::from_uci(uci, game).unwrap();
+
+        return BookEntry::new(polyglot_key(game), encode_move(&movement), weight, 0);
+    }
+
+    #[test]
+    fn decodes_polyglot_movements(){
+        let game = Game::from_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+
+        // e1h1, e1a1 and b7a8 promoting to a Queen.
+        assert_eq!(decode_move(0x0107, &game).unwrap().to_san(&game).unwrap(), "O-O");
+        assert_eq!(decode_move(0x0100, &game).unwrap().to_san(&game).unwrap(), "O-O-O");
+        assert_eq!(decode_move(0x4c78, &game).unwrap().to_san(&game).unwrap(), "bxa8=Q+");
+
+        assert!(decode_move(0x7c78, &game).is_err());
+        assert!(decode_move(0x0000, &game).is_err());
+
+        for movement in game.legal_movements() {
+            let raw_move = encode_move(&movement);
+
+            assert_eq!(decode_move(raw_move, &game).unwrap().to_uci(false), movement.to_uci(false));
+        }
+    }
+
+    #[test]
+    fn finds_the_entries_of_a_position(){
+        let start = Game::new_classical();
+        let sicilian = from_movetext("1.e4 c5").unwrap();
+        let french = from_movetext("1.e4 e6").unwrap();
+
+        let mut book = book(&mut [
+            entry(&start, "e2e4", 30),
+            entry(&sicilian, "g1f3", 5),
+            entry(&start, "d2d4", 20),
+            entry(&french, "d2d4", 8),
+            entry(&start, "c2c4", 0),
+        ]);
+
+        assert_eq!(book.len(), 5);
+
+        let movements : Vec<(String, u16)> = book.movements(&start).unwrap().iter().map(|(movement, weight)| (movement.to_uci(false), *weight)).collect();
+
+        assert_eq!(movements.len(), 3);
+        assert!(movements.contains(&(String::from("e2e4"), 30)));
+        assert!(movements.contains(&(String::from("c2c4"), 0)));
+
+        assert_eq!(book.pick(&sicilian, Selection::Uniform).unwrap().unwrap().to_uci(false), "g1f3");
+        assert_eq!(book.pick(&start, Selection::Best).unwrap().unwrap().to_uci(false), "e2e4");
+        assert!(book.pick(&from_movetext("1.a3").unwrap(), Selection::Weighted).unwrap().is_none());
+    }
+
+    #[test]
+    fn picks_by_weight(){
+        let start = Game::new_classical();
+
+        let mut book = book(&mut [
+            entry(&start, "e2e4", 3),
+            entry(&start, "d2d4", 1),
+            entry(&start, "c2c4", 0),
+        ]);
+
+        let mut e4 = 0;
+
+        for _ in 0..1000 {
+            let movement = book.pick(&start, Selection::Weighted).unwrap().unwrap().to_uci(false);
+
+            assert_ne!(movement, "c2c4");
+
+            if movement == "e2e4" {
+                e4 += 1;
+            }
+        }
+
+        assert!((650..850).contains(&e4), "{}", e4);
+
+        let uniform : Vec<String> = (0..100).map(|_| book.pick(&start, Selection::Uniform).unwrap().unwrap().to_uci(false)).collect();
+        assert!(uniform.contains(&String::from("c2c4")));
+    }
+
+    #[test]
+    fn rejects_truncated_books(){
+        assert!(Book::new(Cursor::new(vec![0; 17])).is_err());
+        assert!(Book::new(Cursor::new(Vec::new())).unwrap().is_empty());
+    }
+}
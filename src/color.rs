@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
 #[derive(PartialEq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color{
     White,
     Black,
@@ -0,0 +1,194 @@
+use std::{io::{BufRead, Read, Write}, sync::{Arc, Mutex, mpsc::{self, Sender}}, thread::{self, JoinHandle}};
+
+use crate::{eval::Evaluator, game::Game, search::{Limits, Search, SearchResult, StopHandle, tt::TranspositionTable}};
+
+pub mod uci;
+pub mod xboard;
+
+pub const ENGINE_NAME : &str = concat!("chess ", env!("CARGO_PKG_VERSION"));
+/// Size of the transposition table until the GUI sets another one, in megabytes.
+pub const DEFAULT_HASH_MB : usize = 16;
+
+/// Plays through the protocol the GUI speaks first: the Chess Engine Communication Protocol when it starts with
+/// `xboard`, the Universal Chess Interface otherwise.
+pub fn run<R: BufRead, W: Write + Send + 'static>(mut input: R, output: Arc<Mutex<W>>) {
+    let mut first_line = String::new();
+
+    while first_line.trim().is_empty() {
+        first_line.clear();
+
+        match input.read_line(&mut first_line) {
+            Ok(0) | Err(_) => return,
+            _ => (),
+        }
+    }
+
+    match first_line.trim() {
+        "xboard" => xboard::run(input, output),
+        _ => uci::run(first_line.as_bytes().chain(input), output),
+    }
+}
+
+/// The state the protocols drive: the game being played, the transposition table kept between moves, and the
+/// search running in the background while the protocol keeps reading commands.
+pub struct Engine {
+    game: Game,
+    table: Arc<TranspositionTable>,
+    threads: usize,
+    multi_pv: usize,
+    evaluator: Evaluator,
+    running: Option<Running>,
+}
+
+/// A search started in the background.
+struct Running {
+    stop: StopHandle,
+    /// Lets a search started `until_stopped` report its result.
+    release: Sender<()>,
+    until_stopped: bool,
+    thread: JoinHandle<()>,
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine {
+            game: Game::new_classical(),
+            table: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)),
+            threads: 1,
+            multi_pv: 1,
+            evaluator: Evaluator::Handcrafted,
+            running: None,
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn game_mut(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    pub fn set_game(&mut self, game: Game) {
+        self.game = game;
+    }
+
+    pub fn table(&self) -> &Arc<TranspositionTable> {
+        &self.table
+    }
+
+    pub fn resize_table(&mut self, size_mb: usize) {
+        self.stop();
+        self.table = Arc::new(TranspositionTable::new(size_mb));
+    }
+
+    pub fn clear_table(&mut self) {
+        self.stop();
+        self.table.clear();
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Sets the number of threads of the next searches.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn multi_pv(&self) -> usize {
+        self.multi_pv
+    }
+
+    /// Sets the number of lines the next searches rank.
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        self.multi_pv = lines.max(1);
+    }
+
+    pub fn evaluator(&self) -> &Evaluator {
+        &self.evaluator
+    }
+
+    /// Sets how the next searches score positions.
+    pub fn set_evaluator(&mut self, evaluator: Evaluator) {
+        self.evaluator = evaluator;
+    }
+
+    /// Searches the current game in another thread. `on_iteration` gets every completed iteration and `on_finish`
+    /// the final result, both from the search thread. When `until_stopped`, the result is held back until the search
+    /// gets stopped, even if it ended by itself.
+    pub fn start<I, F>(&mut self, limits: Limits, until_stopped: bool, mut on_iteration: I, on_finish: F)
+    where
+        I: FnMut(&SearchResult) + Send + 'static,
+        F: FnOnce(SearchResult) + Send + 'static,
+    {
+        self.stop();
+
+        let mut search = Search::new(limits).table(self.table.clone()).threads(self.threads).multi_pv(self.multi_pv).evaluator(self.evaluator.clone());
+        let stop = search.stop_handle();
+        let game = self.game.clone();
+
+        let (release, released) = mpsc::channel();
+
+        let thread = thread::spawn(move || {
+            let result = search.run_with(&game, |result| on_iteration(result));
+
+            if until_stopped {
+                // Returns on the release, or when the engine is dropped.
+                let _ = released.recv();
+            }
+
+            on_finish(result);
+        });
+
+        self.running = Some(Running {
+            stop,
+            release,
+            until_stopped,
+            thread,
+        });
+    }
+
+    pub fn is_searching(&self) -> bool {
+        return self.running.as_ref().is_some_and(|running| !running.thread.is_finished());
+    }
+
+    /// Stops the running search, if any, and waits until it reported its result.
+    pub fn stop(&mut self) {
+        if let Some(running) = &self.running {
+            running.stop.stop();
+            let _ = running.release.send(());
+        }
+
+        self.wait();
+    }
+
+    /// Waits until the running search ends by itself.
+    pub fn wait(&mut self) {
+        if let Some(running) = self.running.take() {
+            let _ = running.thread.join();
+        }
+    }
+
+    /// Waits for the running search once no more commands will come, stopping it if it would only end on a stop.
+    pub fn finish(&mut self) {
+        match self.running.as_ref().is_some_and(|running| running.until_stopped) {
+            true => self.stop(),
+            false => self.wait(),
+        }
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writes a line of a protocol, shared between the thread reading commands and the search thread.
+pub(crate) fn send<W: Write>(output: &Arc<Mutex<W>>, line: &str) {
+    if let Ok(mut output) = output.lock() {
+        let _ = writeln!(output, "{}", line);
+        let _ = output.flush();
+    }
+}
@@ -0,0 +1,416 @@
+use std::{io::{BufRead, Write}, sync::{Arc, Mutex}, time::Duration};
+
+use crate::{color::Color, eval::{Evaluator, nnue::Network}, game::{Game, movement::Movement}, search::{Limits, SearchResult, time::TimeControl}};
+
+use super::{DEFAULT_HASH_MB, Engine, send};
+
+const MAX_THREADS : usize = 256;
+const MAX_MULTI_PV : usize = 256;
+
+/// Plays through the Universal Chess Interface: reads commands from the input until `quit`, and writes the answers
+/// to the output.
+///
+/// At the end of the input, the running search is allowed to finish, so a scripted session can be piped in.
+///
+/// ## Examples
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+///
+/// use chess::engine::uci;
+///
+/// let commands = "uci\nisready\nposition fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 2\n";
+/// let output = Arc::new(Mutex::new(Vec::new()));
+///
+/// uci::run(commands.as_bytes(), output.clone());
+///
+/// let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+/// assert!(output.contains("uciok\nreadyok\n"));
+/// assert!(output.ends_with("bestmove a1a8\n"));
+/// ```
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: Arc<Mutex<W>>) {
+    let mut uci = Uci {
+        engine: Engine::new(),
+        output,
+        network: None,
+        use_nnue: false,
+    };
+
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        if !uci.handle(&line) {
+            uci.engine.stop();
+            return;
+        }
+    }
+
+    // The GUI closed its end, so no `stop` will ever come.
+    uci.engine.finish();
+}
+
+struct Uci<W: Write + Send + 'static> {
+    engine: Engine,
+    output: Arc<Mutex<W>>,
+    /// The network of the `EvalFile` option, only used with `UseNNUE`.
+    network: Option<Arc<Network>>,
+    use_nnue: bool,
+}
+
+impl<W: Write + Send + 'static> Uci<W> {
+    /// Runs a command. Returns false on `quit`.
+    fn handle(&mut self, line: &str) -> bool {
+        let tokens : Vec<&str> = line.split_whitespace().collect();
+
+        match tokens.first() {
+            Some(&"uci") => {
+                send(&self.output, &format!("id name {}", super::ENGINE_NAME));
+                send(&self.output, "id author the chess crate developers");
+                send(&self.output, &format!("option name Hash type spin default {} min 1 max 4096", DEFAULT_HASH_MB));
+                send(&self.output, "option name Clear Hash type button");
+                send(&self.output, &format!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS));
+                send(&self.output, &format!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTI_PV));
+                send(&self.output, "option name EvalFile type string default <empty>");
+                send(&self.output, "option name UseNNUE type check default false");
+                send(&self.output, "uciok");
+            },
+            Some(&"isready") => send(&self.output, "readyok"),
+            Some(&"setoption") => self.set_option(&tokens[1..]),
+            Some(&"ucinewgame") => {
+                self.engine.clear_table();
+                self.engine.set_game(Game::new_classical());
+            },
+            Some(&"position") => {
+                self.engine.stop();
+
+                match parse_position(&tokens[1..]) {
+                    Ok(game) => self.engine.set_game(game),
+                    Err(e) => send(&self.output, &format!("info string {}", e)),
+                }
+            },
+            Some(&"go") => self.go(&tokens[1..]),
+            Some(&"stop") => self.engine.stop(),
+            Some(&"quit") => return false,
+            _ => (),
+        }
+
+        return true;
+    }
+
+    /// Handles `setoption name <name> [value <value>]`, where the name may hold spaces.
+    fn set_option(&mut self, tokens: &[&str]) {
+        let value_index = tokens.iter().position(|token| *token == "value").unwrap_or(tokens.len());
+        let name = tokens[1.min(value_index)..value_index].join(" ").to_lowercase();
+        let value = tokens.get(value_index + 1..).map(|value| value.join(" ")).unwrap_or_default();
+
+        match name.as_str() {
+            "hash" => match value.parse::<usize>() {
+                Ok(size_mb) if size_mb > 0 => self.engine.resize_table(size_mb),
+                _ => send(&self.output, &format!("info string Invalid Hash value {}", value)),
+            },
+            "clear hash" => self.engine.clear_table(),
+            "threads" => match value.parse::<usize>() {
+                Ok(threads) if (1..=MAX_THREADS).contains(&threads) => self.engine.set_threads(threads),
+                _ => send(&self.output, &format!("info string Invalid Threads value {}", value)),
+            },
+            "multipv" => match value.parse::<usize>() {
+                Ok(lines) if (1..=MAX_MULTI_PV).contains(&lines) => self.engine.set_multi_pv(lines),
+                _ => send(&self.output, &format!("info string Invalid MultiPV value {}", value)),
+            },
+            "evalfile" => {
+                match Network::open(&value) {
+                    Ok(network) => self.network = Some(Arc::new(network)),
+                    Err(e) => send(&self.output, &format!("info string {}", e)),
+                }
+
+                self.update_evaluator();
+            },
+            "usennue" => match value.as_str() {
+                "true" | "false" => {
+                    self.use_nnue = value == "true";
+                    self.update_evaluator();
+                },
+                _ => send(&self.output, &format!("info string Invalid UseNNUE value {}", value)),
+            },
+            _ => send(&self.output, &format!("info string Unknown option {}", name)),
+        }
+    }
+
+    /// Falls back on the handcrafted evaluation until a network is loaded.
+    fn update_evaluator(&mut self) {
+        let evaluator = match (&self.network, self.use_nnue) {
+            (Some(network), true) => Evaluator::Nnue(network.clone()),
+            (None, true) => {
+                send(&self.output, "info string UseNNUE needs a network, set EvalFile first");
+                Evaluator::Handcrafted
+            },
+            (_, false) => Evaluator::Handcrafted,
+        };
+
+        self.engine.stop();
+        self.engine.set_evaluator(evaluator);
+    }
+
+    fn go(&mut self, tokens: &[&str]) {
+        let (limits, infinite) = parse_go(tokens, self.engine.game());
+
+        let info_output = self.output.clone();
+        let bestmove_output = self.output.clone();
+        let table = self.engine.table().clone();
+
+        self.engine.start(limits, infinite, move |result| {
+            send(&info_output, &info(result, table.hashfull()));
+        }, move |result| {
+            send(&bestmove_output, &bestmove(&result));
+        });
+    }
+}
+
+/// Parses the arguments of `position`: `startpos` or `fen <fen>`, then optionally `moves` and UCI movements.
+fn parse_position(tokens: &[&str]) -> Result<Game, String> {
+    let moves_index = tokens.iter().position(|token| *token == "moves").unwrap_or(tokens.len());
+
+    let mut game = match tokens.first() {
+        Some(&"startpos") => Game::new_classical(),
+        Some(&"fen") => Game::from_fen(&tokens[1..moves_index].join(" "))?,
+        _ => return Err(format!("Invalid position command: position {}", tokens.join(" "))),
+    };
+
+    for uci in tokens.iter().skip(moves_index + 1) {
+        let movement = Movement::from_uci(uci, &game)?;
+        game.play(movement)?;
+    }
+
+    return Ok(game);
+}
+
+/// Parses the arguments of `go` into the search limits, and whether the search is infinite.
+fn parse_go(tokens: &[&str], game: &Game) -> (Limits, bool) {
+    let mut limits = Limits::new();
+    let mut infinite = false;
+    let mut clock = None;
+    let mut increment = 0;
+    let mut moves_to_go = None;
+
+    let (own_time, own_increment) = match game.turn() {
+        Color::White => ("wtime", "winc"),
+        Color::Black => ("btime", "binc"),
+    };
+
+    let mut index = 0;
+
+    while index < tokens.len() {
+        let value = tokens.get(index + 1).and_then(|value| value.parse::<u64>().ok());
+
+        match (tokens[index], value) {
+            ("infinite", _) => {
+                infinite = true;
+                index += 1;
+                continue;
+            },
+            ("depth", Some(depth)) => limits = limits.depth(depth as u32),
+            ("nodes", Some(nodes)) => limits = limits.nodes(nodes),
+            ("movetime", Some(movetime)) => limits = limits.time(Duration::from_millis(movetime)),
+            ("movestogo", Some(moves)) => moves_to_go = Some(moves as u32),
+            (name, Some(time)) if name == own_time => clock = Some(time),
+            (name, Some(time)) if name == own_increment => increment = time,
+            _ => {
+                index += 1;
+                continue;
+            },
+        }
+
+        index += 2;
+    }
+
+    if let (Some(clock), false) = (clock, infinite) {
+        let mut time_control = TimeControl::new(Duration::from_millis(clock), Duration::from_millis(increment));
+
+        if let Some(moves_to_go) = moves_to_go {
+            time_control = time_control.moves_to_go(moves_to_go);
+        }
+
+        limits = limits.time_control(time_control);
+    }
+
+    return (limits, infinite);
+}
+
+/// The `info` lines of an iteration, one per line of a MultiPV search, which then tell their rank.
+fn info(result: &SearchResult, hashfull: u32) -> String {
+    let milliseconds = result.elapsed().as_millis() as u64;
+    let nodes_per_second = result.nodes() * 1000 / milliseconds.max(1);
+    let is_multi_pv = result.lines().len() > 1;
+
+    let lines : Vec<String> = result.lines().iter().enumerate().map(|(index, line)| {
+        let rank = if is_multi_pv { format!(" multipv {}", index + 1) } else { String::new() };
+        let pv : Vec<String> = line.principal_variation().iter().map(|movement| movement.to_uci(false)).collect();
+
+        return format!("info depth {}{} score {} nodes {} nps {} time {} hashfull {} pv {}", result.depth(), rank, line.score(), result.nodes(), nodes_per_second, milliseconds, hashfull, pv.join(" "));
+    }).collect();
+
+    return lines.join("\n");
+}
+
+fn bestmove(result: &SearchResult) -> String {
+    let best_move = match result.best_move() {
+        Some(movement) => movement.to_uci(false),
+        None => return String::from("bestmove 0000"),
+    };
+
+    return match result.principal_variation().get(1) {
+        Some(ponder) => format!("bestmove {} ponder {}", best_move, ponder.to_uci(false)),
+        None => format!("bestmove {}", best_move),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::{BufReader, Read}, sync::mpsc, thread, time::Instant};
+
+    use super::*;
+
+    fn session(commands: &str) -> Vec<String> {
+        let output = Arc::new(Mutex::new(Vec::new()));
+
+        run(commands.as_bytes(), output.clone());
+
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        return output.lines().map(String::from).collect();
+    }
+
+    #[test]
+    fn answers_the_handshake(){
+        let lines = session("uci\nsetoption name Hash value 1\nsetoption name Clear Hash\nsetoption name Threads value 2\nsetoption name Threads value 0\nsetoption name Ponder value true\nisready\nquit\nisready\n");
+
+        assert!(lines[0].starts_with("id name chess"));
+        assert!(lines.contains(&String::from("option name Hash type spin default 16 min 1 max 4096")));
+        assert!(lines.contains(&String::from("option name Threads type spin default 1 min 1 max 256")));
+        assert_eq!(lines[lines.len() - 4..], ["uciok", "info string Invalid Threads value 0", "info string Unknown option ponder", "readyok"]);
+    }
+
+    #[test]
+    fn sets_up_positions(){
+        let game = parse_position(&["startpos", "moves", "e2e4", "e7e5", "g1f3"]).unwrap();
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        let game = parse_position(&"fen 4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1 moves e1g1".split(' ').collect::<Vec<&str>>()).unwrap();
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/R4RK1 b - - 1 1");
+
+        assert!(parse_position(&["startpos", "moves", "e2e5"]).is_err());
+
+        let lines = session("position somewhere\nposition startpos moves e2e4\ngo depth 1\n");
+        assert_eq!(lines[0], "info string Invalid position command: position somewhere");
+        assert!(lines[1].starts_with("info depth 1 score cp "));
+        assert!(lines[2].starts_with("bestmove "));
+    }
+
+    #[test]
+    fn searches_within_limits(){
+        let lines = session("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 3\n");
+        assert!(lines[0].starts_with("info depth 1 score mate 1 nodes "));
+        assert!(lines[0].ends_with(" pv a1a8"));
+        assert_eq!(lines.last().unwrap(), "bestmove a1a8");
+
+        let lines = session("position fen 6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1\ngo nodes 1\n");
+        assert!(lines.last().unwrap().starts_with("bestmove "));
+
+        let lines = session("setoption name MultiPV value 3\nposition fen q5k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 2\n");
+        assert!(lines[0].starts_with("info depth 1 multipv 1 score mate 1 "));
+        assert!(lines[1].starts_with("info depth 1 multipv 2 score cp "));
+        assert!(lines[0].ends_with(" pv a1a8"));
+        assert!(lines[3].starts_with("info depth 2 multipv 1 score mate 1 "));
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines.last().unwrap(), "bestmove a1a8");
+
+        let lines = session("setoption name Threads value 3\nposition fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 3\n");
+        assert_eq!(lines.last().unwrap(), "bestmove a1a8");
+
+        let lines = session("position fen R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1\ngo depth 3\n");
+        assert_eq!(lines, ["bestmove 0000"]);
+
+        let start = Instant::now();
+        session("position startpos\ngo movetime 100\n");
+        session("position startpos moves e2e4\ngo wtime 10000 btime 3000 winc 0 binc 0\n");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn stops_infinite_searches(){
+        let (sender, receiver) = mpsc::channel::<String>();
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let session_output = output.clone();
+
+        // Feeds the commands one at a time, as a GUI would.
+        struct Commands(mpsc::Receiver<String>, Vec<u8>);
+
+        impl Read for Commands {
+            fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+                if self.1.is_empty() {
+                    match self.0.recv() {
+                        Ok(line) => self.1 = line.into_bytes(),
+                        Err(_) => return Ok(0),
+                    }
+                }
+
+                let length = buffer.len().min(self.1.len());
+                buffer[..length].copy_from_slice(&self.1[..length]);
+                self.1.drain(..length);
+
+                return Ok(length);
+            }
+        }
+
+        let engine = thread::spawn(move || run(BufReader::new(Commands(receiver, Vec::new())), session_output));
+
+        sender.send(String::from("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo infinite\n")).unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        // The mate was found long ago, but the best move waits for the stop.
+        assert!(!String::from_utf8(output.lock().unwrap().clone()).unwrap().contains("bestmove"));
+
+        sender.send(String::from("stop\n")).unwrap();
+        sender.send(String::from("quit\n")).unwrap();
+        engine.join().unwrap();
+
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        assert!(output.ends_with("bestmove a1a8\n"));
+    }
+
+    #[test]
+    fn stops_infinite_searches_at_the_end_of_input(){
+        let lines = session("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo infinite\n");
+
+        // Stopped as soon as the input ends, maybe before the first iteration completed.
+        assert!(lines.last().is_some_and(|line| line.starts_with("bestmove ")), "{:?}", lines);
+    }
+
+    #[test]
+    fn evaluates_with_a_network(){
+        // The smallest network, scoring every position 100 centipawns for the side to move.
+        let mut bytes = b"NNUE".to_vec();
+        [1u32, 1, 1, 1].iter().for_each(|value| bytes.extend(value.to_le_bytes()));
+        bytes.resize(bytes.len() + 2 * (40960 + 1) + 6 + 5, 0);
+        bytes.extend(1600i32.to_le_bytes());
+        bytes.push(0);
+
+        let path = std::env::temp_dir().join(format!("chess-uci-{}.nnue", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+
+        let lines = session(&format!("setoption name UseNNUE value true\nsetoption name EvalFile value /nonexistent.nnue\nsetoption name EvalFile value {}\nposition startpos\ngo depth 1\n", path.display()));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(lines[0], "info string UseNNUE needs a network, set EvalFile first");
+        assert!(lines[1].starts_with("info string Cannot read network /nonexistent.nnue"));
+        assert!(lines[3].starts_with("info depth 1 score cp -100 "));
+
+        // Back to the handcrafted evaluation.
+        let lines = session("uci\nsetoption name UseNNUE value false\nposition startpos\ngo depth 1\n");
+        assert!(lines.contains(&String::from("option name EvalFile type string default <empty>")));
+        assert!(!lines.iter().any(|line| line.contains("score cp -100 ")));
+    }
+}
+
@@ -0,0 +1,409 @@
+use std::{io::{BufRead, Write}, sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}}, time::Duration};
+
+use crate::{color::Color, game::{Game, movement::Movement}, search::{Limits, Score, SearchResult, time::TimeControl}};
+
+use super::{ENGINE_NAME, Engine, send};
+
+/// Plays through the Chess Engine Communication Protocol, version 2, once the GUI sent `xboard`: reads commands from
+/// the input until `quit`, and writes the answers to the output.
+///
+/// At the end of the input, the running search is allowed to finish, so a scripted session can be piped in.
+///
+/// ## Examples
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+///
+/// use chess::engine::xboard;
+///
+/// let commands = "protover 2\nnew\nsetboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\nsd 2\ngo\n";
+/// let output = Arc::new(Mutex::new(Vec::new()));
+///
+/// xboard::run(commands.as_bytes(), output.clone());
+///
+/// let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+/// assert!(output.ends_with("move a1a8\n1-0 {White mates}\n"));
+/// ```
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: Arc<Mutex<W>>) {
+    let mut xboard = Xboard::new(output);
+
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        if !xboard.handle(&line) {
+            xboard.abort();
+            return;
+        }
+    }
+
+    xboard.engine.wait();
+    xboard.apply_pending();
+}
+
+struct Xboard<W: Write + Send + 'static> {
+    engine: Engine,
+    output: Arc<Mutex<W>>,
+    /// The side the engine plays, None in force mode where it only records the movements.
+    engine_color: Option<Color>,
+    depth: Option<u32>,
+    /// Milliseconds per move, set by `st`.
+    move_time: Option<u64>,
+    /// Moves per time control set by `level`, 0 when the time is for the whole game.
+    moves_per_session: u64,
+    /// Milliseconds added after each move.
+    increment: u64,
+    /// Milliseconds left on the engine clock, set by `time`.
+    clock: Option<u64>,
+    post: bool,
+    /// The movement the search played, in UCI notation, until it is applied to the game.
+    pending: Arc<Mutex<Option<String>>>,
+    /// Set while a search is aborted, so it ends without playing.
+    discard: Arc<AtomicBool>,
+}
+
+impl<W: Write + Send + 'static> Xboard<W> {
+    fn new(output: Arc<Mutex<W>>) -> Xboard<W> {
+        Xboard {
+            engine: Engine::new(),
+            output,
+            engine_color: Some(Color::Black),
+            depth: None,
+            move_time: None,
+            moves_per_session: 0,
+            increment: 0,
+            clock: None,
+            post: false,
+            pending: Arc::new(Mutex::new(None)),
+            discard: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Runs a command. Returns false on `quit`.
+    fn handle(&mut self, line: &str) -> bool {
+        self.apply_pending();
+
+        let (command, arguments) = match line.trim().split_once(' ') {
+            Some((command, arguments)) => (command, arguments.trim()),
+            None => (line.trim(), ""),
+        };
+
+        match command {
+            "protover" => send(&self.output, &format!("feature myname=\"{}\" setboard=1 usermove=1 ping=1 smp=1 sigint=0 sigterm=0 colors=0 analyze=0 done=1", ENGINE_NAME)),
+            "new" => {
+                self.abort();
+                self.engine.set_game(Game::new_classical());
+                self.engine_color = Some(Color::Black);
+                self.depth = None;
+                self.move_time = None;
+            },
+            "setboard" => {
+                self.abort();
+
+                match Game::from_fen(arguments) {
+                    Ok(game) => self.engine.set_game(game),
+                    Err(e) => send(&self.output, &format!("tellusererror Illegal position: {}", e)),
+                }
+            },
+            "usermove" => self.user_move(arguments),
+            "go" => {
+                self.abort();
+                self.engine_color = Some(*self.engine.game().turn());
+                self.think();
+            },
+            "force" | "result" => {
+                self.abort();
+                self.engine_color = None;
+            },
+            "?" => {
+                self.engine.stop();
+                self.apply_pending();
+            },
+            "level" => self.set_level(arguments),
+            "st" => self.move_time = arguments.parse::<f64>().ok().map(|seconds| (seconds * 1000.0) as u64),
+            "sd" => self.depth = arguments.parse().ok(),
+            "cores" => if let Ok(cores) = arguments.parse() {
+                self.engine.set_threads(cores);
+            },
+            "time" => self.clock = arguments.parse::<u64>().ok().map(|centiseconds| centiseconds * 10),
+            "undo" => self.take_back(1),
+            "remove" => self.take_back(2),
+            "ping" => send(&self.output, &format!("pong {}", arguments)),
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "quit" => return false,
+            // The opponent clock and the other notifications do not change how the engine plays.
+            _ => (),
+        }
+
+        return true;
+    }
+
+    fn user_move(&mut self, uci: &str) {
+        // The GUI only sends movements on the user's turn, so a search still running is about to play.
+        self.engine.wait();
+        self.apply_pending();
+
+        let mut game = self.engine.game().clone();
+        let played = Movement::from_uci(uci, &game).and_then(|movement| game.play(movement));
+
+        if played.is_err() {
+            send(&self.output, &format!("Illegal move: {}", uci));
+            return;
+        }
+
+        self.engine.set_game(game);
+
+        if self.engine_color.as_ref() == Some(self.engine.game().turn()) {
+            self.think();
+        }
+    }
+
+    /// Handles `level MPS BASE INC`, where the base time is in minutes or in `minutes:seconds`.
+    fn set_level(&mut self, arguments: &str) {
+        let fields : Vec<&str> = arguments.split_whitespace().collect();
+
+        if fields.len() != 3 {
+            send(&self.output, &format!("Error (invalid level): {}", arguments));
+            return;
+        }
+
+        self.moves_per_session = fields[0].parse().unwrap_or(0);
+        self.increment = fields[2].parse::<f64>().map(|seconds| (seconds * 1000.0) as u64).unwrap_or(0);
+        self.move_time = None;
+
+        let base = match fields[1].split_once(':') {
+            Some((minutes, seconds)) => minutes.parse::<u64>().unwrap_or(0) * 60 + seconds.parse::<u64>().unwrap_or(0),
+            None => fields[1].parse::<u64>().unwrap_or(0) * 60,
+        };
+
+        self.clock = Some(base * 1000);
+    }
+
+    fn take_back(&mut self, count: usize) {
+        self.abort();
+
+        let game = self.engine.game();
+        let kept = game.movements().len().saturating_sub(count);
+
+        let replayed = Game::from_fen(game.start_fen()).and_then(|mut replay| {
+            for movement in game.movements().iter().take(kept) {
+                replay.play(movement.clone())?;
+            }
+
+            return Ok(replay);
+        });
+
+        match replayed {
+            Ok(game) => self.engine.set_game(game),
+            Err(e) => send(&self.output, &format!("Error (cannot undo): {}", e)),
+        }
+    }
+
+    fn think(&mut self) {
+        let game = self.engine.game().clone();
+
+        if game.legal_movements().is_empty() {
+            return;
+        }
+
+        let info_output = self.output.clone();
+        let move_output = self.output.clone();
+        let pending = self.pending.clone();
+        let discard = self.discard.clone();
+        let post = self.post;
+
+        self.engine.start(self.limits(), false, move |result| {
+            if post {
+                send(&info_output, &thinking(result));
+            }
+        }, move |result| {
+            let movement = match result.best_move() {
+                Some(movement) if !discard.load(Ordering::Relaxed) => movement.clone(),
+                _ => return,
+            };
+
+            let mut game = game;
+            let uci = movement.to_uci(false);
+
+            if let Ok(mut pending) = pending.lock() {
+                *pending = Some(uci.clone());
+            }
+
+            send(&move_output, &format!("move {}", uci));
+
+            if game.play(movement).is_ok() {
+                if let Some(result) = game_result(&game) {
+                    send(&move_output, result);
+                }
+            }
+        });
+    }
+
+    fn limits(&self) -> Limits {
+        let mut limits = Limits::new();
+
+        if let Some(depth) = self.depth {
+            limits = limits.depth(depth);
+        }
+
+        if let Some(move_time) = self.move_time {
+            return limits.time(Duration::from_millis(move_time));
+        }
+
+        if let Some(clock) = self.clock {
+            let mut time_control = TimeControl::new(Duration::from_millis(clock), Duration::from_millis(self.increment));
+
+            if self.moves_per_session > 0 {
+                let played = self.engine.game().fullmove_number().saturating_sub(1) as u64;
+                time_control = time_control.moves_to_go((self.moves_per_session - played % self.moves_per_session) as u32);
+            }
+
+            limits = limits.time_control(time_control);
+        }
+
+        return limits;
+    }
+
+    /// Plays the movement the last search chose.
+    fn apply_pending(&mut self) {
+        let uci = match self.pending.lock().ok().and_then(|mut pending| pending.take()) {
+            Some(uci) => uci,
+            None => return,
+        };
+
+        self.engine.wait();
+
+        let game = self.engine.game_mut();
+
+        if let Ok(movement) = Movement::from_uci(&uci, game) {
+            let _ = game.play(movement);
+        }
+    }
+
+    /// Stops the running search without playing its movement.
+    fn abort(&mut self) {
+        self.discard.store(true, Ordering::Relaxed);
+        self.engine.stop();
+        self.discard.store(false, Ordering::Relaxed);
+    }
+}
+
+/// A thinking line: the depth, the score in centipawns, the time in centiseconds, the nodes and the variation.
+fn thinking(result: &SearchResult) -> String {
+    let score = match result.score() {
+        Score::Centipawns(centipawns) => centipawns,
+        Score::Mate(moves) if moves > 0 => 100000 + moves,
+        Score::Mate(moves) => -100000 + moves,
+    };
+
+    let pv : Vec<String> = result.principal_variation().iter().map(|movement| movement.to_uci(false)).collect();
+
+    return format!("{} {} {} {} {}", result.depth(), score, result.elapsed().as_millis() / 10, result.nodes(), pv.join(" "));
+}
+
+fn game_result(game: &Game) -> Option<&'static str> {
+    if game.is_checkmate() {
+        return match game.turn() {
+            Color::White => Some("0-1 {Black mates}"),
+            Color::Black => Some("1-0 {White mates}"),
+        };
+    }
+
+    if game.is_stalemate() {
+        return Some("1/2-1/2 {Stalemate}");
+    }
+
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(commands: &str) -> Vec<String> {
+        let output = Arc::new(Mutex::new(Vec::new()));
+
+        super::super::run(commands.as_bytes(), output.clone());
+
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        return output.lines().map(String::from).collect();
+    }
+
+    #[test]
+    fn negotiates_features(){
+        let lines = session("xboard\nprotover 2\naccepted setboard\nping 7\nquit\nping 8\n");
+
+        assert!(lines[0].starts_with("feature myname=\"chess "));
+        assert!(lines[0].ends_with(" done=1"));
+        assert!(lines[0].contains(" smp=1 "));
+        assert_eq!(lines[1], "pong 7");
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn plays_against_the_user(){
+        // The engine plays Black after `new`, and answers each movement of the user.
+        let lines = session("xboard\nnew\nsd 1\nusermove e2e4\nusermove e1e3\nping 1\n");
+        assert!(lines[0].starts_with("move "));
+        assert_eq!(lines[1..], ["Illegal move: e1e3", "pong 1"]);
+
+        // In force mode it only records the movements, until `go` makes it play the side to move.
+        let lines = session("xboard\nnew\nforce\nusermove e2e4\nusermove e7e5\nsd 2\npost\ngo\n");
+        assert!(lines[0].starts_with("1 "));
+        assert!(lines[1].starts_with("2 "));
+        assert!(lines[2].starts_with("move "));
+
+        let lines = session("xboard\nsetboard 6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1\nsd 1\nusermove g8f8\nforce\nusermove a1a8\nresult 1-0 {White mates}\n");
+        assert!(lines.is_empty());
+
+        let lines = session("xboard\nsetboard nonsense\n");
+        assert!(lines[0].starts_with("tellusererror Illegal position"));
+    }
+
+    #[test]
+    fn takes_movements_back(){
+        let lines = session("xboard\nnew\nforce\nusermove e2e4\nusermove e7e5\nundo\nusermove c7c5\nremove\nusermove d2d4\nsd 1\ngo\n");
+
+        // Back to the start position with White to move, which then plays d4, so the engine answers as Black.
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("move "));
+
+        let mut xboard = Xboard::new(Arc::new(Mutex::new(Vec::new())));
+
+        xboard.handle("force");
+        xboard.handle("usermove e2e4");
+        xboard.handle("usermove e7e5");
+        xboard.handle("undo");
+        assert_eq!(xboard.engine.game().movements().len(), 1);
+        xboard.handle("remove");
+        assert_eq!(xboard.engine.game().movements().len(), 0);
+    }
+
+    #[test]
+    fn follows_time_controls(){
+        let mut xboard = Xboard::new(Arc::new(Mutex::new(Vec::new())));
+
+        xboard.handle("level 40 0:30 0");
+        assert_eq!((xboard.moves_per_session, xboard.clock, xboard.increment), (40, Some(30000), 0));
+
+        xboard.handle("level 0 2 12");
+        assert_eq!((xboard.moves_per_session, xboard.clock, xboard.increment), (0, Some(120000), 12000));
+
+        xboard.handle("time 500");
+        xboard.handle("otim 700");
+        assert_eq!(xboard.clock, Some(5000));
+
+        xboard.handle("st 2");
+        assert_eq!(xboard.move_time, Some(2000));
+
+        xboard.handle("sd 3");
+        assert_eq!(xboard.depth, Some(3));
+
+        // A position set up with a fullmove number of 0 counts as the first move of the session.
+        let lines = session("xboard\nsetboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 0\nlevel 40 5 0\ngo\n");
+        assert_eq!(lines[lines.len() - 2..], ["move a1a8", "1-0 {White mates}"]);
+    }
+}
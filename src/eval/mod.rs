@@ -0,0 +1,430 @@
+use std::{fmt::Display, sync::Arc};
+
+use crate::{board::{Board, position::Position}, color::Color, game::Game, piece::{Piece, pieces::pawn::Pawn}};
+
+use self::nnue::Network;
+
+pub mod nnue;
+
+/// Phase of the starting position: 1 per minor piece, 2 per rook and 4 per queen.
+const MAX_PHASE : i32 = 24;
+
+/// Piece-square tables from White's point of view, the eighth rank first so they read like a diagram.
+const PAWN_TABLE : [[i32; 64]; 2] = [
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         10,  10,  20,  30,  30,  20,  10,  10,
+          5,   5,  10,  25,  25,  10,   5,   5,
+          0,   0,   0,  20,  20,   0,   0,   0,
+          5,  -5, -10,   0,   0, -10,  -5,   5,
+          5,  10,  10, -20, -20,  10,  10,   5,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         80,  80,  80,  80,  80,  80,  80,  80,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         30,  30,  30,  30,  30,  30,  30,  30,
+         20,  20,  20,  20,  20,  20,  20,  20,
+         10,  10,  10,  10,  10,  10,  10,  10,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+];
+
+const KNIGHT_TABLE : [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+const BISHOP_TABLE : [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+const ROOK_TABLE : [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+const QUEEN_TABLE : [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+/// The king hides behind its pawns in the middlegame and walks to the center in the endgame.
+const KING_TABLE : [[i32; 64]; 2] = [
+    [
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -20, -30, -30, -40, -40, -30, -30, -20,
+        -10, -20, -20, -20, -20, -20, -20, -10,
+         20,  20,   0,   0,   0,   0,  20,  20,
+         20,  30,  10,   0,   0,  10,  30,  20,
+    ],
+    [
+        -50, -40, -30, -20, -20, -30, -40, -50,
+        -30, -20, -10,   0,   0, -10, -20, -30,
+        -30, -10,  20,  30,  30,  20, -10, -30,
+        -30, -10,  30,  40,  40,  30, -10, -30,
+        -30, -10,  30,  40,  40,  30, -10, -30,
+        -30, -10,  20,  30,  30,  20, -10, -30,
+        -30, -30,   0,   0,   0,   0, -30, -30,
+        -50, -30, -30, -30, -30, -30, -30, -50,
+    ],
+];
+
+/// Bonus per reachable square, in the middlegame and in the endgame.
+fn mobility_weight(prefix: &str) -> (i32, i32) {
+    return match prefix {
+        "N" => (4, 4),
+        "B" => (5, 5),
+        "R" => (2, 4),
+        "Q" => (1, 2),
+        _ => (0, 0),
+    };
+}
+
+fn phase_weight(prefix: &str) -> i32 {
+    return match prefix {
+        "N" | "B" => 1,
+        "R" => 2,
+        "Q" => 4,
+        _ => 0,
+    };
+}
+
+/// A middlegame and an endgame score, from White's point of view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Score {
+    middlegame: i32,
+    endgame: i32,
+}
+
+impl Score {
+    fn add(&mut self, color: &Color, middlegame: i32, endgame: i32) {
+        let sign = match color {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+
+        self.middlegame += sign * middlegame;
+        self.endgame += sign * endgame;
+    }
+
+    /// Blends both scores by the phase, from the full middlegame at [`MAX_PHASE`] to the bare endgame at 0.
+    fn taper(&self, phase: i32) -> i32 {
+        return (self.middlegame * phase + self.endgame * (MAX_PHASE - phase)) / MAX_PHASE;
+    }
+}
+
+/// The terms of a static evaluation, in centipawns from the point of view of the side to move.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::{eval, game::Game};
+///
+/// // White is a knight up.
+/// let game = Game::from_fen("rnbqkb1r/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+///
+/// let evaluation = eval::breakdown(&game);
+///
+/// assert_eq!(evaluation.material(), 300);
+/// assert!(evaluation.total() > 200);
+/// assert_eq!(evaluation.total(), eval::evaluate(&game));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evaluation {
+    material: i32,
+    piece_squares: i32,
+    mobility: i32,
+    king_safety: i32,
+    phase: i32,
+}
+
+impl Evaluation {
+    /// The piece values, 100 centipawns per pawn.
+    pub fn material(&self) -> i32 {
+        self.material
+    }
+
+    /// How well the pieces are placed.
+    pub fn piece_squares(&self) -> i32 {
+        self.piece_squares
+    }
+
+    /// How many squares the knights, bishops, rooks and queens reach.
+    pub fn mobility(&self) -> i32 {
+        self.mobility
+    }
+
+    /// The pawns sheltering each king and the attacks around it.
+    pub fn king_safety(&self) -> i32 {
+        self.king_safety
+    }
+
+    /// From 24 with every piece on the board down to 0 with only kings and pawns.
+    pub fn phase(&self) -> i32 {
+        self.phase
+    }
+
+    pub fn total(&self) -> i32 {
+        return self.material + self.piece_squares + self.mobility + self.king_safety;
+    }
+}
+
+impl Display for Evaluation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Material      {:>6}", self.material)?;
+        writeln!(f, "Piece squares {:>6}", self.piece_squares)?;
+        writeln!(f, "Mobility      {:>6}", self.mobility)?;
+        writeln!(f, "King safety   {:>6}", self.king_safety)?;
+        writeln!(f, "Phase         {:>6}", self.phase)?;
+        write!(f, "Total         {:>6}", self.total())
+    }
+}
+
+/// How the search scores the positions it stops at.
+#[derive(Clone, Default)]
+pub enum Evaluator {
+    /// The terms of [`breakdown`].
+    #[default]
+    Handcrafted,
+    /// A neural network, updated incrementally along the searched line.
+    Nnue(Arc<Network>),
+}
+
+/// Scores a position in centipawns from the point of view of the side to move.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::{eval, game::Game};
+///
+/// assert_eq!(eval::evaluate(&Game::new_classical()), 0);
+/// ```
+pub fn evaluate(game: &Game) -> i32 {
+    return breakdown(game).total();
+}
+
+/// Scores a position term by term, see [`Evaluation`].
+pub fn breakdown(game: &Game) -> Evaluation {
+    let board = game.board();
+
+    let mut material = Score::default();
+    let mut piece_squares = Score::default();
+    let mut mobility = Score::default();
+    let mut king_safety = Score::default();
+    let mut phase = 0;
+
+    for (position, piece) in board.pieces() {
+        let color = piece.color();
+        let value = piece.value() as i32 * 100;
+        let (middlegame, endgame) = piece_square(piece, &position);
+        let (middlegame_weight, endgame_weight) = mobility_weight(piece.prefix());
+        let squares = match middlegame_weight {
+            0 => 0,
+            _ => reachable_squares(board, piece, &position),
+        };
+
+        material.add(color, value, value);
+        piece_squares.add(color, middlegame, endgame);
+        mobility.add(color, middlegame_weight * squares, endgame_weight * squares);
+        phase += phase_weight(piece.prefix());
+    }
+
+    for color in [Color::White, Color::Black] {
+        king_safety.add(&color, king_shelter(board, &color), 0);
+    }
+
+    let phase = phase.min(MAX_PHASE);
+    let sign = match game.turn() {
+        Color::White => 1,
+        Color::Black => -1,
+    };
+
+    return Evaluation {
+        material: sign * material.taper(phase),
+        piece_squares: sign * piece_squares.taper(phase),
+        mobility: sign * mobility.taper(phase),
+        king_safety: sign * king_safety.taper(phase),
+        phase,
+    };
+}
+
+/// Index of a position in the tables, mirrored for Black.
+fn table_index(color: &Color, position: &Position) -> usize {
+    let row = match color {
+        Color::White => 8 - position.rank(),
+        Color::Black => position.rank() - 1,
+    };
+
+    return row as usize * 8 + position.file() as usize - 1;
+}
+
+fn piece_square(piece: &dyn Piece, position: &Position) -> (i32, i32) {
+    let index = table_index(piece.color(), position);
+
+    return match piece.prefix() {
+        "P" => (PAWN_TABLE[0][index], PAWN_TABLE[1][index]),
+        "N" => (KNIGHT_TABLE[index], KNIGHT_TABLE[index]),
+        "B" => (BISHOP_TABLE[index], BISHOP_TABLE[index]),
+        "R" => (ROOK_TABLE[index], ROOK_TABLE[index]),
+        "Q" => (QUEEN_TABLE[index], QUEEN_TABLE[index]),
+        "K" => (KING_TABLE[0][index], KING_TABLE[1][index]),
+        _ => (0, 0),
+    };
+}
+
+/// Squares the piece could move to or capture on, ignoring pins.
+fn reachable_squares(board: &Board, piece: &dyn Piece, from: &Position) -> i32 {
+    let mut squares = 0;
+
+    for relative_position in piece.possible_moves() {
+        let to = match Position::from_relative(*from, relative_position) {
+            Ok(to) => to,
+            Err(_) => continue,
+        };
+
+        let is_own_piece = match board.get_piece_at(&to) {
+            Some(other) => other.color() == piece.color(),
+            None => false,
+        };
+
+        if !is_own_piece && piece.will_colide(board, from, &to).is_ok() {
+            squares += 1;
+        }
+    }
+
+    return squares;
+}
+
+/// Middlegame bonus for the pawns in front of the king, minus a penalty for every square around it the opponent
+/// attacks.
+fn king_shelter(board: &Board, color: &Color) -> i32 {
+    let king = match board.king_position(color) {
+        Some(king) => king,
+        None => return 0,
+    };
+
+    let forward : i8 = match color {
+        Color::White => 1,
+        Color::Black => -1,
+    };
+
+    let mut score = 0;
+
+    for file in king.file() as i8 - 1..=king.file() as i8 + 1 {
+        let is_own_pawn = |distance: i8| {
+            let rank = king.rank() as i8 + forward * distance;
+
+            if !(1..=8).contains(&file) || !(1..=8).contains(&rank) {
+                return false;
+            }
+
+            return match board.get_piece_at(&Position::new(file as u8, rank as u8).unwrap()) {
+                Some(piece) => piece.prefix() == Pawn::prefix() && piece.color() == color,
+                None => false,
+            };
+        };
+
+        if !(1..=8).contains(&file) {
+            continue;
+        }
+
+        score += match (is_own_pawn(1), is_own_pawn(2)) {
+            (true, _) => 10,
+            (false, true) => 5,
+            (false, false) => -15,
+        };
+    }
+
+    for file in king.file() as i8 - 1..=king.file() as i8 + 1 {
+        for rank in king.rank() as i8 - 1..=king.rank() as i8 + 1 {
+            if !(1..=8).contains(&file) || !(1..=8).contains(&rank) {
+                continue;
+            }
+
+            if board.is_attacked(&Position::new(file as u8, rank as u8).unwrap(), &color.opposite()) {
+                score -= 10;
+            }
+        }
+    }
+
+    return score;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate_fen(fen: &str) -> i32 {
+        return evaluate(&Game::from_fen(fen).unwrap());
+    }
+
+    #[test]
+    fn scores_for_the_side_to_move(){
+        assert_eq!(evaluate(&Game::new_classical()), 0);
+
+        let white = evaluate_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let black = evaluate_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1");
+
+        assert!(white > 800);
+        assert_eq!(white, -black);
+
+        // The same position with the colors swapped.
+        assert_eq!(evaluate_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"),
+                   evaluate_fen("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3"));
+    }
+
+    #[test]
+    fn blends_middlegame_and_endgame(){
+        let evaluation = breakdown(&Game::new_classical());
+        assert_eq!(evaluation.phase(), MAX_PHASE);
+
+        // With only kings and pawns, a central king is worth more than a sheltered one.
+        let endgame = breakdown(&Game::from_fen("8/pp6/8/4k3/8/8/PP6/6K1 w - - 0 1").unwrap());
+        assert_eq!(endgame.phase(), 0);
+        assert_eq!(endgame.king_safety(), 0);
+        assert!(endgame.piece_squares() < 0);
+    }
+
+    #[test]
+    fn rewards_mobility_and_king_safety(){
+        let evaluation = breakdown(&Game::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap());
+        assert!(evaluation.mobility() < 0);
+
+        // Black castled behind its pawns, White pushed the pawns in front of its king.
+        let evaluation = breakdown(&Game::from_fen("r4rk1/ppp2ppp/8/8/8/6PP/PPP2P2/R4RK1 w - - 0 1").unwrap());
+        assert!(evaluation.king_safety() < 0);
+        assert_eq!(evaluation.total(), evaluation.material() + evaluation.piece_squares() + evaluation.mobility() + evaluation.king_safety());
+    }
+}
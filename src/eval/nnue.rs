@@ -0,0 +1,770 @@
+//! An efficiently updatable neural network, which scores positions instead of the handcrafted terms of
+//! [`evaluate`](super::evaluate) when the search is given one.
+//!
+//! The network sees the board from both sides. For each side, the features are the pieces other than the kings,
+//! each one paired with the square of the king of that side: 64 king squares times 10 kinds of pieces, 5 of each
+//! color, times 64 squares, 40960 features in all. Black sees the board with the ranks mirrored and the colors
+//! swapped, so both sides read their own pieces the same way. The feature transformer adds up the weights of the
+//! active features into an accumulator per side, which only changes a little from one position to the next, so the
+//! search updates it incrementally instead of adding everything up again.
+//!
+//! The accumulator of the side to move, then the other one, both clipped to `0..=127`, go through two hidden layers,
+//! whose sums are shifted right by 6 and clipped to `0..=127`, and an output layer, whose sum divided by 16 is the
+//! score in centipawns for the side to move.
+//!
+//! ## File format
+//!
+//! Every number is little-endian, and the weights of each layer are stored output by output.
+//!
+//! | Field | Type | Count |
+//! | --- | --- | --- |
+//! | Magic | bytes | `NNUE` |
+//! | Version | u32 | 1 |
+//! | Transformer size `T` | u32 | 1 |
+//! | First hidden layer size `H1` | u32 | 1 |
+//! | Second hidden layer size `H2` | u32 | 1 |
+//! | Transformer biases | i16 | `T` |
+//! | Transformer weights, feature by feature | i16 | `40960 × T` |
+//! | First hidden layer biases | i32 | `H1` |
+//! | First hidden layer weights | i8 | `H1 × 2T` |
+//! | Second hidden layer biases | i32 | `H2` |
+//! | Second hidden layer weights | i8 | `H2 × H1` |
+//! | Output bias | i32 | 1 |
+//! | Output weights | i8 | `H2` |
+//!
+//! Feature `f` of a side with its king on square `k` and a piece of kind `p` (0 for Pawn to 4 for Queen) on square
+//! `s`, squares numbered from 0 for a1 to 63 for h8 and mirrored for Black, is
+//! `f = (k × 10 + p × 2 + c) × 64 + s`, where `c` is 0 for the pieces of that side and 1 for the other ones.
+//!
+//! The hot loops use AVX2 when the CPU supports it, and plain scalar code otherwise, with the same results.
+//!
+//! ## Examples
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use chess::{eval::{Evaluator, nnue::Network}, game::Game, search::{Limits, Search}};
+//!
+//! let network = Arc::new(Network::open("network.nnue").unwrap());
+//!
+//! let result = Search::new(Limits::new().depth(6)).evaluator(Evaluator::Nnue(network)).run(&Game::new_classical());
+//! ```
+
+use std::{fs, sync::Arc};
+
+use crate::{board::{Board, position::Position}, color::Color, game::{Game, movement::Movement}, piece::Piece};
+
+const MAGIC : [u8; 4] = *b"NNUE";
+const VERSION : u32 = 1;
+
+/// Features of each side: king squares times kinds of pieces times squares.
+pub const FEATURES : usize = 64 * 10 * 64;
+/// Largest transformer and hidden layer sizes, against corrupted files asking for absurd allocations.
+const MAX_SIZE : usize = 4096;
+
+const ACTIVATION_MAX : i32 = 127;
+/// The hidden layer weights are fixed point numbers with that many fractional bits.
+const WEIGHT_SHIFT : u32 = 6;
+const OUTPUT_DIVISOR : i32 = 16;
+
+/// Pieces by square, from 0 for a1 to 63 for h8: 0 for an empty square, 1 + 2 × kind + color otherwise, the kinds
+/// going from 0 for Pawn to 5 for King, and the color being 0 for White and 1 for Black.
+type Squares = [u8; 64];
+
+const EMPTY : u8 = 0;
+const PAWN : u8 = 0;
+const KING : u8 = 5;
+
+/// Plies the accumulator stack holds before growing, more than the search usually reaches.
+const STACK_PLIES : usize = 128;
+
+/// A fully connected layer, with int8 weights and int32 biases.
+#[derive(Clone, PartialEq, Eq)]
+struct Layer {
+    inputs: usize,
+    biases: Vec<i32>,
+    weights: Vec<i8>,
+}
+
+impl Layer {
+    fn read(reader: &mut Reader, inputs: usize, outputs: usize) -> Result<Layer, String> {
+        return Ok(Layer {
+            inputs,
+            biases: reader.i32s(outputs)?,
+            weights: reader.i8s(inputs * outputs)?,
+        });
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        self.biases.iter().for_each(|bias| bytes.extend(bias.to_le_bytes()));
+        self.weights.iter().for_each(|weight| bytes.extend(weight.to_le_bytes()));
+    }
+
+    fn forward(&self, inputs: &[u8]) -> Vec<i32> {
+        return self.biases.iter().enumerate().map(|(output, bias)| {
+            bias + dot(inputs, &self.weights[output * self.inputs..(output + 1) * self.inputs])
+        }).collect();
+    }
+}
+
+/// A quantized HalfKP network, see the [module](self) for its architecture and file format.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Network {
+    transformer_biases: Vec<i16>,
+    transformer_weights: Vec<i16>,
+    hidden: [Layer; 2],
+    output: Layer,
+}
+
+impl Network {
+    pub fn open(path: &str) -> Result<Network, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Cannot read network {}: {}", path, e))?;
+
+        return Network::from_bytes(&bytes);
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, String> {
+        let mut reader = Reader {
+            bytes,
+            offset: 0,
+        };
+
+        if reader.take(4)? != MAGIC {
+            return Err(String::from("Invalid network! Wrong magic number"));
+        }
+
+        let version = reader.u32()?;
+
+        if version != VERSION {
+            return Err(format!("Invalid network! Unsupported version {}", version));
+        }
+
+        let transformer = reader.u32()? as usize;
+        let first = reader.u32()? as usize;
+        let second = reader.u32()? as usize;
+
+        if [transformer, first, second].iter().any(|size| *size == 0 || *size > MAX_SIZE) {
+            return Err(format!("Invalid network! Layer sizes {}, {} and {} out of bounds", transformer, first, second));
+        }
+
+        let expected = 20 + transformer * 2 * (FEATURES + 1) + first * (4 + 2 * transformer) + second * (4 + first) + 4 + second;
+
+        if bytes.len() != expected {
+            return Err(format!("Invalid network! Expected {} bytes but found {}", expected, bytes.len()));
+        }
+
+        let transformer_biases = reader.i16s(transformer)?;
+        let transformer_weights = reader.i16s(FEATURES * transformer)?;
+        let hidden = [Layer::read(&mut reader, 2 * transformer, first)?, Layer::read(&mut reader, first, second)?];
+        let output = Layer::read(&mut reader, second, 1)?;
+
+        return Ok(Network {
+            transformer_biases,
+            transformer_weights,
+            hidden,
+            output,
+        });
+    }
+
+    /// The network in the format [`Network::from_bytes`] reads.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(MAGIC);
+
+        for value in [VERSION, self.transformer_size() as u32, self.hidden[0].biases.len() as u32, self.hidden[1].biases.len() as u32] {
+            bytes.extend(value.to_le_bytes());
+        }
+
+        self.transformer_biases.iter().for_each(|bias| bytes.extend(bias.to_le_bytes()));
+        self.transformer_weights.iter().for_each(|weight| bytes.extend(weight.to_le_bytes()));
+        self.hidden.iter().for_each(|layer| layer.write(&mut bytes));
+        self.output.write(&mut bytes);
+
+        return bytes;
+    }
+
+    /// Size of the accumulator of each side.
+    pub fn transformer_size(&self) -> usize {
+        self.transformer_biases.len()
+    }
+
+    /// Scores a position from scratch, in centipawns from the point of view of the side to move.
+    pub fn evaluate(&self, game: &Game) -> i32 {
+        let values = self.accumulate(&squares(game.board()));
+
+        return self.propagate(&values, *game.turn());
+    }
+
+    /// The feature transformer output of both sides, White first, adding up every feature.
+    fn accumulate(&self, squares: &Squares) -> Vec<i16> {
+        let size = self.transformer_size();
+        let mut values = vec![0; 2 * size];
+
+        for side in 0..2 {
+            self.refresh(squares, side, &mut values[side * size..(side + 1) * size]);
+        }
+
+        return values;
+    }
+
+    /// Adds up the weights of every feature of a side.
+    fn refresh(&self, squares: &Squares, side: usize, values: &mut [i16]) {
+        let king = king_square(squares, side);
+
+        values.copy_from_slice(&self.transformer_biases);
+
+        for (square, code) in squares.iter().enumerate() {
+            if *code != EMPTY && kind(*code) != KING {
+                add(values, self.feature_weights(feature(side, king, square, *code)));
+            }
+        }
+    }
+
+    fn feature_weights(&self, feature: usize) -> &[i16] {
+        let size = self.transformer_size();
+
+        return &self.transformer_weights[feature * size..(feature + 1) * size];
+    }
+
+    /// Scores the feature transformer output of both sides, White first.
+    fn propagate(&self, values: &[i16], turn: Color) -> i32 {
+        let (white, black) = values.split_at(self.transformer_size());
+
+        let (us, them) = match turn {
+            Color::White => (white, black),
+            Color::Black => (black, white),
+        };
+
+        let inputs : Vec<u8> = us.iter().chain(them.iter()).map(|value| (*value as i32).clamp(0, ACTIVATION_MAX) as u8).collect();
+
+        let first = activate(&self.hidden[0].forward(&inputs));
+        let second = activate(&self.hidden[1].forward(&first));
+
+        return self.output.forward(&second)[0] / OUTPUT_DIVISOR;
+    }
+}
+
+fn activate(sums: &[i32]) -> Vec<u8> {
+    return sums.iter().map(|sum| (sum >> WEIGHT_SHIFT).clamp(0, ACTIVATION_MAX) as u8).collect();
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let bytes = self.bytes.get(self.offset..self.offset + length).ok_or("Invalid network! Unexpected end of file")?;
+        self.offset += length;
+
+        return Ok(bytes);
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        return Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+    }
+
+    fn i16s(&mut self, count: usize) -> Result<Vec<i16>, String> {
+        return Ok(self.take(2 * count)?.chunks_exact(2).map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]])).collect());
+    }
+
+    fn i32s(&mut self, count: usize) -> Result<Vec<i32>, String> {
+        return Ok(self.take(4 * count)?.chunks_exact(4).map(|bytes| i32::from_le_bytes(bytes.try_into().unwrap())).collect());
+    }
+
+    fn i8s(&mut self, count: usize) -> Result<Vec<i8>, String> {
+        return Ok(self.take(count)?.iter().map(|byte| *byte as i8).collect());
+    }
+}
+
+/// Accumulators along the line the search is playing, updated incrementally on every movement and taken back as the
+/// search returns.
+///
+/// ## Examples
+///
+/// ```no_run
+/// use std::sync::Arc;
+///
+/// use chess::{eval::nnue::{Accumulators, Network}, game::{Game, movement::Movement}};
+///
+/// let network = Arc::new(Network::open("network.nnue").unwrap());
+/// let mut game = Game::new_classical();
+/// let mut accumulators = Accumulators::new(network.clone(), &game);
+///
+/// let movement = Movement::from_uci("e2e4", &game).unwrap();
+/// accumulators.push(&movement);
+/// game.play(movement).unwrap();
+///
+/// assert_eq!(accumulators.evaluate(), network.evaluate(&game));
+///
+/// accumulators.pop();
+/// ```
+pub struct Accumulators {
+    network: Arc<Network>,
+    /// The feature transformer output of both sides, White first, of every position along the line, each one in a
+    /// slot of twice the transformer size. The slots are allocated up front and reused as the search goes back and
+    /// forth.
+    values: Vec<i16>,
+    /// The pieces and the side to move of every position along the line.
+    positions: Vec<(Squares, Color)>,
+}
+
+impl Accumulators {
+    pub fn new(network: Arc<Network>, game: &Game) -> Accumulators {
+        let squares = squares(game.board());
+        let slot = 2 * network.transformer_size();
+
+        let mut values = network.accumulate(&squares);
+        values.resize(STACK_PLIES * slot, 0);
+
+        let mut positions = Vec::with_capacity(STACK_PLIES);
+        positions.push((squares, *game.turn()));
+
+        Accumulators {
+            network,
+            values,
+            positions,
+        }
+    }
+
+    /// Plays a movement on top of the current position. Only the features of the squares the movement changes are
+    /// updated, unless a king moved, in which case every feature of its side changes.
+    pub fn push(&mut self, movement: &Movement) {
+        let size = self.network.transformer_size();
+        let ply = self.positions.len();
+
+        if self.values.len() < (ply + 1) * 2 * size {
+            self.values.resize(2 * ply * 2 * size, 0);
+        }
+
+        let (before, turn) = self.positions[ply - 1];
+        let mut squares = before;
+        let (changed, count) = play(&mut squares, movement);
+
+        let (previous, current) = self.values.split_at_mut(ply * 2 * size);
+        let current = &mut current[..2 * size];
+        current.copy_from_slice(&previous[(ply - 1) * 2 * size..]);
+
+        let moved_king = match kind(before[changed[0]]) == KING {
+            true => Some(color(before[changed[0]])),
+            false => None,
+        };
+
+        for side in 0..2 {
+            let values = &mut current[side * size..(side + 1) * size];
+
+            if moved_king == Some(side) {
+                self.network.refresh(&squares, side, values);
+                continue;
+            }
+
+            let king = king_square(&squares, side);
+
+            for square in &changed[..count] {
+                let (before, after) = (before[*square], squares[*square]);
+
+                if before == after {
+                    continue;
+                }
+
+                if before != EMPTY && kind(before) != KING {
+                    sub(values, self.network.feature_weights(feature(side, king, *square, before)));
+                }
+
+                if after != EMPTY && kind(after) != KING {
+                    add(values, self.network.feature_weights(feature(side, king, *square, after)));
+                }
+            }
+        }
+
+        self.positions.push((squares, turn.opposite()));
+    }
+
+    /// Takes the last movement back. The starting position always stays.
+    pub fn pop(&mut self) {
+        if self.positions.len() > 1 {
+            self.positions.pop();
+        }
+    }
+
+    /// Scores the current position, in centipawns from the point of view of the side to move.
+    pub fn evaluate(&self) -> i32 {
+        return self.network.propagate(self.current(), self.positions[self.positions.len() - 1].1);
+    }
+
+    /// The feature transformer output of both sides, White first, for the current position.
+    fn current(&self) -> &[i16] {
+        let slot = 2 * self.network.transformer_size();
+        let ply = self.positions.len() - 1;
+
+        return &self.values[ply * slot..(ply + 1) * slot];
+    }
+}
+
+/// Plays a movement on the pieces, returning the squares it changes: the origin first, then the destination, then
+/// the Pawn taken en passant or the squares of the castling Rook.
+fn play(squares: &mut Squares, movement: &Movement) -> ([usize; 4], usize) {
+    let back_rank = match movement.piece().color() {
+        Color::White => 0,
+        Color::Black => 56,
+    };
+
+    let castle = |squares: &mut Squares, king_to: usize, rook_from: usize, rook_to: usize| {
+        let (king_from, king_to, rook_from, rook_to) = (back_rank + 4, back_rank + king_to, back_rank + rook_from, back_rank + rook_to);
+
+        squares[king_to] = squares[king_from];
+        squares[rook_to] = squares[rook_from];
+        squares[king_from] = EMPTY;
+        squares[rook_from] = EMPTY;
+
+        return ([king_from, king_to, rook_from, rook_to], 4);
+    };
+
+    return match movement {
+        Movement::CastleKingSide(_) => castle(squares, 6, 7, 5),
+        Movement::CastleQueenSide(_) => castle(squares, 2, 0, 3),
+        Movement::Move(_, from, to, promotion) | Movement::Capture(_, from, to, promotion) => {
+            let (from, to) = (square(from), square(to));
+            let code = squares[from];
+            let mut changed = ([from, to, 0, 0], 2);
+
+            // A Pawn taking diagonally onto an empty square takes en passant.
+            if code != EMPTY && kind(code) == PAWN && from % 8 != to % 8 && squares[to] == EMPTY {
+                let taken = from / 8 * 8 + to % 8;
+
+                squares[taken] = EMPTY;
+                changed = ([from, to, taken, 0], 3);
+            }
+
+            squares[to] = match promotion {
+                Some(promotion) => piece_code(promotion.as_ref()),
+                None => code,
+            };
+            squares[from] = EMPTY;
+
+            changed
+        },
+    };
+}
+
+fn squares(board: &Board) -> Squares {
+    let mut squares = [EMPTY; 64];
+
+    for (position, piece) in board.pieces() {
+        squares[square(&position)] = piece_code(piece);
+    }
+
+    return squares;
+}
+
+fn piece_code(piece: &dyn Piece) -> u8 {
+    let kind = match piece.prefix() {
+        "P" => 0,
+        "N" => 1,
+        "B" => 2,
+        "R" => 3,
+        "Q" => 4,
+        _ => KING,
+    };
+
+    let color = match piece.color() {
+        Color::White => 0,
+        Color::Black => 1,
+    };
+
+    return 1 + 2 * kind + color;
+}
+
+fn square(position: &Position) -> usize {
+    return (position.rank() as usize - 1) * 8 + position.file() as usize - 1;
+}
+
+fn kind(code: u8) -> u8 {
+    return (code - 1) / 2;
+}
+
+fn color(code: u8) -> usize {
+    return (code as usize - 1) % 2;
+}
+
+/// Square of the king of a side, a1 in the positions without one.
+fn king_square(squares: &Squares, side: usize) -> usize {
+    let code = 1 + 2 * KING + side as u8;
+
+    return squares.iter().position(|square| *square == code).unwrap_or(0);
+}
+
+fn feature(side: usize, king: usize, square: usize, code: u8) -> usize {
+    let orient = |square: usize| match side {
+        0 => square,
+        _ => square ^ 56,
+    };
+
+    let relative = (color(code) != side) as usize;
+
+    return (orient(king) * 10 + kind(code) as usize * 2 + relative) * 64 + orient(square);
+}
+
+fn add(values: &mut [i16], weights: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safe since the CPU supports AVX2.
+        unsafe { avx2::add(values, weights) };
+        return;
+    }
+
+    scalar::add(values, weights);
+}
+
+fn sub(values: &mut [i16], weights: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safe since the CPU supports AVX2.
+        unsafe { avx2::sub(values, weights) };
+        return;
+    }
+
+    scalar::sub(values, weights);
+}
+
+fn dot(inputs: &[u8], weights: &[i8]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safe since the CPU supports AVX2.
+        return unsafe { avx2::dot(inputs, weights) };
+    }
+
+    return scalar::dot(inputs, weights);
+}
+
+/// The reference implementation, for every CPU. The accumulators wrap around on overflow like the vector
+/// instructions do.
+mod scalar {
+    pub(super) fn add(values: &mut [i16], weights: &[i16]) {
+        values.iter_mut().zip(weights).for_each(|(value, weight)| *value = value.wrapping_add(*weight));
+    }
+
+    pub(super) fn sub(values: &mut [i16], weights: &[i16]) {
+        values.iter_mut().zip(weights).for_each(|(value, weight)| *value = value.wrapping_sub(*weight));
+    }
+
+    pub(super) fn dot(inputs: &[u8], weights: &[i8]) -> i32 {
+        return inputs.iter().zip(weights).map(|(input, weight)| *input as i32 * *weight as i32).sum();
+    }
+}
+
+/// 16 accumulator values or 32 layer inputs at once. The tails shorter than a vector go through the scalar code.
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn add(values: &mut [i16], weights: &[i16]) {
+        let length = values.len().min(weights.len());
+        let vectors = length / 16;
+
+        for index in 0..vectors {
+            let value = values.as_mut_ptr().add(index * 16) as *mut __m256i;
+            let weight = weights.as_ptr().add(index * 16) as *const __m256i;
+
+            _mm256_storeu_si256(value, _mm256_add_epi16(_mm256_loadu_si256(value), _mm256_loadu_si256(weight)));
+        }
+
+        super::scalar::add(&mut values[vectors * 16..length], &weights[vectors * 16..length]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn sub(values: &mut [i16], weights: &[i16]) {
+        let length = values.len().min(weights.len());
+        let vectors = length / 16;
+
+        for index in 0..vectors {
+            let value = values.as_mut_ptr().add(index * 16) as *mut __m256i;
+            let weight = weights.as_ptr().add(index * 16) as *const __m256i;
+
+            _mm256_storeu_si256(value, _mm256_sub_epi16(_mm256_loadu_si256(value), _mm256_loadu_si256(weight)));
+        }
+
+        super::scalar::sub(&mut values[vectors * 16..length], &weights[vectors * 16..length]);
+    }
+
+    /// The inputs are at most 127, so the pairs of products never saturate the 16 bits `maddubs` sums them into.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn dot(inputs: &[u8], weights: &[i8]) -> i32 {
+        let length = inputs.len().min(weights.len());
+        let vectors = length / 32;
+        let ones = _mm256_set1_epi16(1);
+        let mut sums = _mm256_setzero_si256();
+
+        for index in 0..vectors {
+            let input = _mm256_loadu_si256(inputs.as_ptr().add(index * 32) as *const __m256i);
+            let weight = _mm256_loadu_si256(weights.as_ptr().add(index * 32) as *const __m256i);
+
+            sums = _mm256_add_epi32(sums, _mm256_madd_epi16(_mm256_maddubs_epi16(input, weight), ones));
+        }
+
+        let mut lanes = [0i32; 8];
+        _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sums);
+
+        return lanes.iter().sum::<i32>() + super::scalar::dot(&inputs[vectors * 32..length], &weights[vectors * 32..length]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval::Evaluator, game::movement::Movement, search::{Limits, Score, Search}};
+
+    use super::*;
+
+    /// A xorshift generator, so the test networks are the same on every run.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self, range: i64) -> i64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+
+            return (self.0 % (2 * range as u64 + 1)) as i64 - range;
+        }
+    }
+
+    fn random_layer(random: &mut Random, inputs: usize, outputs: usize) -> Layer {
+        return Layer {
+            inputs,
+            biases: (0..outputs).map(|_| random.next(2000) as i32).collect(),
+            weights: (0..inputs * outputs).map(|_| random.next(127) as i8).collect(),
+        };
+    }
+
+    fn random_network(transformer: usize, first: usize, second: usize) -> Network {
+        let mut random = Random(0x9E3779B97F4A7C15);
+
+        return Network {
+            transformer_biases: (0..transformer).map(|_| random.next(40) as i16).collect(),
+            transformer_weights: (0..FEATURES * transformer).map(|_| random.next(20) as i16).collect(),
+            hidden: [random_layer(&mut random, 2 * transformer, first), random_layer(&mut random, first, second)],
+            output: random_layer(&mut random, second, 1),
+        };
+    }
+
+    #[test]
+    fn reads_and_writes_networks(){
+        let network = random_network(8, 4, 4);
+        let bytes = network.to_bytes();
+
+        assert_eq!(&bytes[0..4], b"NNUE");
+        assert!(Network::from_bytes(&bytes).unwrap() == network);
+
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).err().unwrap().contains("Expected"));
+        assert!(Network::from_bytes(&bytes[..10]).err().unwrap().contains("end of file"));
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(Network::from_bytes(&wrong_magic).err().unwrap().contains("magic"));
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 2;
+        assert!(Network::from_bytes(&wrong_version).err().unwrap().contains("version 2"));
+
+        assert!(Network::open("/nonexistent/network.nnue").is_err());
+    }
+
+    #[test]
+    fn updates_accumulators_incrementally(){
+        let network = Arc::new(random_network(40, 32, 8));
+        let mut game = Game::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        let mut accumulators = Accumulators::new(network.clone(), &game);
+        let mut evaluations = vec![accumulators.evaluate()];
+
+        // An en passant capture, both castlings, a promotion with a capture and king moves.
+        for uci in ["e5d6", "e8g8", "b7a8q", "f8a8", "e1c1", "g8g7", "d6d7", "a8a1"] {
+            let movement = Movement::from_uci(uci, &game).unwrap();
+            accumulators.push(&movement);
+            game.play(movement).unwrap();
+
+            assert!(accumulators.current() == network.accumulate(&squares(game.board())), "after {}", uci);
+            assert_eq!(accumulators.evaluate(), network.evaluate(&game));
+
+            evaluations.push(accumulators.evaluate());
+        }
+
+        for evaluation in evaluations.iter().rev() {
+            assert_eq!(accumulators.evaluate(), *evaluation);
+            accumulators.pop();
+        }
+
+        assert_eq!(accumulators.positions.len(), 1);
+    }
+
+    #[test]
+    fn grows_past_the_preallocated_plies(){
+        let network = Arc::new(random_network(8, 4, 4));
+        let mut game = Game::new_classical();
+        let mut accumulators = Accumulators::new(network.clone(), &game);
+
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8"].iter().cycle().take(STACK_PLIES + 10) {
+            let movement = Movement::from_uci(uci, &game).unwrap();
+            accumulators.push(&movement);
+            game.play(movement).unwrap();
+        }
+
+        assert_eq!(accumulators.evaluate(), network.evaluate(&game));
+    }
+
+    #[test]
+    fn evaluates_for_the_side_to_move(){
+        let network = random_network(16, 8, 8);
+
+        // The same position with the colors swapped.
+        let white = network.evaluate(&Game::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap());
+        let black = network.evaluate(&Game::from_fen("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3").unwrap());
+
+        assert_eq!(white, black);
+    }
+
+    #[test]
+    fn vectors_match_the_scalar_code(){
+        let mut random = Random(42);
+
+        let inputs : Vec<u8> = (0..77).map(|_| random.next(127).unsigned_abs() as u8).collect();
+        let weights : Vec<i8> = (0..77).map(|_| random.next(127) as i8).collect();
+        let expected : i32 = inputs.iter().zip(&weights).map(|(input, weight)| *input as i32 * *weight as i32).sum();
+
+        assert_eq!(scalar::dot(&inputs, &weights), expected);
+        assert_eq!(dot(&inputs, &weights), expected);
+
+        let accumulator : Vec<i16> = (0..37).map(|_| random.next(30000) as i16).collect();
+        let feature : Vec<i16> = (0..37).map(|_| random.next(30000) as i16).collect();
+
+        let (mut vector, mut reference) = (accumulator.clone(), accumulator.clone());
+        add(&mut vector, &feature);
+        scalar::add(&mut reference, &feature);
+        assert_eq!(vector, reference);
+
+        sub(&mut vector, &feature);
+        scalar::sub(&mut reference, &feature);
+        assert_eq!(vector, accumulator);
+        assert_eq!(reference, accumulator);
+    }
+
+    #[test]
+    fn replaces_the_handcrafted_evaluation(){
+        let network = Arc::new(random_network(16, 8, 8));
+        let search = |fen: &str, depth: u32| {
+            Search::new(Limits::new().depth(depth)).evaluator(Evaluator::Nnue(network.clone())).run(&Game::from_fen(fen).unwrap())
+        };
+
+        let result = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2);
+        assert_eq!(result.score(), Score::Mate(1));
+
+        // A quiet position with nothing to capture scores as the network says after a single ply.
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K2R w - - 0 1").unwrap();
+        let result = search("4k3/8/8/8/8/8/8/4K2R w - - 0 1", 1);
+
+        let mut child = game.clone();
+        child.play(result.best_move().unwrap().clone()).unwrap();
+        assert_eq!(result.score(), Score::Centipawns(-network.evaluate(&child)));
+    }
+}
+
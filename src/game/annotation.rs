@@ -0,0 +1,450 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::board::position::Position;
+
+/// Engine evaluation from White's point of view.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Evaluation{
+    Centipawns(i32),
+    /// Moves until mate, negative when Black mates.
+    Mate(i32),
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MarkColor{
+    Red,
+    Green,
+    Blue,
+    Yellow,
+}
+
+impl MarkColor {
+    fn from_char(c: char) -> Option<MarkColor> {
+        return match c {
+            'R' => Some(MarkColor::Red),
+            'G' => Some(MarkColor::Green),
+            'B' => Some(MarkColor::Blue),
+            'Y' => Some(MarkColor::Yellow),
+            _ => None,
+        }
+    }
+
+    fn to_char(self) -> char {
+        return match self {
+            MarkColor::Red => 'R',
+            MarkColor::Green => 'G',
+            MarkColor::Blue => 'B',
+            MarkColor::Yellow => 'Y',
+        }
+    }
+}
+
+/// An arrow drawn from one tile to another, as in `[%cal Ge2e4]`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Arrow{
+    color: MarkColor,
+    from: Position,
+    to: Position,
+}
+
+impl Arrow {
+    pub fn new(color: MarkColor, from: Position, to: Position) -> Arrow {
+        Arrow {
+            color,
+            from,
+            to,
+        }
+    }
+
+    pub fn color(&self) -> &MarkColor {
+        &self.color
+    }
+
+    pub fn from(&self) -> &Position {
+        &self.from
+    }
+
+    pub fn to(&self) -> &Position {
+        &self.to
+    }
+}
+
+/// A highlighted tile, as in `[%csl Rd4]`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Highlight{
+    color: MarkColor,
+    position: Position,
+}
+
+impl Highlight {
+    pub fn new(color: MarkColor, position: Position) -> Highlight {
+        Highlight {
+            color,
+            position,
+        }
+    }
+
+    pub fn color(&self) -> &MarkColor {
+        &self.color
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+}
+
+/// Data attached to a move by the embedded commands of PGN comments, along with the free text of those comments.
+///
+/// ## Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use chess::game::annotation::{Annotation, Evaluation};
+///
+/// let annotation = Annotation::parse("[%eval -1.05] Black is better [%clk 0:03:12]");
+///
+/// assert_eq!(annotation.clock(), Some(&Duration::from_secs(192)));
+/// assert_eq!(annotation.evaluation(), Some(&Evaluation::Centipawns(-105)));
+/// assert_eq!(annotation.comment(), Some("Black is better"));
+/// assert_eq!(annotation.to_string(), "[%clk 0:03:12] [%eval -1.05] Black is better");
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default))]
+pub struct Annotation{
+    clock: Option<Duration>,
+    elapsed: Option<Duration>,
+    evaluation: Option<Evaluation>,
+    arrows: Vec<Arrow>,
+    highlights: Vec<Highlight>,
+    comment: Option<String>,
+}
+
+impl Annotation {
+    pub fn new() -> Annotation {
+        return Annotation::default();
+    }
+
+    /// Reads the commands of a comment. Unknown or malformed commands are kept in the comment text.
+    pub fn parse(comment: &str) -> Annotation {
+        let mut annotation = Annotation::new();
+        let mut text = String::new();
+        let mut rest = comment;
+
+        while let Some(start) = rest.find("[%") {
+            let end = match rest[start..].find(']') {
+                Some(end) => start + end,
+                None => break,
+            };
+
+            text.push_str(&rest[..start]);
+            text.push(' ');
+
+            let command = &rest[start + 2..end];
+
+            if !annotation.read_command(command) {
+                text.push_str(&rest[start..=end]);
+            }
+
+            rest = &rest[end + 1..];
+        }
+
+        text.push_str(rest);
+
+        let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+        if !text.is_empty() {
+            annotation.comment = Some(text);
+        }
+
+        return annotation;
+    }
+
+    /// Remaining time on the clock of the player who moved.
+    pub fn clock(&self) -> Option<&Duration> {
+        self.clock.as_ref()
+    }
+
+    /// Time spent on the move.
+    pub fn elapsed(&self) -> Option<&Duration> {
+        self.elapsed.as_ref()
+    }
+
+    pub fn evaluation(&self) -> Option<&Evaluation> {
+        self.evaluation.as_ref()
+    }
+
+    pub fn arrows(&self) -> &Vec<Arrow> {
+        &self.arrows
+    }
+
+    pub fn highlights(&self) -> &Vec<Highlight> {
+        &self.highlights
+    }
+
+    /// The text of the comment, without its commands.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    pub fn set_clock(&mut self, clock: Option<Duration>) {
+        self.clock = clock;
+    }
+
+    pub fn set_elapsed(&mut self, elapsed: Option<Duration>) {
+        self.elapsed = elapsed;
+    }
+
+    pub fn set_evaluation(&mut self, evaluation: Option<Evaluation>) {
+        self.evaluation = evaluation;
+    }
+
+    pub fn add_arrow(&mut self, arrow: Arrow) {
+        self.arrows.push(arrow);
+    }
+
+    pub fn add_highlight(&mut self, highlight: Highlight) {
+        self.highlights.push(highlight);
+    }
+
+    pub fn set_comment(&mut self, comment: Option<String>) {
+        self.comment = comment;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self == &Annotation::new();
+    }
+
+    /// Adds the data of another annotation, its values replacing the ones already set.
+    pub fn merge(&mut self, other: Annotation) {
+        if other.clock.is_some() {
+            self.clock = other.clock;
+        }
+
+        if other.elapsed.is_some() {
+            self.elapsed = other.elapsed;
+        }
+
+        if other.evaluation.is_some() {
+            self.evaluation = other.evaluation;
+        }
+
+        self.arrows.extend(other.arrows);
+        self.highlights.extend(other.highlights);
+
+        self.comment = match (self.comment.take(), other.comment) {
+            (Some(comment), Some(other)) => Some(format!("{} {}", comment, other)),
+            (comment, other) => comment.or(other),
+        };
+    }
+
+    /// Returns false when the command is unknown or its arguments cannot be read.
+    fn read_command(&mut self, command: &str) -> bool {
+        let (name, arguments) = match command.trim().split_once(char::is_whitespace) {
+            Some((name, arguments)) => (name, arguments.trim()),
+            None => return false,
+        };
+
+        match name {
+            "clk" => match parse_duration(arguments) {
+                Some(clock) => self.clock = Some(clock),
+                None => return false,
+            },
+            "emt" => match parse_duration(arguments) {
+                Some(elapsed) => self.elapsed = Some(elapsed),
+                None => return false,
+            },
+            "eval" => match parse_evaluation(arguments) {
+                Some(evaluation) => self.evaluation = Some(evaluation),
+                None => return false,
+            },
+            "cal" => {
+                let mut arrows = Vec::new();
+
+                for arrow in arguments.split(',') {
+                    let arrow = arrow.trim();
+
+                    if arrow.len() != 5 || !arrow.is_ascii() {
+                        return false;
+                    }
+
+                    match (MarkColor::from_char(arrow.chars().next().unwrap()), Position::from_string(&arrow[1..3]), Position::from_string(&arrow[3..5])) {
+                        (Some(color), Ok(from), Ok(to)) => arrows.push(Arrow::new(color, from, to)),
+                        _ => return false,
+                    }
+                }
+
+                self.arrows.append(&mut arrows);
+            },
+            "csl" => {
+                let mut highlights = Vec::new();
+
+                for highlight in arguments.split(',') {
+                    let highlight = highlight.trim();
+
+                    if highlight.len() != 3 || !highlight.is_ascii() {
+                        return false;
+                    }
+
+                    match (MarkColor::from_char(highlight.chars().next().unwrap()), Position::from_string(&highlight[1..3])) {
+                        (Some(color), Ok(position)) => highlights.push(Highlight::new(color, position)),
+                        _ => return false,
+                    }
+                }
+
+                self.highlights.append(&mut highlights);
+            },
+            _ => return false,
+        }
+
+        return true;
+    }
+}
+
+/// Writes the annotation back as the body of a PGN comment.
+impl Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+
+        if let Some(clock) = self.clock {
+            parts.push(format!("[%clk {}]", format_duration(&clock)));
+        }
+
+        if let Some(elapsed) = self.elapsed {
+            parts.push(format!("[%emt {}]", format_duration(&elapsed)));
+        }
+
+        match self.evaluation {
+            Some(Evaluation::Centipawns(centipawns)) => {
+                let sign = if centipawns < 0 { "-" } else { "" };
+                parts.push(format!("[%eval {}{}.{:02}]", sign, centipawns.abs() / 100, centipawns.abs() % 100));
+            },
+            Some(Evaluation::Mate(moves)) => parts.push(format!("[%eval #{}]", moves)),
+            None => {},
+        }
+
+        if !self.arrows.is_empty() {
+            let arrows : Vec<String> = self.arrows.iter().map(|arrow| format!("{}{}{}", arrow.color.to_char(), arrow.from, arrow.to)).collect();
+            parts.push(format!("[%cal {}]", arrows.join(",")));
+        }
+
+        if !self.highlights.is_empty() {
+            let highlights : Vec<String> = self.highlights.iter().map(|highlight| format!("{}{}", highlight.color.to_char(), highlight.position)).collect();
+            parts.push(format!("[%csl {}]", highlights.join(",")));
+        }
+
+        if let Some(comment) = &self.comment {
+            parts.push(comment.clone());
+        }
+
+        return write!(f, "{}", parts.join(" "));
+    }
+}
+
+/// Reads `h:mm:ss`, with optional fractions of a second.
+fn parse_duration(duration: &str) -> Option<Duration> {
+    let parts : Vec<&str> = duration.split(':').collect();
+
+    if parts.is_empty() || parts.len() > 3 {
+        return None;
+    }
+
+    let seconds : f64 = match parts[parts.len() - 1].parse::<f64>() {
+        Ok(seconds) if seconds >= 0.0 && seconds.is_finite() => seconds,
+        _ => return None,
+    };
+
+    let mut total : u64 = 0;
+
+    for part in &parts[..parts.len() - 1] {
+        let value = part.parse::<u64>().ok()?;
+
+        total = total.checked_mul(60)?.checked_add(value)?;
+    }
+
+    return Duration::from_secs(total.checked_mul(60)?).checked_add(Duration::from_millis((seconds * 1000.0).round() as u64));
+}
+
+fn format_duration(duration: &Duration) -> String {
+    let seconds = duration.as_secs();
+    let millis = duration.subsec_millis();
+
+    let mut formatted = format!("{}:{:02}:{:02}", seconds / 3600, seconds % 3600 / 60, seconds % 60);
+
+    if millis > 0 {
+        formatted.push('.');
+        formatted.push_str(format!("{:03}", millis).trim_end_matches('0'));
+    }
+
+    return formatted;
+}
+
+/// Reads pawn units like `+0.35` or mates like `#-3`. Anything after a comma, such as a search depth, is ignored.
+fn parse_evaluation(evaluation: &str) -> Option<Evaluation> {
+    let evaluation = match evaluation.split(',').next() {
+        Some(evaluation) => evaluation.trim(),
+        None => return None,
+    };
+
+    if let Some(moves) = evaluation.strip_prefix('#') {
+        return moves.trim_start_matches('+').parse::<i32>().ok().map(Evaluation::Mate);
+    }
+
+    return match evaluation.parse::<f64>() {
+        Ok(pawns) if pawns.is_finite() => Some(Evaluation::Centipawns((pawns * 100.0).round() as i32)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_every_command(){
+        let annotation = Annotation::parse("[%clk 1:02:03.5] [%emt 0:00:07] [%eval #-3] [%cal Ge2e4,Rd1d8] [%csl Yd4]");
+
+        assert_eq!(annotation.clock(), Some(&Duration::from_millis(3_723_500)));
+        assert_eq!(annotation.elapsed(), Some(&Duration::from_secs(7)));
+        assert_eq!(annotation.evaluation(), Some(&Evaluation::Mate(-3)));
+        assert_eq!(annotation.arrows(), &vec![
+            Arrow::new(MarkColor::Green, Position::from_string("e2").unwrap(), Position::from_string("e4").unwrap()),
+            Arrow::new(MarkColor::Red, Position::from_string("d1").unwrap(), Position::from_string("d8").unwrap()),
+        ]);
+        assert_eq!(annotation.highlights(), &vec![Highlight::new(MarkColor::Yellow, Position::from_string("d4").unwrap())]);
+        assert_eq!(annotation.comment(), None);
+
+        assert_eq!(annotation.to_string(), "[%clk 1:02:03.5] [%emt 0:00:07] [%eval #-3] [%cal Ge2e4,Rd1d8] [%csl Yd4]");
+    }
+
+    #[test]
+    fn keeps_unknown_commands_as_text(){
+        let annotation = Annotation::parse("Good move [%tqu \"Find it\"] [%clk soon] [%eval 0.2,18]");
+
+        assert_eq!(annotation.evaluation(), Some(&Evaluation::Centipawns(20)));
+        assert_eq!(annotation.clock(), None);
+        assert_eq!(annotation.comment(), Some("Good move [%tqu \"Find it\"] [%clk soon]"));
+    }
+
+    #[test]
+    fn merges_annotations(){
+        let mut annotation = Annotation::parse("[%clk 0:01:00] first");
+
+        annotation.merge(Annotation::parse("[%clk 0:00:59] [%csl Ra1] second"));
+
+        assert_eq!(annotation.to_string(), "[%clk 0:00:59] [%csl Ra1] first second");
+        assert!(Annotation::parse("  ").is_empty());
+    }
+
+    #[test]
+    fn keeps_overflowing_clocks_as_text(){
+        let annotation = Annotation::parse("[%clk 999999999999999999:00:00]");
+
+        assert_eq!(annotation.clock(), None);
+        assert_eq!(annotation.comment(), Some("[%clk 999999999999999999:00:00]"));
+        assert_eq!(parse_duration("18446744073709551615:00"), None);
+    }
+}
@@ -1,7 +1,8 @@
 use std::fmt::Display;
 
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CastleRights{
     QueenSide,
     KingSide,
@@ -1,245 +1,405 @@
-use crate::{color::Color, board::{Board, relative_position::RelativePosition, position::Position}, piece::{pieces::{king::King, rook::Rook, pawn::Pawn}, Piece}};
+use crate::{color::Color, board::{Board, relative_position::RelativePosition, position::Position}, piece::{pieces::{king::King, rook::Rook, pawn::Pawn}, Piece, piece_factory}, parser::{fen::Fen, pgn}};
 
-use self::{movement::Movement, castle_rights::CastleRights};
+use self::{movement::Movement, castle_rights::CastleRights, annotation::Annotation};
 
 pub mod movement;
 pub mod castle_rights;
+pub mod annotation;
 
+#[derive(Clone)]
 pub struct Game{
     turn: Color,
     board: Board,
     movements: Vec<Movement>,
+    annotations: Vec<Annotation>,
+    tags: Vec<(String, String)>,
+    start_fen: String,
+    halfmove_clock: u32,
+    fullmove_number: u32,
 }
 
 impl Game{
     pub fn new_classical() -> Game{
-        return Game{
-            turn: Color::White,
-            board: Board::new_classical(),
+        return Game::new(Board::new_classical(), Color::White, 0, 1);
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Game, String>{
+        return Fen::new(fen.to_string()).to_game();
+    }
+
+    pub(crate) fn new(board: Board, turn: Color, halfmove_clock: u32, fullmove_number: u32) -> Game{
+        let mut game = Game{
+            turn,
+            board,
             movements: Vec::new(),
-        }
+            annotations: Vec::new(),
+            tags: Vec::new(),
+            start_fen: String::new(),
+            halfmove_clock,
+            fullmove_number,
+        };
+
+        game.start_fen = game.to_fen();
+
+        return game;
     }
 
     pub fn board(&self) -> &Board{
         &self.board
     }
 
-    pub fn play(&mut self, movement: Movement) -> Result<(), String> {
+    pub fn turn(&self) -> &Color{
+        &self.turn
+    }
 
-        match movement {
-            Movement::Move(_, from, to, _) => self.move_piece(from, to)?,
-            Movement::Capture(_, from, to, _) => self.capture_piece(from, to)?,
-            Movement::CastleKingSide(_) => self.castle(&movement)?,
-            Movement::CastleQueenSide(_) => self.castle(&movement)?,
-        }
+    pub fn movements(&self) -> &Vec<Movement>{
+        &self.movements
+    }
 
-        self.movements.push(movement);
+    /// The annotations of each movement, in the same order as the movements.
+    pub fn annotations(&self) -> &Vec<Annotation>{
+        &self.annotations
+    }
 
-        match self.board.from_movements(&self.movements){
-            Ok(board) => self.board = board,
-            Err(e) => {
-                self.movements.pop();
-                return Err(e)
+    /// Adds an annotation to the last movement played.
+    pub fn annotate(&mut self, annotation: Annotation) -> Result<(), String>{
+        return match self.annotations.last_mut(){
+            Some(last) => {
+                last.merge(annotation);
+                Ok(())
             },
+            None => Err(String::from("There is no movement to annotate")),
         }
+    }
+
+    /// Number of moves since the last capture or pawn move.
+    pub fn halfmove_clock(&self) -> u32{
+        self.halfmove_clock
+    }
+
+    pub fn fullmove_number(&self) -> u32{
+        self.fullmove_number
+    }
+
+    /// The FEN of the position the game started from.
+    pub fn start_fen(&self) -> &str{
+        &self.start_fen
+    }
+
+    pub fn to_fen(&self) -> String{
+        return Fen::from_game(self).to_string();
+    }
+
+    pub fn to_pgn(&self) -> Result<String, String>{
+        return pgn::to_pgn(self);
+    }
+
+    pub fn tags(&self) -> &Vec<(String, String)>{
+        &self.tags
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str>{
+        return self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str());
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str){
+        match self.tags.iter_mut().find(|(tag, _)| tag == name){
+            Some((_, previous)) => *previous = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// The ECO opening of the game, see [`crate::opening::classify`].
+    pub fn opening(&self) -> Option<crate::opening::Opening>{
+        return crate::opening::classify(self);
+    }
+
+    pub fn play(&mut self, movement: Movement) -> Result<(), String> {
+        self.validate(&movement)?;
+
+        let resets_clock = movement.piece().prefix() == Pawn::prefix() || matches!(movement, Movement::Capture(..));
+
+        self.board.apply(&movement)?;
+
+        self.halfmove_clock = match resets_clock {
+            true => 0,
+            false => self.halfmove_clock + 1,
+        };
+
+        if self.turn == Color::Black {
+            self.fullmove_number += 1;
+        }
+
+        self.movements.push(movement);
+        self.annotations.push(Annotation::new());
 
         self.turn = match self.turn {
             Color::White => Color::Black,
             Color::Black => Color::White,
         };
 
-        self.on_move();
-
         return Ok(());
     }
 
     pub fn is_legal(&self, movement: Movement) -> bool {
+        return self.validate(&movement).is_ok();
+    }
+
+    /// Every movement the side to move can legally play.
+    pub fn legal_movements(&self) -> Vec<Movement> {
+        let mut movements = Vec::new();
+
+        for (from, piece) in self.board.pieces() {
+            if piece.color() != &self.turn {
+                continue;
+            }
+
+            for relative_position in piece.possible_moves() {
+                let to = match Position::from_relative(from, relative_position){
+                    Ok(to) => to,
+                    Err(_) => continue,
+                };
+
+                for promotion in Game::promotions(piece, &to) {
+                    let movement = Movement::Move(piece_factory(piece.prefix(), *piece.color()), from, to, promotion);
+
+                    if self.validate(&movement).is_ok() {
+                        movements.push(movement);
+                    }
+                }
+            }
 
+            for relative_position in piece.possible_captures() {
+                let to = match Position::from_relative(from, relative_position){
+                    Ok(to) => to,
+                    Err(_) => continue,
+                };
+
+                for promotion in Game::promotions(piece, &to) {
+                    let movement = Movement::Capture(piece_factory(piece.prefix(), *piece.color()), from, to, promotion);
+
+                    if self.validate(&movement).is_ok() {
+                        movements.push(movement);
+                    }
+                }
+            }
+        }
+
+        for movement in [Movement::CastleKingSide(Box::new(King::new(self.turn))), Movement::CastleQueenSide(Box::new(King::new(self.turn)))] {
+            if self.validate(&movement).is_ok() {
+                movements.push(movement);
+            }
+        }
+
+        return movements;
+    }
+
+    pub fn is_check(&self) -> bool {
+        return self.board.is_in_check(&self.turn);
+    }
+
+    pub fn is_checkmate(&self) -> bool {
+        return self.is_check() && self.legal_movements().is_empty();
+    }
+
+    pub fn is_stalemate(&self) -> bool {
+        return !self.is_check() && self.legal_movements().is_empty();
+    }
+
+    fn validate(&self, movement: &Movement) -> Result<(), String> {
         match movement {
-            Movement::Move(_, from, to, _) => match self.move_piece(from, to){
-                Err(_) => return false,
-                _ => (),
-            },
-            Movement::Capture(_, from, to, _) => match self.capture_piece(from, to){
-                Err(_) => return false,
-                _ => (),
-            },
-            Movement::CastleKingSide(_) => match self.castle(&movement){
-                Err(_) => return false,
-                _ => (),
-            },
-            Movement::CastleQueenSide(_) => match self.castle(&movement){
-                Err(_) => return false,
-                _ => (),
-            },
+            Movement::Move(_, from, to, _) => self.move_piece(*from, *to)?,
+            Movement::Capture(_, from, to, _) => self.capture_piece(*from, *to)?,
+            Movement::CastleKingSide(_) => self.castle(movement)?,
+            Movement::CastleQueenSide(_) => self.castle(movement)?,
+        }
+
+        if let Some(from) = movement.from() {
+            if let Some(piece) = self.board.get_piece_at(from) {
+                if piece.prefix() != movement.piece().prefix() || piece.color() != movement.piece().color() {
+                    return Err(format!("Invalid movement {}! There is a {} {} at {}", movement, piece.color(), piece.name(), from));
+                }
+            }
+        }
+
+        if let Some(to) = movement.to() {
+            Game::check_promotion(movement.piece(), to, movement.promotion())?;
         }
 
-        return true;
+        let mut board = self.board.clone();
+        board.apply(movement)?;
+
+        if board.is_in_check(&self.turn) {
+            return Err(format!("Invalid movement {}! It would leave the {} King in check", movement, self.turn));
+        }
+
+        return Ok(());
+    }
+
+    fn promotions(piece: &dyn Piece, to: &Position) -> Vec<Option<Box<dyn Piece>>> {
+        if piece.prefix() != Pawn::prefix() || (to.rank() != 8 && to.rank() != 1) {
+            return vec![None];
+        }
+
+        return ["Q", "R", "B", "N"].iter().map(|prefix| Some(piece_factory(prefix, *piece.color()))).collect();
     }
 
     fn move_piece(&self, from: Position, to: Position) -> Result<(), String>{
         let piece = match self.board.get_piece_at(&from){
             Some(piece) => piece,
-            None => return Err(String::from(format!("Cannot move from an empty tile! There is no piece at {}", from))),
+            None => return Err(format!("Cannot move from an empty tile! There is no piece at {}", from)),
         };
 
-        match self.board.get_piece_at(&to){
-            Some(piece) => return Err(String::from(format!("Cannot move to an occupied tile! There is already a {} {} at {}", piece.color(), piece.name(), to))),
-            None => (),
-        };
+        if let Some(piece) = self.board.get_piece_at(&to) {
+            return Err(format!("Cannot move to an occupied tile! There is already a {} {} at {}", piece.color(), piece.name(), to));
+        }
 
         if piece.color() != &self.turn {
-            return Err(String::from(format!("Cannot move opponent's {}. It's {} turn and the selected piece is {}", piece.name(), self.turn, piece.color())));
+            return Err(format!("Cannot move opponent's {}. It's {} turn and the selected piece is {}", piece.name(), self.turn, piece.color()));
         }
 
-        let position = match RelativePosition::from_absolute(&from, &to){
-            Ok(position) => position,
-            Err(e) => return Err(e),
-        };
+        let position = RelativePosition::from_absolute(&from, &to)?;
 
         if !piece.is_valid_move(&position){
-            return Err(String::from(format!("Invalid move for {} {}, this type of piece cannot move like that!", piece.color(), piece.name())));
+            return Err(format!("Invalid move for {} {}, this type of piece cannot move like that!", piece.color(), piece.name()));
         }
 
-        match piece.will_colide(&self.board, &from, &to){
-            Err(e) => return Err(e),
-            _ => (),
+        if piece.prefix() == Pawn::prefix() && Pawn::is_double_move(&position) {
+            let starting_rank = match piece.color() {
+                Color::White => 2,
+                Color::Black => 7,
+            };
+
+            if from.rank() != starting_rank {
+                return Err(format!("Invalid move for {} {}, it can only move two tiles from its starting rank!", piece.color(), piece.name()));
+            }
         }
 
+        piece.will_colide(&self.board, &from, &to)?;
+
         return Ok(());
     }
 
     fn capture_piece(&self, from: Position, to: Position) -> Result<(), String>{
         let from_piece = match self.board.get_piece_at(&from){
             Some(piece) => piece,
-            None => return Err(String::from(format!("Cannot move from an empty tile! There is no piece at {}", from))),
+            None => return Err(format!("Cannot move from an empty tile! There is no piece at {}", from)),
         };
 
         match self.board.get_piece_at(&to){
             Some(piece) => {
                 if piece.color() == &self.turn {
-                    return Err(String::from(format!("Cannot capture your own piece! There is a {} {} at {}", piece.color(), piece.name(), to)));
+                    return Err(format!("Cannot capture your own piece! There is a {} {} at {}", piece.color(), piece.name(), to));
+                }
+            },
+            None => {
+                let is_en_passant = from_piece.prefix() == Pawn::prefix() && self.board.en_passant() == Some(&to);
+
+                if !is_en_passant {
+                    return Err(format!("Cannot capture an empty tile! There is no piece at {}", to));
                 }
             },
-            None => return Err(String::from(format!("Cannot capture an empty tile! There is no piece at {}", to))),
         };
 
         if from_piece.color() != &self.turn {
-            return Err(String::from(format!("Cannot move opponent's {}. It's {} turn and the selected piece is {}", from_piece.name(), self.turn, from_piece.color())));
+            return Err(format!("Cannot move opponent's {}. It's {} turn and the selected piece is {}", from_piece.name(), self.turn, from_piece.color()));
         }
 
-        let position = match RelativePosition::from_absolute(&from, &to){
-            Ok(position) => position,
-            Err(e) => return Err(e),
-        };
+        let position = RelativePosition::from_absolute(&from, &to)?;
 
         if !from_piece.is_valid_capture(&position){
-            return Err(String::from(format!("Invalid capture for {} {}, this type of piece cannot capture like that!", from_piece.color(), from_piece.name())));
+            return Err(format!("Invalid capture for {} {}, this type of piece cannot capture like that!", from_piece.color(), from_piece.name()));
         }
 
-        match from_piece.will_colide(&self.board, &from, &to){
-            Err(e) => return Err(e),
-            _ => (),
-        }
+        from_piece.will_colide(&self.board, &from, &to)?;
 
         return Ok(());
     }
 
     fn castle(&self, movement: &Movement) -> Result<(), String>{
-        let rank = match movement.piece().color() {
+        let color = movement.piece().color();
+
+        if color != &self.turn {
+            return Err(format!("Cannot castle for {}. It's {} turn", color, self.turn));
+        }
+
+        let rank = match color {
             Color::White => 1,
             Color::Black => 8,
         };
 
-        match movement {
-            Movement::CastleKingSide(_) => {
-                let from = Position::new(5, rank).unwrap();
-                let to = Position::new(7, rank).unwrap();
-
-                match movement.piece().will_colide(&self.board, &from, &to){
-                    Err(e) => return Err(e),
-                    _ => (),
-                }
-            },
-            Movement::CastleQueenSide(_) => {
-                let from = Position::new(5, rank).unwrap();
-                let to = Position::new(3, rank).unwrap();
-
-                match movement.piece().will_colide(&self.board, &from, &to){
-                    Err(e) => return Err(e),
-                    _ => (),
-                }
-            },
-            _ => return Err(String::from(format!("Invalid castle movement {}", movement))),
-        }
-
-        return Ok(());
-    }
+        let (castle_right, rook_file, empty_files, safe_files) = match movement {
+            Movement::CastleKingSide(_) => (CastleRights::KingSide, 8, vec![6, 7], vec![5, 6, 7]),
+            Movement::CastleQueenSide(_) => (CastleRights::QueenSide, 1, vec![2, 3, 4], vec![5, 4, 3]),
+            _ => return Err(format!("Invalid castle movement {}", movement)),
+        };
 
-    fn on_move(&mut self){
-        self.change_castle_rights();
-    }
+        self.board.has_castle_rights(color, &castle_right)?;
 
-    fn change_castle_rights(&mut self){
-        let last_movement = match self.movements.last(){
-            Some(movement) => movement,
-            None => return,
-        };
+        for (file, prefix) in [(5, King::prefix()), (rook_file, Rook::prefix())] {
+            let position = Position::new(file, rank)?;
 
-        if last_movement.piece().prefix() == King::prefix() {
-            self.board.revoke_castle_right(&Color::opposite(&self.turn), CastleRights::Both);
+            match self.board.get_piece_at(&position){
+                Some(piece) if piece.prefix() == prefix && piece.color() == color => (),
+                _ => return Err(format!("Cannot castle without the {} pieces on their starting tiles! Nothing to castle with at {}", color, position)),
+            }
         }
 
-        if last_movement.piece().prefix() == Rook::prefix(){
-            let is_king_side = match last_movement.from() {
-                Some(from) => from.file() == 8,
-                None => false,
-            };
+        for file in empty_files {
+            let position = Position::new(file, rank)?;
 
-            if is_king_side {
-                self.board.revoke_castle_right(&Color::opposite(&self.turn), CastleRights::KingSide);
+            if let Some(piece) = self.board.get_piece_at(&position) {
+                return Err(format!("Cannot castle through pieces! There is a {} {} at {}", piece.color(), piece.name(), position));
             }
+        }
 
-            let is_queen_side = match last_movement.from() {
-                Some(from) => from.file() == 1,
-                None => false,
-            };
+        for file in safe_files {
+            let position = Position::new(file, rank)?;
 
-            if is_queen_side {
-                self.board.revoke_castle_right(&Color::opposite(&self.turn), CastleRights::QueenSide);
+            if self.board.is_attacked(&position, &color.opposite()) {
+                return Err(format!("Cannot castle through check! {} is attacked", position));
             }
         }
+
+        return Ok(());
     }
 
-    fn check_promotion(piece : &Box<dyn Piece>, to : &Position, promotion: Option<&Box<dyn Piece>>) -> Result<(), String>{
+    fn check_promotion(piece : &dyn Piece, to : &Position, promotion: Option<&dyn Piece>) -> Result<(), String>{
         match promotion {
             Some(promotion) => {
                 if piece.prefix() != Pawn::prefix() {
-                    return Err(String::from(format!("A {} cannot be promoted! Only a {}", piece.name(), Pawn::prefix())));
+                    return Err(format!("A {} cannot be promoted! Only a {}", piece.name(), Pawn::prefix()));
                 }
 
                 if promotion.color() != piece.color() {
-                    return Err(String::from(format!("Cannot promote to a different color! {} is {} and {} is {}", piece, piece.color(), promotion, promotion.color())));
+                    return Err(format!("Cannot promote to a different color! {} is {} and {} is {}", piece, piece.color(), promotion, promotion.color()));
                 }
 
                 if promotion.prefix() == King::prefix() || promotion.prefix() == Pawn::prefix() {
-                    return Err(String::from(format!("Cannot promote to a {}! Only Rooks, Knights, Bishops and Queens are allowed", promotion.name())));
+                    return Err(format!("Cannot promote to a {}! Only Rooks, Knights, Bishops and Queens are allowed", promotion.name()));
                 }
 
                 if to.rank() != 8 && to.rank() != 1 {
-                    return Err(String::from(format!("Cannot promote {} in this location! Only on rank 1 or 8", piece.name())));
+                    return Err(format!("Cannot promote {} in this location! Only on rank 1 or 8", piece.name()));
+                }
+
+                return Ok(());
+            },
+            None => {
+                if piece.prefix() == Pawn::prefix() && (to.rank() == 8 || to.rank() == 1) {
+                    return Err(format!("A {} reaching the last rank must be promoted!", piece.name()));
                 }
 
                 return Ok(());
             },
-            None => return Ok(()),
         }
     }
 }
 
+
 #[cfg(test)]
 mod tests{
-    use crate::{piece::{pieces::pawn::Pawn, Piece}, board::position::Position};
+    use crate::{piece::{pieces::{pawn::Pawn, knight::Knight}, Piece}, board::position::Position};
 
     use super::*;
 
@@ -310,4 +470,31 @@ mod tests{
         let result = game.play(m5);
         assert_eq!(result, Ok(()), "\n{}", game.board);
     }
+
+    #[test]
+    fn detects_checkmate(){
+        let game = Game::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+
+        assert!(game.is_check());
+        assert!(game.is_checkmate());
+        assert!(!game.is_stalemate());
+    }
+
+    #[test]
+    fn cannot_move_pinned_piece(){
+        let game = Game::from_fen("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+
+        let movement = Movement::new_move(Box::new(Knight::new(Color::White)), Position::new(5, 2).unwrap(), Position::new(6, 4).unwrap(), None).unwrap();
+
+        assert!(!game.is_legal(movement));
+        assert!(game.legal_movements().iter().all(|movement| movement.piece().prefix() == King::prefix()));
+    }
+
+    #[test]
+    fn cannot_castle_through_check(){
+        let game = Game::from_fen("4k3/8/8/8/8/8/6r1/R3K2R w KQ - 0 1").unwrap();
+
+        assert!(!game.is_legal(Movement::CastleKingSide(Box::new(King::new(Color::White)))));
+        assert!(game.is_legal(Movement::CastleQueenSide(Box::new(King::new(Color::White)))));
+    }
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
 use std::fmt::Display;
 
-use crate::{board::position::Position, piece::Piece};
+use crate::{board::position::Position, color::Color, parser::san::PieceLetters, piece::{Piece, piece_factory, pieces::{king::King, pawn::Pawn, rook::Rook}}};
+
+use super::Game;
 
 pub enum Movement{
     CastleKingSide(Box<dyn Piece>),
@@ -26,44 +28,268 @@ impl Movement {
         return Some(Movement::Capture(piece, from, to, promotion));
     }
 
-    pub fn piece(&self) -> &Box<dyn Piece> {
+    pub fn piece(&self) -> &dyn Piece {
         return match self {
-            Movement::Move(piece, _, _, _) => piece,
-            Movement::Capture(piece, _, _, _) => piece,
-            Movement::CastleKingSide(piece) => piece,
-            Movement::CastleQueenSide(piece) => piece,
+            Movement::Move(piece, _, _, _) => piece.as_ref(),
+            Movement::Capture(piece, _, _, _) => piece.as_ref(),
+            Movement::CastleKingSide(piece) => piece.as_ref(),
+            Movement::CastleQueenSide(piece) => piece.as_ref(),
         }
     }
 
     pub fn from(&self) -> Option<&Position> {
         return match self {
-            Movement::Move(_, from, _, _) => Some(&from),
-            Movement::Capture(_, from, _, _) => Some(&from),
+            Movement::Move(_, from, _, _) => Some(from),
+            Movement::Capture(_, from, _, _) => Some(from),
             _ => None,
         }
     }
 
     pub fn to(&self) -> Option<&Position> {
         return match self {
-            Movement::Move(_, _, to, _) => Some(&to),
-            Movement::Capture(_, _, to, _) => Some(&to),
+            Movement::Move(_, _, to, _) => Some(to),
+            Movement::Capture(_, _, to, _) => Some(to),
             _ => None,
         }
     }
 
-    pub fn promotion(&self) -> Option<&Box<dyn Piece>> {
+    pub fn promotion(&self) -> Option<&dyn Piece> {
         return match self {
-            Movement::Move(_, _, _, promotion) => promotion.as_ref(),
-            Movement::Capture(_, _, _, promotion) => promotion.as_ref(),
+            Movement::Move(_, _, _, promotion) => promotion.as_deref(),
+            Movement::Capture(_, _, _, promotion) => promotion.as_deref(),
             _ => None,
         }
     }
 
+    /// Writes the movement in Standard Algebraic Notation, as played in the given game.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use chess::game::{Game, movement::Movement};
+    /// use chess::board::position::Position;
+    /// use chess::piece::piece_factory;
+    /// use chess::color::Color;
+    ///
+    /// let game = Game::new_classical();
+    ///
+    /// let movement = Movement::new_move(piece_factory("N", Color::White), Position::from_string("g1").unwrap(), Position::from_string("f3").unwrap(), None).unwrap();
+    ///
+    /// assert_eq!(movement.to_san(&game), Ok(String::from("Nf3")));
+    /// ```
+    pub fn to_san(&self, game: &Game) -> Result<String, String> {
+        return self.to_san_with(game, &PieceLetters::english());
+    }
+
+    /// Writes the movement in SAN with the piece letters of another language, or as figurines.
+    pub fn to_san_with(&self, game: &Game, letters: &PieceLetters) -> Result<String, String> {
+        let mut san = match self {
+            Movement::CastleKingSide(_) => String::from("O-O"),
+            Movement::CastleQueenSide(_) => String::from("O-O-O"),
+            Movement::Move(piece, from, to, promotion) => Movement::get_san(game, self, piece.as_ref(), from, to, promotion, letters),
+            Movement::Capture(piece, from, to, promotion) => Movement::get_san(game, self, piece.as_ref(), from, to, promotion, letters),
+        };
+
+        san.push_str(self.check_suffix(game)?);
+
+        return Ok(san);
+    }
+
+    /// Plays the movement on a copy of the game, returning `#` when it mates, `+` when it checks and nothing otherwise.
+    pub(crate) fn check_suffix(&self, game: &Game) -> Result<&'static str, String> {
+        let mut after = game.clone();
+
+        after.play(self.clone())?;
+
+        if after.is_checkmate() {
+            return Ok("#");
+        }
+
+        if after.is_check() {
+            return Ok("+");
+        }
+
+        return Ok("");
+    }
+
+    /// Reads a movement written in UCI long algebraic notation, such as `e2e4` or `e7e8q`.
+    ///
+    /// The piece, capture, castle and en passant are inferred from the game. Castles are accepted both as
+    /// the King's two tile move (`e1g1`) and in the King takes Rook style used for Chess960 (`e1h1`).
+    pub fn from_uci(uci: &str, game: &Game) -> Result<Movement, String> {
+        if (uci.len() != 4 && uci.len() != 5) || !uci.is_ascii() {
+            return Err(format!("Invalid UCI movement {}! Expected 4 or 5 characters", uci));
+        }
+
+        let from = Position::from_string(&uci[0..2])?;
+        let to = Position::from_string(&uci[2..4])?;
+
+        let piece = match game.board().get_piece_at(&from) {
+            Some(piece) => piece,
+            None => return Err(format!("Invalid UCI movement {}! There is no piece at {}", uci, from)),
+        };
+
+        let color = *piece.color();
+
+        let promotion = match uci.chars().nth(4) {
+            Some(c) => match c {
+                'q' | 'r' | 'b' | 'n' => Some(piece_factory(&c.to_ascii_uppercase().to_string(), color)),
+                _ => return Err(format!("Invalid UCI movement {}! Unknown promotion {}", uci, c)),
+            },
+            None => None,
+        };
+
+        let target = game.board().get_piece_at(&to);
+
+        if piece.prefix() == King::prefix() && from.file() == 5 && from.rank() == to.rank() {
+            let takes_own_rook = match target {
+                Some(target) => target.prefix() == Rook::prefix() && target.color() == &color,
+                None => false,
+            };
+
+            match to.file() {
+                7 => return Movement::castle(Movement::CastleKingSide(Box::new(King::new(color))), uci, game),
+                3 => return Movement::castle(Movement::CastleQueenSide(Box::new(King::new(color))), uci, game),
+                8 if takes_own_rook => return Movement::castle(Movement::CastleKingSide(Box::new(King::new(color))), uci, game),
+                1 if takes_own_rook => return Movement::castle(Movement::CastleQueenSide(Box::new(King::new(color))), uci, game),
+                _ => {},
+            }
+        }
+
+        let is_capture = target.is_some() || (piece.prefix() == Pawn::prefix() && game.board().en_passant() == Some(&to));
+
+        let movement = match is_capture {
+            true => Movement::new_capture(piece_factory(piece.prefix(), color), from, to, promotion),
+            false => Movement::new_move(piece_factory(piece.prefix(), color), from, to, promotion),
+        };
+
+        return match movement {
+            Some(movement) if game.is_legal(movement.clone()) => Ok(movement),
+            _ => Err(format!("Illegal UCI movement {}", uci)),
+        }
+    }
+
+    /// Writes the movement in UCI long algebraic notation.
+    ///
+    /// Castles are written as the King's two tile move, or as the King taking its own Rook when `king_takes_rook` is set, as Chess960 engines expect.
+    pub fn to_uci(&self, king_takes_rook: bool) -> String {
+        let rank = match self.piece().color() {
+            Color::White => 1,
+            Color::Black => 8,
+        };
+
+        return match self {
+            Movement::CastleKingSide(_) => match king_takes_rook {
+                true => format!("e{}h{}", rank, rank),
+                false => format!("e{}g{}", rank, rank),
+            },
+            Movement::CastleQueenSide(_) => match king_takes_rook {
+                true => format!("e{}a{}", rank, rank),
+                false => format!("e{}c{}", rank, rank),
+            },
+            Movement::Move(_, from, to, promotion) | Movement::Capture(_, from, to, promotion) => {
+                let promotion = match promotion {
+                    Some(promotion) => promotion.prefix().to_lowercase(),
+                    None => String::new(),
+                };
+
+                format!("{}{}{}", from, to, promotion)
+            },
+        }
+    }
+
+    fn castle(movement: Movement, uci: &str, game: &Game) -> Result<Movement, String> {
+        if !game.is_legal(movement.clone()) {
+            return Err(format!("Illegal UCI movement {}! Cannot castle", uci));
+        }
+
+        return Ok(movement);
+    }
+
+    fn get_san(game: &Game, movement: &Movement, piece: &dyn Piece, from: &Position, to: &Position, promotion: &Option<Box<dyn Piece>>, letters: &PieceLetters) -> String {
+        let is_capture = matches!(movement, Movement::Capture(..));
+        let mut san = String::new();
+
+        if piece.prefix() == Pawn::prefix() {
+            if is_capture {
+                san.push(from.file_char());
+            }
+        } else {
+            san.push_str(letters.letter(piece.prefix()));
+            san.push_str(&Movement::get_disambiguation(game, movement));
+        }
+
+        if is_capture {
+            san.push('x');
+        }
+
+        san.push_str(&to.to_string());
+
+        if let Some(promotion) = promotion {
+            san.push('=');
+            san.push_str(letters.letter(promotion.prefix()));
+        }
+
+        return san;
+    }
+
+    /// The file, rank or tile needed to tell the moving piece apart from identical pieces that could also reach the destination.
+    fn get_disambiguation(game: &Game, movement: &Movement) -> String {
+        let (piece, from, to) = match (movement.from(), movement.to()) {
+            (Some(from), Some(to)) => (movement.piece(), from, to),
+            _ => return String::new(),
+        };
+
+        let mut others = Vec::new();
+
+        for (position, other) in game.board().pieces() {
+            if position == *from || other.prefix() != piece.prefix() || other.color() != piece.color() {
+                continue;
+            }
+
+            let would_be_movement = match movement {
+                Movement::Capture(..) => Movement::Capture(piece_factory(other.prefix(), *other.color()), position, *to, None),
+                _ => Movement::Move(piece_factory(other.prefix(), *other.color()), position, *to, None),
+            };
+
+            if game.is_legal(would_be_movement) {
+                others.push(position);
+            }
+        }
+
+        if others.is_empty() {
+            return String::new();
+        }
+
+        if !others.iter().any(|other| other.file() == from.file()) {
+            return from.file_char().to_string();
+        }
+
+        if !others.iter().any(|other| other.rank() == from.rank()) {
+            return from.rank().to_string();
+        }
+
+        return from.to_string();
+    }
+
     fn is_moving(from : &Position, to: &Position) -> bool {
         return from != to;
     }
 }
 
+impl Clone for Movement {
+    fn clone(&self) -> Movement {
+        let clone_piece = |piece: &dyn Piece| piece_factory(piece.prefix(), *piece.color());
+
+        return match self {
+            Movement::CastleKingSide(piece) => Movement::CastleKingSide(clone_piece(piece.as_ref())),
+            Movement::CastleQueenSide(piece) => Movement::CastleQueenSide(clone_piece(piece.as_ref())),
+            Movement::Move(piece, from, to, promotion) => Movement::Move(clone_piece(piece.as_ref()), *from, *to, promotion.as_deref().map(clone_piece)),
+            Movement::Capture(piece, from, to, promotion) => Movement::Capture(clone_piece(piece.as_ref()), *from, *to, promotion.as_deref().map(clone_piece)),
+        }
+    }
+}
+
 impl Display for Movement {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         return match self {
@@ -85,4 +311,108 @@ impl Display for Movement {
             Movement::CastleQueenSide(_) => write!(f, "O-O-O"),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn movement(game: &Game, prefix: &str, from: &str, to: &str, promotion: Option<&str>) -> Movement {
+        let from = Position::from_string(from).unwrap();
+        let to = Position::from_string(to).unwrap();
+        let piece = piece_factory(prefix, *game.turn());
+        let promotion = promotion.map(|promotion| piece_factory(promotion, *game.turn()));
+
+        return match game.board().get_piece_at(&to).is_some() || (prefix == "P" && from.file() != to.file()) {
+            true => Movement::new_capture(piece, from, to, promotion).unwrap(),
+            false => Movement::new_move(piece, from, to, promotion).unwrap(),
+        }
+    }
+
+    #[test]
+    fn writes_pawn_moves_without_prefix(){
+        let game = Game::from_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+
+        assert_eq!(movement(&game, "P", "e4", "e5", None).to_san(&game), Ok(String::from("e5")));
+        assert_eq!(movement(&game, "P", "e4", "d5", None).to_san(&game), Ok(String::from("exd5")));
+    }
+
+    #[test]
+    fn writes_en_passant_and_promotions(){
+        let game = Game::from_fen("8/1P2k3/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+
+        assert_eq!(movement(&game, "P", "e5", "d6", None).to_san(&game), Ok(String::from("exd6+")));
+        assert_eq!(movement(&game, "P", "b7", "b8", Some("N")).to_san(&game), Ok(String::from("b8=N")));
+    }
+
+    #[test]
+    fn disambiguates_only_when_needed(){
+        let game = Game::from_fen("1k6/8/8/Q7/8/8/8/Q3Q1RK w - - 0 1").unwrap();
+
+        assert_eq!(movement(&game, "Q", "a1", "c3", None).to_san(&game), Ok(String::from("Qa1c3")));
+        assert_eq!(movement(&game, "Q", "a5", "a3", None).to_san(&game), Ok(String::from("Q5a3")));
+        assert_eq!(movement(&game, "Q", "e1", "d1", None).to_san(&game), Ok(String::from("Qed1")));
+        assert_eq!(movement(&game, "R", "g1", "g8", None).to_san(&game), Ok(String::from("Rg8+")));
+    }
+
+    #[test]
+    fn ignores_pinned_pieces_when_disambiguating(){
+        let game = Game::from_fen("4k3/4r3/8/8/8/8/4N3/2N1K3 w - - 0 1").unwrap();
+
+        assert_eq!(movement(&game, "N", "c1", "d3", None).to_san(&game), Ok(String::from("Nd3")));
+    }
+
+    #[test]
+    fn writes_castles_and_checkmates(){
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+
+        assert_eq!(Movement::CastleKingSide(Box::new(King::new(Color::White))).to_san(&game), Ok(String::from("O-O")));
+        assert_eq!(movement(&game, "R", "h1", "h8", None).to_san(&game), Err(String::from("Rook at h1 will colide with Pawn at h7 to move to h8")));
+
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/3RK3 w - - 0 1").unwrap();
+
+        assert_eq!(movement(&game, "R", "d1", "d8", None).to_san(&game), Ok(String::from("Rd8#")));
+    }
+
+    #[test]
+    fn reads_uci_movements(){
+        let game = Game::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+
+        assert_eq!(Movement::from_uci("e5e6", &game).map(|movement| movement.to_string()), Ok(String::from("Pe5e6")));
+        assert_eq!(Movement::from_uci("e5d6", &game).map(|movement| movement.to_string()), Ok(String::from("Pe5xd6")));
+        assert_eq!(Movement::from_uci("b7a8q", &game).map(|movement| movement.to_string()), Ok(String::from("Pb7xa8=Q")));
+        assert_eq!(Movement::from_uci("a1a8", &game).map(|movement| movement.to_string()), Ok(String::from("Ra1xa8")));
+        assert!(Movement::from_uci("b7b8", &game).is_err());
+        assert!(Movement::from_uci("e5e7", &game).is_err());
+        assert!(Movement::from_uci("e9e5", &game).is_err());
+        assert!(Movement::from_uci("d1d2", &game).is_err());
+    }
+
+    #[test]
+    fn reads_uci_castles_in_both_styles(){
+        let game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+
+        for uci in ["e8g8", "e8h8"] {
+            assert!(matches!(Movement::from_uci(uci, &game), Ok(Movement::CastleKingSide(_))), "{}", uci);
+        }
+
+        for uci in ["e8c8", "e8a8"] {
+            assert!(matches!(Movement::from_uci(uci, &game), Ok(Movement::CastleQueenSide(_))), "{}", uci);
+        }
+
+        assert!(matches!(Movement::from_uci("e8f8", &game), Ok(Movement::Move(..))));
+        assert!(Movement::from_uci("e1g1", &game).is_err());
+    }
+
+    #[test]
+    fn writes_uci_movements(){
+        let game = Game::from_fen("4k3/1P6/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+
+        assert_eq!(movement(&game, "P", "b7", "b8", Some("N")).to_uci(false), "b7b8n");
+        assert_eq!(movement(&game, "K", "e1", "f1", None).to_uci(false), "e1f1");
+        assert_eq!(Movement::CastleKingSide(Box::new(King::new(Color::White))).to_uci(false), "e1g1");
+        assert_eq!(Movement::CastleKingSide(Box::new(King::new(Color::White))).to_uci(true), "e1h1");
+        assert_eq!(Movement::CastleQueenSide(Box::new(King::new(Color::Black))).to_uci(false), "e8c8");
+    }
+}
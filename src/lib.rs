@@ -1,5 +1,16 @@
+// Every function in the crate ends with an explicit `return`, which clippy would flag hundreds of times.
+#![allow(clippy::needless_return)]
+
 pub mod board;
 pub mod piece;
 pub mod color;
 pub mod parser;
-pub mod game;
\ No newline at end of file
+pub mod game;
+pub mod book;
+pub mod tablebase;
+pub mod opening;
+pub mod eval;
+pub mod search;
+pub mod engine;
+#[cfg(feature = "serde")]
+pub mod serialization;
\ No newline at end of file
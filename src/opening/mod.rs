@@ -0,0 +1,205 @@
+use std::{collections::HashMap, fmt::Display, sync::OnceLock};
+
+use crate::{book::zobrist::polyglot_key, game::Game, parser::pgn::from_movetext};
+
+/// The bundled opening lines, one per row: the ECO code, the name and the movetext.
+const ECO_TABLE : &str = include_str!("eco.tsv");
+
+/// An opening from the Encyclopaedia of Chess Openings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opening {
+    eco: String,
+    name: String,
+    variation: Option<String>,
+}
+
+impl Opening {
+    /// Splits a full name like `Sicilian Defense: Najdorf Variation` into the opening and its variation.
+    fn new(eco: &str, full_name: &str) -> Opening {
+        let (name, variation) = match full_name.split_once(": ") {
+            Some((name, variation)) => (name, Some(variation.to_string())),
+            None => (full_name, None),
+        };
+
+        Opening {
+            eco: eco.to_string(),
+            name: name.to_string(),
+            variation,
+        }
+    }
+
+    pub fn eco(&self) -> &str {
+        &self.eco
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn variation(&self) -> Option<&str> {
+        self.variation.as_deref()
+    }
+}
+
+impl Display for Opening {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.variation {
+            Some(variation) => write!(f, "{} {}: {}", self.eco, self.name, variation),
+            None => write!(f, "{} {}", self.eco, self.name),
+        }
+    }
+}
+
+struct EcoTable {
+    openings: HashMap<u64, Opening>,
+}
+
+fn eco_table() -> &'static EcoTable {
+    static TABLE : OnceLock<EcoTable> = OnceLock::new();
+
+    return TABLE.get_or_init(|| {
+        let mut table = EcoTable {
+            openings: HashMap::new(),
+        };
+
+        for row in ECO_TABLE.lines().skip(1) {
+            let columns : Vec<&str> = row.split('\t').collect();
+
+            if columns.len() != 3 {
+                continue;
+            }
+
+            let game = match from_movetext(columns[2]) {
+                Ok(game) => game,
+                Err(e) => panic!("Invalid line for {} in the ECO table: {}", columns[0], e),
+            };
+
+            table.openings.entry(polyglot_key(&game)).or_insert_with(|| Opening::new(columns[0], columns[1]));
+        }
+
+        return table;
+    });
+}
+
+/// Finds the opening of a game: the last position of the game found in the ECO table, at any ply, so move orders
+/// transposing into a known line are recognized even after a detour.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::{opening, parser::pgn::from_movetext};
+///
+/// // The Najdorf, reached through an unusual move order.
+/// let game = from_movetext("1.e4 c5 2.Nf3 d6 3.d4 cxd4 4.Nxd4 a6 5.Nc3 Nf6 6.Be3").unwrap();
+///
+/// let opening = opening::classify(&game).unwrap();
+///
+/// assert_eq!(opening.eco(), "B90");
+/// assert_eq!(opening.name(), "Sicilian Defense");
+/// assert_eq!(opening.variation(), Some("Najdorf Variation"));
+/// ```
+pub fn classify(game: &Game) -> Option<Opening> {
+    let table = eco_table();
+
+    let mut replay = match Game::from_fen(game.start_fen()) {
+        Ok(replay) => replay,
+        Err(_) => return None,
+    };
+
+    let mut opening = table.openings.get(&polyglot_key(&replay));
+
+    for movement in game.movements() {
+        if replay.play(movement.clone()).is_err() {
+            break;
+        }
+
+        if let Some(found) = table.openings.get(&polyglot_key(&replay)) {
+            opening = Some(found);
+        }
+    }
+
+    return opening.cloned();
+}
+
+/// Sets the `ECO`, `Opening` and `Variation` tags the game does not have yet, as when importing a database whose
+/// games miss them. Returns the opening found, if any.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::{opening, parser::pgn_reader::PgnReader};
+///
+/// let pgn = "[Event \"Club\"]\n[ECO \"C20\"]\n\n1.e4 e5 2.Nf3 Nc6 3.Bb5 *\n";
+///
+/// for pgn_game in PgnReader::new(pgn.as_bytes()) {
+///     let mut game = pgn_game.unwrap().game().unwrap();
+///
+///     opening::fill_tags(&mut game);
+///
+///     assert_eq!(game.tag("ECO"), Some("C20"));
+///     assert_eq!(game.tag("Opening"), Some("Ruy Lopez"));
+/// }
+/// ```
+pub fn fill_tags(game: &mut Game) -> Option<Opening> {
+    let opening = classify(game)?;
+
+    if game.tag("ECO").is_none() {
+        game.set_tag("ECO", opening.eco());
+    }
+
+    if game.tag("Opening").is_none() {
+        game.set_tag("Opening", opening.name());
+    }
+
+    if let Some(variation) = opening.variation() {
+        if game.tag("Variation").is_none() {
+            game.set_tag("Variation", variation);
+        }
+    }
+
+    return Some(opening);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::pgn::from_movetext;
+
+    use super::*;
+
+    #[test]
+    fn reads_the_whole_table(){
+        let rows = ECO_TABLE.lines().skip(1).count();
+
+        // Every line reaches its own position.
+        assert_eq!(eco_table().openings.len(), rows);
+    }
+
+    #[test]
+    fn recognizes_transpositions(){
+        let queens_gambit = from_movetext("1.Nf3 d5 2.d4 Nf6 3.c4 e6 4.Nc3").unwrap();
+        assert_eq!(classify(&queens_gambit).unwrap().to_string(), "D37 Queen's Gambit Declined: Three Knights Variation");
+
+        let english = from_movetext("1.c4 e5 2.Nc3 Nf6 3.g3 d5 4.cxd5 Nxd5").unwrap();
+        assert_eq!(classify(&english).unwrap().to_string(), "A22 English Opening: King's English Variation, Two Knights Variation");
+
+        let detour : String = (0..15).map(|i| format!("{}.Nf3 Nf6 {}.Ng1 Ng8 ", 2 * i + 1, 2 * i + 2)).collect();
+        let ruy_lopez = from_movetext(&format!("{}31.e4 e5 32.Nf3 Nc6 33.Bb5", detour)).unwrap();
+        assert_eq!(classify(&ruy_lopez).unwrap().name(), "Ruy Lopez");
+
+        assert!(classify(&Game::new_classical()).is_none());
+        assert!(classify(&Game::from_fen("8/8/8/4k3/8/8/8/KQ6 w - - 0 1").unwrap()).is_none());
+    }
+
+    #[test]
+    fn fills_missing_tags(){
+        let mut game = from_movetext("1.d4 Nf6 2.c4 e6 3.Nc3 Bb4 4.Qc2 O-O").unwrap();
+        game.set_tag("Opening", "Nimzo");
+
+        let opening = fill_tags(&mut game).unwrap();
+
+        assert_eq!(opening.eco(), "E32");
+        assert_eq!(game.tag("ECO"), Some("E32"));
+        assert_eq!(game.tag("Opening"), Some("Nimzo"));
+        assert_eq!(game.tag("Variation"), Some("Classical Variation"));
+    }
+}
@@ -0,0 +1,290 @@
+use std::io::{Read, Write};
+
+use crate::game::{Game, movement::Movement};
+
+use super::fen::CLASSICAL;
+
+/// Version byte starting every game record.
+const VERSION : u8 = 1;
+
+/// Writes a game in the compact binary format.
+///
+/// A record is made of the version byte, the tag count followed by each name and value, the starting FEN (empty for
+/// the classical position), the movement count and then one byte per movement: its index in the legal movements of
+/// the position, sorted by their UCI notation. Lengths and counts are LEB128 variable length integers and strings are
+/// UTF-8. Annotations are not stored.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::parser::{binary, pgn::from_movetext};
+///
+/// let game = from_movetext("1.e4 e5 2.Nf3 Nc6 3.Bb5").unwrap();
+///
+/// let bytes = binary::to_binary(&game).unwrap();
+///
+/// assert_eq!(bytes.len(), 9);
+/// assert_eq!(binary::from_binary(&bytes).unwrap().to_pgn().unwrap(), game.to_pgn().unwrap());
+/// ```
+pub fn to_binary(game: &Game) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+
+    write_game(game, &mut bytes)?;
+
+    return Ok(bytes);
+}
+
+/// Reads a single game written by [`to_binary`].
+pub fn from_binary(bytes: &[u8]) -> Result<Game, String> {
+    return match BinaryReader::new(bytes).next() {
+        Some(game) => game,
+        None => Err(String::from("No game found in binary data")),
+    }
+}
+
+/// Appends a game record to a writer, so many games can be archived in one file.
+pub fn write_game<W: Write>(game: &Game, writer: &mut W) -> Result<(), String> {
+    let mut bytes = vec![VERSION];
+
+    write_number(&mut bytes, game.tags().len() as u32);
+
+    for (name, value) in game.tags() {
+        write_string(&mut bytes, name);
+        write_string(&mut bytes, value);
+    }
+
+    let start_fen = match game.start_fen() {
+        CLASSICAL => "",
+        start_fen => start_fen,
+    };
+
+    write_string(&mut bytes, start_fen);
+    write_number(&mut bytes, game.movements().len() as u32);
+
+    let mut replay = Game::from_fen(game.start_fen())?;
+
+    for movement in game.movements() {
+        let uci = movement.to_uci(false);
+
+        let index = match sorted_movements(&replay).iter().position(|legal| legal.to_uci(false) == uci) {
+            Some(index) => index,
+            None => return Err(format!("Movement {} is not legal in {}", movement, replay.to_fen())),
+        };
+
+        bytes.push(index as u8);
+
+        replay.play(movement.clone())?;
+    }
+
+    return writer.write_all(&bytes).map_err(|e| format!("Cannot write game: {}", e));
+}
+
+/// Reads game records one at a time from a binary source.
+pub struct BinaryReader<R: Read> {
+    reader: R,
+    done: bool,
+}
+
+impl<R: Read> BinaryReader<R> {
+    pub fn new(reader: R) -> BinaryReader<R> {
+        BinaryReader {
+            reader,
+            done: false,
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, String> {
+        let mut byte = [0];
+
+        return match self.reader.read(&mut byte) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(byte[0])),
+            Err(e) => Err(format!("Cannot read game: {}", e)),
+        }
+    }
+
+    fn expect_byte(&mut self) -> Result<u8, String> {
+        return match self.read_byte()? {
+            Some(byte) => Ok(byte),
+            None => Err(String::from("Unexpected end of binary game")),
+        }
+    }
+
+    fn read_number(&mut self) -> Result<u32, String> {
+        let mut number : u32 = 0;
+
+        for shift in (0..35).step_by(7) {
+            let byte = self.expect_byte()?;
+
+            number |= ((byte & 0x7f) as u32) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(number);
+            }
+        }
+
+        return Err(String::from("Invalid number in binary game"));
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        let length = self.read_number()? as usize;
+        let mut bytes = Vec::new();
+
+        // The buffer only grows with the bytes actually read, so a corrupt length cannot allocate gigabytes.
+        if let Err(e) = (&mut self.reader).take(length as u64).read_to_end(&mut bytes) {
+            return Err(format!("Cannot read game: {}", e));
+        }
+
+        if bytes.len() != length {
+            return Err(String::from("Unexpected end of binary game"));
+        }
+
+        return String::from_utf8(bytes).map_err(|_| String::from("Invalid UTF-8 string in binary game"));
+    }
+
+    fn read_game(&mut self, version: u8) -> Result<Game, String> {
+        if version != VERSION {
+            return Err(format!("Unknown binary game version {}", version));
+        }
+
+        let mut tags = Vec::new();
+
+        for _ in 0..self.read_number()? {
+            tags.push((self.read_string()?, self.read_string()?));
+        }
+
+        let mut game = match self.read_string()?.as_str() {
+            "" => Game::new_classical(),
+            start_fen => Game::from_fen(start_fen)?,
+        };
+
+        for (name, value) in tags {
+            game.set_tag(&name, &value);
+        }
+
+        for _ in 0..self.read_number()? {
+            let index = self.expect_byte()? as usize;
+
+            let movement = match sorted_movements(&game).into_iter().nth(index) {
+                Some(movement) => movement,
+                None => return Err(format!("Movement index {} is out of range in {}", index, game.to_fen())),
+            };
+
+            game.play(movement)?;
+        }
+
+        return Ok(game);
+    }
+}
+
+impl<R: Read> Iterator for BinaryReader<R> {
+    type Item = Result<Game, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let version = match self.read_byte() {
+            Ok(Some(version)) => version,
+            Ok(None) => {
+                self.done = true;
+                return None;
+            },
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            },
+        };
+
+        let game = self.read_game(version);
+
+        // A broken record leaves the reader in the middle of it, so nothing after it can be trusted.
+        if game.is_err() {
+            self.done = true;
+        }
+
+        return Some(game);
+    }
+}
+
+/// The legal movements of the position sorted by their UCI notation. There are never more than 218 of them.
+fn sorted_movements(game: &Game) -> Vec<Movement> {
+    let mut movements = game.legal_movements();
+
+    movements.sort_by_cached_key(|movement| movement.to_uci(false));
+
+    return movements;
+}
+
+fn write_number(bytes: &mut Vec<u8>, number: u32) {
+    let mut number = number;
+
+    while number >= 0x80 {
+        bytes.push((number as u8 & 0x7f) | 0x80);
+        number >>= 7;
+    }
+
+    bytes.push(number as u8);
+}
+
+fn write_string(bytes: &mut Vec<u8>, string: &str) {
+    write_number(bytes, string.len() as u32);
+    bytes.extend_from_slice(string.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::pgn_reader::PgnReader;
+
+    use super::*;
+
+    const CORPUS : &str = include_str!("fixtures/classics.pgn");
+
+    #[test]
+    fn round_trips_the_corpus(){
+        let records : Vec<_> = PgnReader::new(CORPUS.as_bytes()).map(|record| record.unwrap()).collect();
+        let games : Vec<Game> = records.iter().map(|record| record.game().unwrap()).collect();
+
+        let mut archive = Vec::new();
+
+        for game in &games {
+            write_game(game, &mut archive).unwrap();
+        }
+
+        // The tags are stored as they are, so the movements are what the format compresses.
+        let movetext_size : usize = records.iter().map(|record| record.movetext().len()).sum();
+        let movements_size : usize = games.iter().map(|game| {
+            let mut bare = Game::from_fen(game.start_fen()).unwrap();
+            game.movements().iter().for_each(|movement| bare.play(movement.clone()).unwrap());
+
+            return to_binary(&bare).unwrap().len();
+        }).sum();
+
+        assert!(movements_size * 3 < movetext_size, "{} bytes against {} for PGN", movements_size, movetext_size);
+        assert!(archive.len() < CORPUS.len());
+
+        let restored : Vec<Game> = BinaryReader::new(archive.as_slice()).map(|game| game.unwrap()).collect();
+
+        assert_eq!(games.len(), 8);
+        assert_eq!(restored.len(), games.len());
+
+        for (restored, game) in restored.iter().zip(&games) {
+            assert_eq!(restored.to_pgn().unwrap(), game.to_pgn().unwrap());
+        }
+    }
+
+    #[test]
+    fn rejects_broken_records(){
+        let bytes = to_binary(&Game::new_classical()).unwrap();
+
+        assert!(from_binary(&[]).is_err());
+        assert!(from_binary(&[2, 0, 0, 0]).is_err());
+        assert!(from_binary(&bytes[..bytes.len() - 1]).is_err());
+        assert!(from_binary(&[VERSION, 0, 0, 1, 255]).is_err());
+
+        // A tag name claiming to be 4 GB long, then a truncated one.
+        assert!(from_binary(&[VERSION, 1, 0xff, 0xff, 0xff, 0xff, 0x0f, b'E']).is_err());
+        assert!(from_binary(&[VERSION, 1, 5, b'E', b'v']).is_err());
+    }
+}
@@ -0,0 +1,371 @@
+use crate::{board::position::Position, color::Color, game::{Game, movement::Movement}, piece::pieces::pawn::Pawn};
+
+/// The names of the files, from the Queen's Rook file to the King's Rook file.
+const FILES : [&str; 8] = ["QR", "QN", "QB", "Q", "K", "KB", "KN", "KR"];
+
+/// A piece as descriptive notation names it: its kind, the wing or file it stands on, and the tile it stands on.
+struct Designation{
+    prefix: String,
+    qualifier: Option<String>,
+    tiles: Option<Vec<Position>>,
+}
+
+impl Designation {
+    /// Reads designations like `P`, `QBP`, `KN`, `Kt` or `R/Q1`.
+    fn parse(text: &str, color: &Color) -> Option<Designation> {
+        let (piece, tiles) = match text.split_once('/') {
+            Some((piece, tile)) => (piece, Some(parse_tiles(tile, color)?)),
+            None => (text, None),
+        };
+
+        let (prefix, qualifier) = match piece.strip_suffix("Kt") {
+            Some(qualifier) => ("N", qualifier),
+            None => match piece.char_indices().last() {
+                Some((index, c)) if "PNBRQK".contains(c) => (&piece[index..], &piece[..index]),
+                _ => return None,
+            },
+        };
+
+        let qualifier = match qualifier {
+            "" => None,
+            "K" | "Q" => Some(qualifier.to_string()),
+            "R" | "N" | "B" if prefix == Pawn::prefix() => Some(qualifier.to_string()),
+            _ if prefix == Pawn::prefix() && FILES.contains(&qualifier) => Some(qualifier.to_string()),
+            _ => return None,
+        };
+
+        return Some(Designation {
+            prefix: prefix.to_string(),
+            qualifier,
+            tiles,
+        });
+    }
+
+    /// A Pawn's qualifier names its file, possibly shortened to `R`, `N` or `B`. Any other piece's names its wing.
+    fn matches(&self, prefix: &str, position: &Position) -> bool {
+        if self.prefix != prefix {
+            return false;
+        }
+
+        let qualified = match &self.qualifier {
+            Some(qualifier) if prefix == Pawn::prefix() => {
+                let file = FILES[position.file() as usize - 1];
+                file == qualifier || (qualifier.len() == 1 && file.len() == 2 && file.ends_with(qualifier.as_str()))
+            },
+            Some(qualifier) if qualifier == "K" => position.file() >= 5,
+            Some(_) => position.file() <= 4,
+            None => true,
+        };
+
+        return qualified && self.tiles.as_ref().is_none_or(|tiles| tiles.contains(position));
+    }
+}
+
+/// Reads a movement written in English descriptive notation, such as `P-K4`, `N-KB3`, `NxQP` or `PxP e.p.`.
+///
+/// Ranks are counted from the side of the player moving. Files may be shortened to `R`, `N` or `B` and captured
+/// pieces named without their file whenever only one legal movement matches.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::{parser::{descriptive, pgn::from_movetext}};
+///
+/// let game = from_movetext("1.e4 e5 2.Nf3").unwrap();
+///
+/// let movement = descriptive::parse(&game, "N-QB3").unwrap();
+///
+/// assert_eq!(movement.to_san(&game).unwrap(), "Nc6");
+/// assert_eq!(descriptive::format(&movement, &game).unwrap(), "N-QB3");
+/// ```
+pub fn parse(game: &Game, descriptive: &str) -> Result<Movement, String> {
+    let mut cleaned = descriptive.trim();
+
+    while let Some(suffix) = ["e.p.", "mate", "ch", "+", "#", "!", "?"].iter().find(|suffix| cleaned.ends_with(*suffix)) {
+        cleaned = cleaned[..cleaned.len() - suffix.len()].trim_end();
+    }
+
+    let castles = match cleaned {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    };
+
+    if let Some(king_side) = castles {
+        let movement = game.legal_movements().into_iter().find(|movement| {
+            return match movement {
+                Movement::CastleKingSide(_) => king_side,
+                Movement::CastleQueenSide(_) => !king_side,
+                _ => false,
+            }
+        });
+
+        return movement.ok_or(format!("Illegal descriptive movement {}", descriptive));
+    }
+
+    let (cleaned, promotion) = split_promotion(cleaned);
+
+    let invalid = || format!("Invalid descriptive movement {}", descriptive);
+
+    let separator = cleaned.find(['-', 'x']).ok_or_else(invalid)?;
+    let is_capture = cleaned[separator..].starts_with('x');
+
+    let color = game.turn();
+    let piece = Designation::parse(&cleaned[..separator], color).ok_or_else(invalid)?;
+    let target = &cleaned[separator + 1..];
+
+    let (captured, tiles) = match is_capture {
+        true => (Some(Designation::parse(target, color).ok_or_else(invalid)?), None),
+        false => (None, Some(parse_tiles(target, color).ok_or_else(invalid)?)),
+    };
+
+    let candidates : Vec<Movement> = game.legal_movements().into_iter().filter(|movement| {
+        let (from, to) = match (movement.from(), movement.to()) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return false,
+        };
+
+        if !piece.matches(movement.piece().prefix(), from) {
+            return false;
+        }
+
+        if movement.promotion().map(|promotion| promotion.prefix()) != promotion {
+            return false;
+        }
+
+        return match (&captured, &tiles) {
+            (Some(captured), _) => match captured_piece(game, movement) {
+                Some((prefix, position)) => captured.matches(&prefix, &position),
+                None => false,
+            },
+            (None, Some(tiles)) => matches!(movement, Movement::Move(..)) && tiles.contains(to),
+            _ => false,
+        };
+    }).collect();
+
+    return match candidates.len() {
+        0 => Err(format!("Illegal descriptive movement {}", descriptive)),
+        1 => Ok(candidates.into_iter().next().unwrap()),
+        _ => Err(format!("Ambiguous descriptive movement {}", descriptive)),
+    }
+}
+
+/// Writes the movement in English descriptive notation, using the shortest form that only this movement matches.
+pub fn format(movement: &Movement, game: &Game) -> Result<String, String> {
+    let suffix = match movement.check_suffix(game)? {
+        "#" => "mate",
+        "+" => "ch",
+        _ => "",
+    };
+
+    let (piece, from, to, promotion) = match movement {
+        Movement::CastleKingSide(_) => return Ok(format!("O-O{}", suffix)),
+        Movement::CastleQueenSide(_) => return Ok(format!("O-O-O{}", suffix)),
+        Movement::Move(piece, from, to, promotion) | Movement::Capture(piece, from, to, promotion) => (piece, from, to, promotion),
+    };
+
+    let color = piece.color();
+
+    let promotion = match promotion {
+        Some(promotion) => format!("={}", promotion.prefix()),
+        None => String::new(),
+    };
+
+    let pieces = designations(piece.prefix(), from, color);
+
+    let targets = match captured_piece(game, movement) {
+        Some((prefix, position)) => designations(&prefix, &position, color).into_iter().map(|captured| format!("x{}", captured)).collect(),
+        None => {
+            let full = tile_name(to, color);
+
+            let mut targets = vec![format!("-{}", full)];
+
+            if full.len() == 3 {
+                targets.insert(0, format!("-{}", &full[1..]));
+            }
+
+            targets
+        },
+    };
+
+    let mut candidates = Vec::new();
+
+    for piece in &pieces {
+        for target in &targets {
+            candidates.push(format!("{}{}{}", piece, target, promotion));
+        }
+    }
+
+    candidates.sort_by_key(|candidate| candidate.len());
+
+    let uci = movement.to_uci(false);
+
+    for candidate in &candidates {
+        match parse(game, candidate) {
+            Ok(parsed) if parsed.to_uci(false) == uci => return Ok(format!("{}{}", candidate, suffix)),
+            _ => continue,
+        }
+    }
+
+    return Err(format!("Cannot write movement {} in descriptive notation", movement));
+}
+
+/// The ways to name a piece, from the shortest to the most precise.
+fn designations(prefix: &str, position: &Position, color: &Color) -> Vec<String> {
+    let qualifier = match prefix {
+        "P" => FILES[position.file() as usize - 1],
+        _ if position.file() >= 5 => "K",
+        _ => "Q",
+    };
+
+    let mut designations = vec![prefix.to_string(), format!("{}{}", qualifier, prefix)];
+
+    if qualifier.len() == 2 {
+        designations.push(format!("{}{}", &qualifier[1..], prefix));
+    }
+
+    designations.push(format!("{}/{}", prefix, tile_name(position, color)));
+
+    return designations;
+}
+
+/// The piece taken by a capture and the tile it stood on, which is not the destination for en passant.
+fn captured_piece(game: &Game, movement: &Movement) -> Option<(String, Position)> {
+    let (from, to) = match movement {
+        Movement::Capture(_, from, to, _) => (from, to),
+        _ => return None,
+    };
+
+    return match game.board().get_piece_at(to) {
+        Some(piece) => Some((piece.prefix().to_string(), *to)),
+        None => Position::new(to.file(), from.rank()).ok().map(|position| (Pawn::prefix().to_string(), position)),
+    }
+}
+
+fn tile_name(position: &Position, color: &Color) -> String {
+    let rank = match color {
+        Color::White => position.rank(),
+        Color::Black => 9 - position.rank(),
+    };
+
+    return format!("{}{}", FILES[position.file() as usize - 1], rank);
+}
+
+/// Reads tiles like `K4` or `QB3`. Shortened files like `B3` stand for the tiles on both wings.
+fn parse_tiles(text: &str, color: &Color) -> Option<Vec<Position>> {
+    let rank = text.chars().last()?.to_digit(10)? as u8;
+
+    if !(1..=8).contains(&rank) {
+        return None;
+    }
+
+    let rank = match color {
+        Color::White => rank,
+        Color::Black => 9 - rank,
+    };
+
+    let name = &text[..text.len() - 1];
+
+    let names = match name {
+        "R" | "N" | "B" | "Kt" => vec![format!("Q{}", name), format!("K{}", name)],
+        _ => vec![name.to_string()],
+    };
+
+    let mut tiles = Vec::new();
+
+    for name in names {
+        let name = name.replace("Kt", "N");
+
+        match FILES.iter().position(|file| *file == name) {
+            Some(file) => tiles.push(Position::new(file as u8 + 1, rank).ok()?),
+            None => return None,
+        }
+    }
+
+    return Some(tiles);
+}
+
+/// Splits a promotion written as `=Q`, `(Q)` or `/Q` from the rest of the movement.
+fn split_promotion(text: &str) -> (&str, Option<&str>) {
+    for (start, end) in [("=", ""), ("(", ")"), ("/", "")] {
+        let body = match text.strip_suffix(end) {
+            Some(body) => body,
+            None => continue,
+        };
+
+        for (letter, prefix) in [("Q", "Q"), ("R", "R"), ("B", "B"), ("N", "N"), ("Kt", "N")] {
+            if let Some(rest) = body.strip_suffix(&format!("{}{}", start, letter)) {
+                return (rest, Some(prefix));
+            }
+        }
+    }
+
+    return (text, None);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::pgn::from_movetext;
+
+    use super::*;
+
+    fn san(game: &Game, descriptive: &str) -> String {
+        return parse(game, descriptive).unwrap().to_san(game).unwrap();
+    }
+
+    #[test]
+    fn reads_descriptive(){
+        let game = Game::new_classical();
+        assert_eq!(san(&game, "P-K4"), "e4");
+        assert_eq!(san(&game, "KKt-B3"), "Nf3");
+        assert!(parse(&game, "N-B3").is_err());
+
+        let game = from_movetext("1.e4 e5 2.Nf3 Nc6 3.Bb5 a6 4.Bxc6").unwrap();
+        assert_eq!(san(&game, "QPxB"), "dxc6");
+        assert_eq!(san(&game, "NPxB"), "bxc6");
+        assert!(parse(&game, "PxB").is_err());
+
+        let game = from_movetext("1.e4 Nf6 2.e5 d5").unwrap();
+        assert_eq!(san(&game, "PxP e.p."), "exd6");
+        assert_eq!(san(&game, "PxN"), "exf6");
+
+        let game = Game::from_fen("3qk3/4P3/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert_eq!(san(&game, "PxQ=Q ch"), "exd8=Q+");
+        assert_eq!(san(&game, "PxQ(Kt)"), "exd8=N");
+        assert_eq!(san(&game, "O-O"), "O-O");
+    }
+
+    #[test]
+    fn writes_descriptive(){
+        let game = from_movetext("1.e4 e5 2.Nf3 Nc6 3.Bb5 a6 4.Bxc6").unwrap();
+        let dxc6 = parse(&game, "QPxB").unwrap();
+        assert_eq!(format(&dxc6, &game).unwrap(), "QPxB");
+
+        let game = from_movetext("1.e4 e5 2.Nf3 Nc6 3.Bb5").unwrap();
+        let a6 = parse(&game, "P-QR3").unwrap();
+        assert_eq!(format(&a6, &game).unwrap(), "P-QR3");
+
+        let nf6 = parse(&game, "KN-KB3").unwrap();
+        assert_eq!(format(&nf6, &game).unwrap(), "N-B3");
+    }
+
+    #[test]
+    fn round_trips_every_legal_movement(){
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2",
+        ];
+
+        for fen in fens {
+            let game = Game::from_fen(fen).unwrap();
+
+            for movement in game.legal_movements() {
+                let descriptive = format(&movement, &game).unwrap();
+
+                assert_eq!(parse(&game, &descriptive).unwrap().to_uci(false), movement.to_uci(false), "{} in {}", descriptive, fen);
+            }
+        }
+    }
+}
@@ -1,4 +1,9 @@
-use crate::board::Board;
+use std::fmt::Display;
+
+use crate::{board::{Board, position::Position}, color::Color, game::{Game, castle_rights::CastleRights}, piece::piece_factory};
+
+/// The FEN of the classical starting position.
+pub const CLASSICAL : &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
 pub struct Fen{
     fen: String,
@@ -11,7 +16,240 @@ impl Fen {
         }
     }
 
-    pub fn to_board(&self) -> Board{
-        Board::new()
+    pub fn from_game(game: &Game) -> Fen {
+        let board = game.board();
+        let mut fen = String::new();
+
+        for rank in (1..=8).rev() {
+            let mut empty = 0;
+
+            for file in 1..=8 {
+                let piece = match board.get_piece_at(&Position::new(file, rank).unwrap()) {
+                    Some(piece) => piece,
+                    None => {
+                        empty += 1;
+                        continue;
+                    },
+                };
+
+                if empty > 0 {
+                    fen.push_str(&empty.to_string());
+                    empty = 0;
+                }
+
+                match piece.color() {
+                    Color::White => fen.push_str(&piece.prefix().to_uppercase()),
+                    Color::Black => fen.push_str(&piece.prefix().to_lowercase()),
+                }
+            }
+
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+
+            if rank > 1 {
+                fen.push('/');
+            }
+        }
+
+        let turn = match game.turn() {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+
+        let mut castling = String::new();
+
+        for (color, king_side, queen_side) in [(Color::White, "K", "Q"), (Color::Black, "k", "q")] {
+            match board.get_castle_rights(&color) {
+                CastleRights::KingSide => castling.push_str(king_side),
+                CastleRights::QueenSide => castling.push_str(queen_side),
+                CastleRights::Both => {
+                    castling.push_str(king_side);
+                    castling.push_str(queen_side);
+                },
+                CastleRights::None => {},
+            }
+        }
+
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let en_passant = match board.en_passant() {
+            Some(position) => position.to_string(),
+            None => String::from("-"),
+        };
+
+        return Fen::new(format!("{} {} {} {} {} {}", fen, turn, castling, en_passant, game.halfmove_clock(), game.fullmove_number()));
     }
-}
\ No newline at end of file
+
+    pub fn to_board(&self) -> Result<Board, String> {
+        let fields : Vec<&str> = self.fen.split_whitespace().collect();
+
+        if fields.len() < 4 {
+            return Err(format!("Invalid FEN {}! Expected at least 4 fields", self.fen));
+        }
+
+        let mut white_castle_rights = CastleRights::None;
+        let mut black_castle_rights = CastleRights::None;
+
+        for c in fields[2].chars() {
+            match c {
+                'K' => white_castle_rights = Fen::grant_castle_right(white_castle_rights, CastleRights::KingSide),
+                'Q' => white_castle_rights = Fen::grant_castle_right(white_castle_rights, CastleRights::QueenSide),
+                'k' => black_castle_rights = Fen::grant_castle_right(black_castle_rights, CastleRights::KingSide),
+                'q' => black_castle_rights = Fen::grant_castle_right(black_castle_rights, CastleRights::QueenSide),
+                '-' => {},
+                _ => return Err(format!("Invalid FEN {}! Unknown castling right {}", self.fen, c)),
+            }
+        }
+
+        let mut board = Board::new(white_castle_rights, black_castle_rights);
+
+        let ranks : Vec<&str> = fields[0].split('/').collect();
+
+        if ranks.len() != 8 {
+            return Err(format!("Invalid FEN {}! Expected 8 ranks but found {}", self.fen, ranks.len()));
+        }
+
+        for (index, pieces) in ranks.iter().enumerate() {
+            let rank = 8 - index as u8;
+            let mut file : u8 = 1;
+
+            for c in pieces.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    if !(1..=8).contains(&empty) {
+                        return Err(format!("Invalid FEN {}! Wrong number of empty tiles {}", self.fen, c));
+                    }
+
+                    file = match file.checked_add(empty as u8) {
+                        Some(file) if file <= 9 => file,
+                        _ => return Err(format!("Invalid FEN {}! Rank {} has more than 8 tiles", self.fen, rank)),
+                    };
+
+                    continue;
+                }
+
+                if !"PNBRQK".contains(c.to_ascii_uppercase()) {
+                    return Err(format!("Invalid FEN {}! Unknown piece {}", self.fen, c));
+                }
+
+                let color = match c.is_uppercase() {
+                    true => Color::White,
+                    false => Color::Black,
+                };
+
+                let position = match Position::new(file, rank) {
+                    Ok(position) => position,
+                    Err(_) => return Err(format!("Invalid FEN {}! Rank {} has more than 8 tiles", self.fen, rank)),
+                };
+
+                board.set_piece_at(position, piece_factory(&c.to_ascii_uppercase().to_string(), color));
+                file += 1;
+            }
+
+            if file != 9 {
+                return Err(format!("Invalid FEN {}! Rank {} does not have 8 tiles", self.fen, rank));
+            }
+        }
+
+        for color in [Color::White, Color::Black] {
+            if board.king_position(&color).is_none() {
+                return Err(format!("Invalid FEN {}! There is no {} King", self.fen, color));
+            }
+        }
+
+        let en_passant = match fields[3] {
+            "-" => None,
+            square => Some(Position::from_string(square)?),
+        };
+
+        board.set_en_passant(en_passant)?;
+
+        return Ok(board);
+    }
+
+    pub fn to_game(&self) -> Result<Game, String> {
+        let board = self.to_board()?;
+        let fields : Vec<&str> = self.fen.split_whitespace().collect();
+
+        let turn = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(format!("Invalid FEN {}! Unknown side to move {}", self.fen, fields[1])),
+        };
+
+        let halfmove_clock = match fields.get(4) {
+            Some(field) => match field.parse::<u32>() {
+                Ok(halfmove_clock) => halfmove_clock,
+                Err(_) => return Err(format!("Invalid FEN {}! Halfmove clock is not a number", self.fen)),
+            },
+            None => 0,
+        };
+
+        let fullmove_number = match fields.get(5) {
+            Some(field) => match field.parse::<u32>() {
+                Ok(fullmove_number) => fullmove_number,
+                Err(_) => return Err(format!("Invalid FEN {}! Fullmove number is not a number", self.fen)),
+            },
+            None => 1,
+        };
+
+        return Ok(Game::new(board, turn, halfmove_clock, fullmove_number));
+    }
+
+    fn grant_castle_right(rights: CastleRights, right: CastleRights) -> CastleRights {
+        return match (rights, right) {
+            (CastleRights::None, right) => right,
+            (CastleRights::KingSide, CastleRights::QueenSide) => CastleRights::Both,
+            (CastleRights::QueenSide, CastleRights::KingSide) => CastleRights::Both,
+            (rights, _) => rights,
+        }
+    }
+}
+
+impl Display for Fen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.fen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{game::Game, parser::pgn::from_movetext};
+
+    use super::*;
+
+    #[test]
+    fn writes_the_classical_position(){
+        assert_eq!(Game::new_classical().to_fen(), CLASSICAL);
+    }
+
+    #[test]
+    fn writes_en_passant_and_clocks(){
+        let game = from_movetext("1.e4 Nf6 2.Nc3").unwrap();
+
+        assert_eq!(game.to_fen(), "rnbqkb1r/pppppppp/5n2/8/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 2");
+
+        let game = from_movetext("1.e4").unwrap();
+
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    }
+
+    #[test]
+    fn round_trips_a_position(){
+        let fen = "r3k2r/8/8/8/3pP3/8/8/R3K2R b Kq e3 0 23";
+
+        assert_eq!(Game::from_fen(fen).unwrap().to_fen(), fen);
+    }
+
+    #[test]
+    fn rejects_invalid_positions(){
+        assert!(Game::from_fen("8/8/8/8/8/8/8/8 w - - 0 1").is_err());
+        assert!(Game::from_fen("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_err());
+        assert!(Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1").is_err());
+        assert!(Game::from_fen("rnbqkbnr/pppppppp/08/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_err());
+        assert!(Game::from_fen("rnbqkbnr/pppppppp/8/8/72/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_err());
+        assert!(Game::from_fen(&format!("rnbqkbnr/pppppppp/{}/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "8".repeat(40))).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use crate::game::{Game, movement::Movement};
+
+/// Reads a movement written in ICCF numeric notation, such as `5254` or `47481`.
+///
+/// Files and ranks are both numbered from 1 to 8, castles are written as the King's move and an optional fifth
+/// digit gives the promotion: 1 for a Queen, 2 for a Rook, 3 for a Bishop and 4 for a Knight.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::{game::Game, parser::iccf};
+///
+/// let game = Game::new_classical();
+///
+/// let movement = iccf::parse(&game, "7163").unwrap();
+///
+/// assert_eq!(movement.to_san(&game).unwrap(), "Nf3");
+/// assert_eq!(iccf::format(&movement), "7163");
+/// ```
+pub fn parse(game: &Game, iccf: &str) -> Result<Movement, String> {
+    let digits : Vec<u32> = match iccf.trim().chars().map(|c| c.to_digit(10)).collect() {
+        Some(digits) => digits,
+        None => return Err(format!("Invalid ICCF movement {}! Expected only digits", iccf)),
+    };
+
+    if digits.len() != 4 && digits.len() != 5 {
+        return Err(format!("Invalid ICCF movement {}! Expected 4 or 5 digits", iccf));
+    }
+
+    let mut uci = String::new();
+
+    for (index, digit) in digits[..4].iter().enumerate() {
+        if !(1..=8).contains(digit) {
+            return Err(format!("Invalid ICCF movement {}! Tiles are numbered from 1 to 8", iccf));
+        }
+
+        match index % 2 {
+            0 => uci.push((b'a' + *digit as u8 - 1) as char),
+            _ => uci.push_str(&digit.to_string()),
+        }
+    }
+
+    if let Some(promotion) = digits.get(4) {
+        match promotion {
+            1 => uci.push('q'),
+            2 => uci.push('r'),
+            3 => uci.push('b'),
+            4 => uci.push('n'),
+            _ => return Err(format!("Invalid ICCF movement {}! Unknown promotion {}", iccf, promotion)),
+        }
+    }
+
+    return Movement::from_uci(&uci, game).map_err(|_| format!("Illegal ICCF movement {}", iccf));
+}
+
+/// Writes the movement in ICCF numeric notation.
+pub fn format(movement: &Movement) -> String {
+    let uci : Vec<char> = movement.to_uci(false).chars().collect();
+
+    let mut iccf = String::new();
+
+    for (index, c) in uci.iter().enumerate() {
+        let digit = match (index, c) {
+            (0 | 2, _) => (*c as u8 - b'a' + 1) as u32,
+            (4, 'q') => 1,
+            (4, 'r') => 2,
+            (4, 'b') => 3,
+            (4, 'n') => 4,
+            _ => c.to_digit(10).unwrap_or(0),
+        };
+
+        iccf.push_str(&digit.to_string());
+    }
+
+    return iccf;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::pgn::from_movetext;
+
+    use super::*;
+
+    #[test]
+    fn reads_iccf(){
+        let game = Game::from_fen("3qk3/4P3/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+
+        assert_eq!(parse(&game, "57481").unwrap().to_san(&game).unwrap(), "exd8=Q+");
+        assert_eq!(parse(&game, "57484").unwrap().to_san(&game).unwrap(), "exd8=N");
+        assert_eq!(parse(&game, "5171").unwrap().to_san(&game).unwrap(), "O-O");
+
+        assert!(parse(&game, "5758").is_err());
+        assert!(parse(&game, "5958").is_err());
+        assert!(parse(&game, "e7e8").is_err());
+    }
+
+    #[test]
+    fn round_trips_every_legal_movement(){
+        let game = from_movetext("1.e4 d5 2.exd5 Qxd5 3.Nc3 Qa5 4.d4 Nf6 5.Nf3 Bf5 6.Bd3").unwrap();
+
+        for movement in game.legal_movements() {
+            let iccf = format(&movement);
+
+            assert_eq!(parse(&game, &iccf).unwrap().to_uci(false), movement.to_uci(false), "{}", iccf);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::{color::Color, game::{Game, movement::Movement}};
+
+static LAN : LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([KQRBN])?([a-h][1-8])([-x])([a-h][1-8])(?:=?([QRBN]))?$").unwrap());
+
+/// Reads a movement written in long algebraic notation, such as `Nb1-c3`, `e7xd8=Q` or `O-O`.
+///
+/// Both tiles are always written, so the movement is resolved like a UCI movement and then checked against the
+/// piece letter and the capture sign.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::{game::Game, parser::long_algebraic};
+///
+/// let game = Game::new_classical();
+///
+/// let movement = long_algebraic::parse(&game, "Nb1-c3").unwrap();
+///
+/// assert_eq!(movement.to_san(&game).unwrap(), "Nc3");
+/// assert_eq!(long_algebraic::format(&movement, &game).unwrap(), "Nb1-c3");
+/// ```
+pub fn parse(game: &Game, lan: &str) -> Result<Movement, String> {
+    let cleaned = lan.trim().trim_end_matches(['+', '#', '!', '?']);
+
+    let castles_file = match cleaned {
+        "O-O" | "0-0" => Some('g'),
+        "O-O-O" | "0-0-0" => Some('c'),
+        _ => None,
+    };
+
+    if let Some(file) = castles_file {
+        let rank = match game.turn() {
+            Color::White => 1,
+            Color::Black => 8,
+        };
+
+        return Movement::from_uci(&format!("e{}{}{}", rank, file, rank), game).map_err(|_| format!("Illegal long algebraic movement {}", lan));
+    }
+
+    let captures = match LAN.captures(cleaned) {
+        Some(captures) => captures,
+        None => return Err(format!("Invalid long algebraic movement {}", lan)),
+    };
+
+    let promotion = captures.get(5).map_or(String::new(), |promotion| promotion.as_str().to_lowercase());
+
+    let movement = match Movement::from_uci(&format!("{}{}{}", &captures[2], &captures[4], promotion), game) {
+        Ok(movement) => movement,
+        Err(_) => return Err(format!("Illegal long algebraic movement {}", lan)),
+    };
+
+    let prefix = captures.get(1).map_or("P", |prefix| prefix.as_str());
+
+    let matches = match &movement {
+        // A King moving two tiles is accepted as a castle.
+        Movement::CastleKingSide(_) | Movement::CastleQueenSide(_) => prefix == "K" && &captures[3] == "-",
+        Movement::Move(piece, ..) => piece.prefix() == prefix && &captures[3] == "-",
+        Movement::Capture(piece, ..) => piece.prefix() == prefix && &captures[3] == "x",
+    };
+
+    if !matches {
+        return Err(format!("Long algebraic movement {} does not match the piece or capture on the board", lan));
+    }
+
+    return Ok(movement);
+}
+
+/// Writes the movement in long algebraic notation, with `+` or `#` when it checks or mates.
+pub fn format(movement: &Movement, game: &Game) -> Result<String, String> {
+    let mut lan = match movement {
+        Movement::CastleKingSide(_) => String::from("O-O"),
+        Movement::CastleQueenSide(_) => String::from("O-O-O"),
+        Movement::Move(piece, from, to, promotion) | Movement::Capture(piece, from, to, promotion) => {
+            let prefix = match piece.prefix() {
+                "P" => "",
+                prefix => prefix,
+            };
+
+            let separator = match movement {
+                Movement::Capture(..) => "x",
+                _ => "-",
+            };
+
+            let promotion = match promotion {
+                Some(promotion) => format!("={}", promotion.prefix()),
+                None => String::new(),
+            };
+
+            format!("{}{}{}{}{}", prefix, from, separator, to, promotion)
+        },
+    };
+
+    lan.push_str(movement.check_suffix(game)?);
+
+    return Ok(lan);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::pgn::from_movetext;
+
+    use super::*;
+
+    #[test]
+    fn reads_long_algebraic(){
+        let game = Game::from_fen("3qk3/4P3/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+
+        assert_eq!(parse(&game, "e7xd8=Q+").unwrap().to_san(&game).unwrap(), "exd8=Q+");
+        assert_eq!(parse(&game, "Ke1-g1").unwrap().to_san(&game).unwrap(), "O-O");
+        assert_eq!(parse(&game, "0-0").unwrap().to_san(&game).unwrap(), "O-O");
+
+        assert!(parse(&game, "e7-d8=Q").is_err());
+        assert!(parse(&game, "Re1-e2").is_err());
+        assert!(parse(&game, "Nb1-c3").is_err());
+    }
+
+    #[test]
+    fn round_trips_every_legal_movement(){
+        let game = from_movetext("1.e4 d5 2.exd5 Qxd5 3.Nc3 Qa5 4.d4 Nf6 5.Nf3 Bf5 6.Bd3").unwrap();
+
+        for movement in game.legal_movements() {
+            let lan = format(&movement, &game).unwrap();
+
+            assert_eq!(parse(&game, &lan).unwrap().to_uci(false), movement.to_uci(false), "{}", lan);
+        }
+    }
+}
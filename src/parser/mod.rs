@@ -1,2 +1,9 @@
-// pub mod fen;
-pub mod pgn;
\ No newline at end of file
+pub mod binary;
+pub mod descriptive;
+pub mod fen;
+pub mod iccf;
+pub mod long_algebraic;
+pub mod pgn;
+pub mod pgn_reader;
+pub mod pgn_tree;
+pub mod san;
\ No newline at end of file
@@ -1,132 +1,190 @@
-use regex::Regex;
-
-use crate::{game::{Game, movement::Movement}, color::Color, piece::{pieces::king::King, Piece, piece_factory}, board::position::Position};
+use crate::{game::Game, color::Color};
+
+use super::{fen::CLASSICAL, pgn_reader::PgnReader, pgn_tree::parse_movetext, san::SanParser};
+
+const SEVEN_TAG_ROSTER : [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+const MAX_LINE_LENGTH : usize = 79;
+
+/// Parses the first game of a PGN string, tags included.
+pub fn from_pgn(pgn: &str) -> Result<Game, String> {
+    let mut reader = PgnReader::new(pgn.as_bytes());
+
+    return match reader.next() {
+        Some(Ok(game)) => game.game(),
+        Some(Err(e)) => Err(e),
+        None => Err(String::from("No game found in PGN")),
+    }
+}
 
+/// Plays the moves of a PGN movetext section from the classical starting position.
+///
+/// Only the mainline is played, comments, variations and NAGs are skipped.
+pub fn from_movetext(movetext: &str) -> Result<Game, String> {
+    let mut game = Game::new_classical();
 
+    play_movetext(&mut game, movetext)?;
 
-pub fn from_pgn(pgn: &str) -> Game {
-    unimplemented!()
+    return Ok(game);
 }
 
-fn clean_string(pgn: &str) -> String {
-    let regex = Regex::new(r"\+").unwrap();
-    let pgn = regex.replace_all(pgn, "");
-
-    return pgn.to_string();
+/// Plays the moves of a PGN movetext section on top of an existing game.
+pub fn play_movetext(game: &mut Game, movetext: &str) -> Result<(), String> {
+    return play_movetext_with(game, movetext, &SanParser::new().lenient());
 }
 
-fn get_turn_strings(pgn: &str) -> Vec<String> {
-    let regex = Regex::new(r"\d+\.").unwrap();
+/// Plays the moves of a PGN movetext section, reading each move with the given parser.
+pub fn play_movetext_with(game: &mut Game, movetext: &str, parser: &SanParser) -> Result<(), String> {
+    let (_, played, _) = parse_movetext(game, movetext, parser)?;
 
-    let turns = regex.split(pgn).map(|s| s.to_string().trim().to_string()).filter(|s| s.len() > 0).collect();
+    *game = played;
 
-    return turns;
+    return Ok(());
 }
 
-fn get_movement_string(turn: &str) -> (String, String) {
-    let regex = Regex::new(r"\s+").unwrap();
+/// Writes a game in PGN export format: the Seven Tag Roster, the remaining tags and the SAN movetext.
+///
+/// Fails when a movement of the game cannot be replayed from its starting position.
+pub fn to_pgn(game: &Game) -> Result<String, String> {
+    let result = get_result(game);
+
+    let mut pgn = write_tags(game.tags(), game.start_fen(), result);
 
-    let moves : Vec<String> = regex.split(turn).map(|s| s.to_string().trim().to_string()).collect();
+    let mut tokens = get_movetext_tokens(game)?;
+    tokens.push(result.to_string());
 
-    return (moves[0].clone(), moves[1].clone());
+    pgn.push('\n');
+    pgn.push_str(&wrap_tokens(tokens));
+
+    return Ok(pgn);
 }
 
-fn get_movement(game: &mut Game,movement_string: &str, color: Color) -> Option<Movement> {
-    let mut disambiguation : Option<&str> = None;
-    let mut prefix : Option<&str> = None;
-    let mut destination : Option<&str> = None;
-    let mut capture : Option<&str> = None;
-    let mut promotion : Option<&str> = None;
+/// Writes the Seven Tag Roster, then the `SetUp` and `FEN` tags for custom positions and the other tags sorted by name.
+pub(crate) fn write_tags(tags: &[(String, String)], start_fen: &str, result: &str) -> String {
+    let mut pgn = String::new();
 
-    if movement_string == "O-O" {
-        return Some(Movement::CastleKingSide(Box::new(King::new(color))))
+    for (name, default) in SEVEN_TAG_ROSTER {
+        let value = match name {
+            "Result" => result,
+            _ => match tags.iter().find(|(tag, _)| tag == name) {
+                Some((_, value)) => value,
+                None => default,
+            },
+        };
+
+        pgn.push_str(&format_tag(name, value));
     }
 
-    if movement_string == "O-O-O" {
-        return Some(Movement::CastleQueenSide(Box::new(King::new(color))))
+    if start_fen != CLASSICAL {
+        pgn.push_str(&format_tag("SetUp", "1"));
+        pgn.push_str(&format_tag("FEN", start_fen));
     }
 
+    let mut tags : Vec<&(String, String)> = tags.iter().filter(|(name, _)| {
+        return !SEVEN_TAG_ROSTER.iter().any(|(roster_name, _)| roster_name == name) && name != "SetUp" && name != "FEN";
+    }).collect();
 
-    if movement_string.contains("=") {
-        promotion = Some(&movement_string[movement_string.len() - 1..movement_string.len()]);
-    }
+    tags.sort_by(|(a, _), (b, _)| a.cmp(b));
 
-    if let Some(c) = movement_string.chars().nth(0) {
-        if c.is_uppercase() {
-            prefix = Some(&movement_string[0..1]);
-            disambiguation = Some(&movement_string[1..movement_string.len()]);
-        }else{
-            prefix = Some("P");
-        }
+    for (name, value) in tags {
+        pgn.push_str(&format_tag(name, value));
     }
 
+    return pgn;
+}
 
-    if movement_string.chars().nth(movement_string.len() - 1).is_some(){
-
-        if promotion.is_some(){
-            destination = Some(&movement_string[movement_string.len() - 4..movement_string.len() - 2]);
-            match disambiguation{
-                Some(prev) => {
-                    disambiguation = Some(&prev[0..prev.len() - 4]);
-                },
-                None => {
-                    disambiguation = Some(&movement_string[0..movement_string.len() - 4]);
-                },
-            }
-        }else{
-            destination = Some(&movement_string[movement_string.len() - 2..movement_string.len()]);
-            match disambiguation{
-                Some(prev) => {
-                    disambiguation = Some(&prev[0..prev.len() - 2]);
-                },
-                None => {
-                    disambiguation = Some(&movement_string[0..movement_string.len() - 2]);
-                },
-            }
+/// Joins movetext tokens into lines shorter than 80 columns, breaking comments between words.
+pub(crate) fn wrap_tokens(tokens: Vec<String>) -> String {
+    let mut pgn = String::new();
+    let mut line = String::new();
+
+    let words = tokens.iter().flat_map(|token| match token.starts_with('{') {
+        true => token.split_whitespace().collect(),
+        false => vec![token.as_str()],
+    });
+
+    for token in words {
+        if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
         }
-    }
 
-    if movement_string.contains("x") {
-        capture = Some("x");
-        match disambiguation{
-            Some(prev) => {
-                disambiguation = Some(&prev[0..prev.len() - 1]);
-            },
-            None => {
-                destination = Some(&movement_string[0..movement_string.len() - 1]);
-            },
+        if !line.is_empty() {
+            line.push(' ');
         }
+
+        line.push_str(token);
     }
 
+    pgn.push_str(&line);
+    pgn.push('\n');
 
+    return pgn;
+}
+
+fn format_tag(name: &str, value: &str) -> String {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
 
-    let mut movement_gen : Option<Movement> = None;
+    return format!("[{} \"{}\"]\n", name, value);
+}
 
+fn get_result(game: &Game) -> &str {
+    if game.is_checkmate() {
+        return match game.turn() {
+            Color::White => "0-1",
+            Color::Black => "1-0",
+        };
+    }
 
-    let disambiguation_char : Option<char> = match disambiguation {
-        Some(disambiguation) => {
-            match disambiguation.chars().nth(0){
-                Some(c) => Some(c),
-                None => None,
-            }
-        },
-        None => None,
+    if game.is_stalemate() {
+        return "1/2-1/2";
+    }
+
+    return match game.tag("Result") {
+        Some(result) if ["1-0", "0-1", "1/2-1/2"].contains(&result) => result,
+        _ => "*",
     };
+}
 
-    let to = Position::from_string(destination.unwrap()).unwrap();
-    let from = game.board().get_position_by_movement(game ,&piece_factory(prefix.unwrap(), color), &to, capture.is_some(), disambiguation_char, movement_string);
+/// Replays the game from its starting position, numbering and writing each move in SAN followed by its annotation, if any.
+fn get_movetext_tokens(game: &Game) -> Result<Vec<String>, String> {
+    let mut replay = Game::from_fen(game.start_fen())?;
 
+    let mut tokens = Vec::new();
+    let mut needs_number = true;
 
+    for (movement, annotation) in game.movements().iter().zip(game.annotations()) {
+        match replay.turn() {
+            Color::White => tokens.push(format!("{}.", replay.fullmove_number())),
+            Color::Black => if needs_number {
+                tokens.push(format!("{}...", replay.fullmove_number()));
+            },
+        }
 
-    match capture {
-        Some(_) => movement_gen = Movement::new_capture(piece_factory(prefix.unwrap(), color),from, to, None),
-        None => movement_gen = Movement::new_move(piece_factory(prefix.unwrap(), color),from, to, None),
-    }
+        let san = movement.to_san(&replay).map_err(|e| format!("Cannot replay movement {}: {}", movement, e))?;
 
+        replay.play(movement.clone()).map_err(|e| format!("Cannot replay movement {}: {}", movement, e))?;
 
-    return movement_gen;
-}
+        tokens.push(san);
 
+        needs_number = !annotation.is_empty();
+
+        if needs_number {
+            tokens.push(format!("{{{}}}", annotation));
+        }
+    }
+
+    return Ok(tokens);
+}
 
 #[test]
 fn test_pgn(){
@@ -137,34 +195,148 @@ fn test_pgn(){
     30.Be3 Rd3 31.Re1 Kh8 32.Rf1 Qe7 33.e6 Kg8 34.f5 Qxc5 35.Bxc5 Ne5 36.f6 gxf6
     37.e7 Kf7 38.e8=Q Kg7");
 
-    let cleaned = clean_string(&pgn);
-
-    let turns = get_turn_strings(&cleaned);
+    use super::pgn_tree::{tokenize, Token};
 
-    let moves_per_turn = turns.iter().map(|turn| get_movement_string(turn)).collect::<Vec<(String, String)>>();
+    let tokens = tokenize(&pgn).unwrap();
 
     let mut game = Game::new_classical();
 
-    for (index, (white_move, black_move)) in moves_per_turn.iter().enumerate() {
-        let m1 = match get_movement(&mut game, &white_move, Color::White){
-            Some(movement) => movement,
-            None => panic!("Invalid movement"),
+    for token in tokens {
+        let movement_string = match token {
+            Token::Move(movement_string) => movement_string,
+            _ => continue,
         };
 
-        
-        assert_eq!(game.play(m1).is_ok(), true, "Invalid movement {} on turn {}", white_move, index + 1);
+        let turn = game.fullmove_number();
 
-        // println!("{}", game.board());
-
-        let m2 = match get_movement(&mut game, &black_move, Color::Black){
-            Some(movement) => movement,
-            None => panic!("Invalid movement"),
+        let movement = match SanParser::new().lenient().parse(&game, &movement_string){
+            Ok(movement) => movement,
+            Err(e) => panic!("Invalid movement: {}", e),
         };
 
-        assert_eq!(game.play(m2).is_ok(), true, "Invalid movement {} on turn {}", black_move, index + 1);
+        assert!(game.play(movement).is_ok(), "Invalid movement {} on turn {}", movement_string, turn);
 
         // println!("{}", game.board());
     }
 
     // assert_eq!(1,2);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_to_pgn(){
+    let mut game = from_movetext("1.f3 e5 2.g4 Qh4#").unwrap();
+
+    game.set_tag("White", "Fool");
+    game.set_tag("Annotator", "Nobody");
+
+    let expected = "[Event \"?\"]
+[Site \"?\"]
+[Date \"????.??.??\"]
+[Round \"?\"]
+[White \"Fool\"]
+[Black \"?\"]
+[Result \"0-1\"]
+[Annotator \"Nobody\"]
+
+1. f3 e5 2. g4 Qh4# 0-1
+";
+
+    assert_eq!(game.to_pgn().unwrap(), expected);
+}
+
+#[test]
+fn test_to_pgn_round_trip(){
+    let pgn = "[Event \"Casual\"]
+[White \"Someone \\\"Quoted\\\"\"]
+[Result \"1/2-1/2\"]
+
+1.e4 e5 2.Nf3 Nc6 3.Bb5 a6 4.Ba4 Nf6 5.O-O Be7 6.d4 exd4 7.e5 Ne4 8.Nxd4 O-O
+9.Nf5 d5 10.Bxc6 bxc6 11.Nxe7+ Qxe7 12.Re1 Re8 13.f3 Nd6 14.Bf4 Nf5 15.Qd2 Rb8
+16.b3 Rb4 17.c3 Rb6 18.Qf2 c5 19.Nd2 Bb7 20.Nf1 d4 21.Ng3 Nh4 22.Ne4 Bxe4
+23.Rxe4 Ng6 24.Bd2 Re6 25.f4 Qd7 26.cxd4 f5 27.d5 fxe4 28.dxe6 Qxe6 29.Qxc5 Rd8
+30.Be3 Rd3 31.Re1 Kh8 32.Rf1 Qe7 33.e6 Kg8 34.f5 Qxc5 35.Bxc5 Ne5 36.f6 gxf6
+37.e7 Kf7 38.e8=Q+ Kg7 1/2-1/2";
+
+    let exported = from_pgn(pgn).unwrap().to_pgn().unwrap();
+
+    assert!(exported.lines().all(|line| line.len() < 80), "{}", exported);
+    assert!(exported.contains("[White \"Someone \\\"Quoted\\\"\"]"), "{}", exported);
+    assert!(exported.contains("38. e8=Q+ Kg7 1/2-1/2"), "{}", exported);
+
+    let reimported = from_pgn(&exported).unwrap();
+
+    assert_eq!(reimported.to_pgn().unwrap(), exported);
+    assert_eq!(reimported.movements().len(), 76);
+}
+
+#[test]
+fn test_to_pgn_from_fen(){
+    use crate::{game::movement::Movement, piece::{pieces::king::King, Piece, piece_factory}, board::position::Position};
+
+    let fen = "4k3/8/8/8/8/8/4P3/R3K2R b KQ - 0 12";
+
+    let mut game = Game::from_fen(fen).unwrap();
+
+    let movement = Movement::new_move(piece_factory("K", Color::Black), Position::from_string("e8").unwrap(), Position::from_string("d7").unwrap(), None).unwrap();
+
+    assert!(game.play(movement).is_ok());
+    assert!(game.play(Movement::CastleQueenSide(Box::new(King::new(Color::White)))).is_ok());
+
+    let exported = game.to_pgn().unwrap();
+
+    assert!(exported.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/R3K2R b KQ - 0 12\"]"), "{}", exported);
+    assert!(exported.contains("12... Kd7 13. O-O-O+ *"), "{}", exported);
+
+    let reimported = from_pgn(&exported).unwrap();
+
+    assert_eq!(reimported.to_fen(), game.to_fen());
+}
+
+#[test]
+fn test_to_pgn_annotations(){
+    let game = from_movetext("1.e4 {[%eval 0.3] [%clk 0:05:00]} e5 {[%clk 0:04:58] Solid} 2.Nf3 {[%cal Gg1f3] [%csl Re5]} *").unwrap();
+
+    let annotations = game.annotations();
+
+    assert_eq!(annotations.len(), 3);
+    assert_eq!(annotations[0].clock(), Some(&std::time::Duration::from_secs(300)));
+    assert_eq!(annotations[0].evaluation(), Some(&crate::game::annotation::Evaluation::Centipawns(30)));
+    assert_eq!(annotations[1].comment(), Some("Solid"));
+    assert_eq!(annotations[2].arrows().len(), 1);
+    assert_eq!(annotations[2].highlights().len(), 1);
+
+    let exported = game.to_pgn().unwrap();
+
+    assert!(exported.ends_with("1. e4 {[%clk 0:05:00] [%eval 0.30]} 1... e5 {[%clk 0:04:58] Solid} 2. Nf3\n{[%cal Gg1f3] [%csl Re5]} *\n"), "{}", exported);
+
+    let reimported = from_pgn(&exported).unwrap();
+
+    assert_eq!(reimported.annotations(), game.annotations());
+}
+
+#[test]
+fn test_pgn_lenient_movetext(){
+    let game = from_movetext("1.e4 d5 2.e5 f5 3.exf6 e.p. gxf6 4.Ng1-f3!? Bh3 5.gxh3 e6 6.Bd3 Ne7 7.0-0 Nbc6 8.Bh7 Rg8 9.Bxg8 Kd7
+    10.h4 Kc8 11.h5 Kb8 12.h6 a6 13.h7 Qe8 14.h8(Q) 1-0").unwrap();
+
+    assert_eq!(game.movements().len(), 27);
+    assert!(game.to_pgn().unwrap().contains("14. h8=Q"));
+
+    let ambiguous = from_movetext("1.Nf3 a6 2.Nc3 a5 3.Nd4 a4 4.Nb5 *").err().unwrap();
+
+    assert!(ambiguous.contains("Ambiguous movement Nb5"), "{}", ambiguous);
+}
+
+#[test]
+fn test_to_pgn_wraps_long_comments(){
+    let comment = "A long comment that does not fit on a single line of movetext, so it has to be wrapped between its words";
+    let game = from_movetext(&format!("1.e4 {{{}}} e5 *", comment)).unwrap();
+
+    let exported = game.to_pgn().unwrap();
+
+    assert!(exported.lines().all(|line| line.len() < 80), "{}", exported);
+
+    let reimported = from_pgn(&exported).unwrap();
+
+    assert_eq!(reimported.annotations()[0].comment(), Some(comment));
+}
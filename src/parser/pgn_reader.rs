@@ -0,0 +1,433 @@
+use std::io::BufRead;
+
+use crate::game::Game;
+
+use super::{pgn::play_movetext_with, pgn_tree::GameTree, san::{PieceLetters, SanParser}};
+
+/// A single game read from a PGN file: its tag pairs and raw movetext.
+pub struct PgnGame {
+    tags: Vec<(String, String)>,
+    movetext: String,
+    letters: PieceLetters,
+}
+
+impl PgnGame {
+    pub fn new(tags: Vec<(String, String)>, movetext: String) -> PgnGame {
+        PgnGame {
+            tags,
+            movetext,
+            letters: PieceLetters::english(),
+        }
+    }
+
+    /// Reads the movetext with the piece letters of another language, or as figurines.
+    pub fn with_letters(mut self, letters: PieceLetters) -> PgnGame {
+        self.letters = letters;
+        return self;
+    }
+
+    pub fn tags(&self) -> &Vec<(String, String)> {
+        return &self.tags;
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        return self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str());
+    }
+
+    /// The unparsed movetext. Empty when the game was read in headers only mode.
+    pub fn movetext(&self) -> &str {
+        return &self.movetext;
+    }
+
+    /// Plays the movetext from the starting position given by the `FEN` tag, if any, and copies the tags into the game.
+    pub fn game(&self) -> Result<Game, String> {
+        let mut game = match self.tag("FEN") {
+            Some(fen) => Game::from_fen(fen)?,
+            None => Game::new_classical(),
+        };
+
+        for (name, value) in &self.tags {
+            if name != "FEN" && name != "SetUp" {
+                game.set_tag(name, value);
+            }
+        }
+
+        play_movetext_with(&mut game, &self.movetext, &self.san_parser())?;
+
+        return Ok(game);
+    }
+
+    /// Parses the movetext into a tree keeping every variation, comment and NAG.
+    pub fn tree(&self) -> Result<GameTree, String> {
+        let start = match self.tag("FEN") {
+            Some(fen) => Game::from_fen(fen)?,
+            None => Game::new_classical(),
+        };
+
+        let tags = self.tags.iter().filter(|(name, _)| name != "FEN" && name != "SetUp").cloned().collect();
+
+        return GameTree::from_movetext(tags, &start, &self.movetext, &self.san_parser());
+    }
+
+    fn san_parser(&self) -> SanParser {
+        return SanParser::new().lenient().with_letters(self.letters.clone());
+    }
+}
+
+/// Reads games one at a time from a PGN source.
+///
+/// Only the game being read is kept in memory, so arbitrarily large files can be streamed.
+/// A malformed game is reported as an `Err` item and reading resumes with the next game.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::parser::pgn_reader::PgnReader;
+///
+/// let pgn = "[White \"Morphy\"]\n\n1.e4 e5 1-0\n\n[White \"Anderssen\"]\n\n1.d4 d5 0-1\n";
+///
+/// let games : Vec<_> = PgnReader::new(pgn.as_bytes()).map(|game| game.unwrap()).collect();
+///
+/// assert_eq!(games.len(), 2);
+/// assert_eq!(games[1].tag("White"), Some("Anderssen"));
+/// ```
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    headers_only: bool,
+    letters: PieceLetters,
+    buffer: Vec<u8>,
+    pending: Option<String>,
+    line_number: usize,
+    game_number: usize,
+    done: bool,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            reader,
+            headers_only: false,
+            letters: PieceLetters::english(),
+            buffer: Vec::new(),
+            pending: None,
+            line_number: 0,
+            game_number: 0,
+            done: false,
+        }
+    }
+
+    /// Skips the movetext of every game, only collecting the tag pairs.
+    pub fn headers_only(mut self) -> PgnReader<R> {
+        self.headers_only = true;
+        return self;
+    }
+
+    /// Reads the movetext of every game with the piece letters of another language, or as figurines.
+    pub fn with_letters(mut self, letters: PieceLetters) -> PgnReader<R> {
+        self.letters = letters;
+        return self;
+    }
+
+    fn read_line(&mut self) -> Result<Option<String>, String> {
+        if let Some(line) = self.pending.take() {
+            return Ok(Some(line));
+        }
+
+        self.buffer.clear();
+
+        match self.reader.read_until(b'\n', &mut self.buffer) {
+            Ok(0) => return Ok(None),
+            Ok(_) => (),
+            Err(e) => return Err(format!("Could not read line {}: {}", self.line_number + 1, e)),
+        }
+
+        self.line_number += 1;
+
+        // Real world PGN files are not always valid UTF-8, mostly Latin-1 player names.
+        return Ok(Some(String::from_utf8_lossy(&self.buffer).trim_end().to_string()));
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut tags = Vec::new();
+        let mut movetext = String::new();
+        let mut error : Option<String> = None;
+        let mut has_content = false;
+        let mut in_movetext = false;
+        let mut in_comment = false;
+
+        loop {
+            let line = match self.read_line() {
+                Ok(Some(line)) => line,
+                Ok(None) => {
+                    self.done = true;
+                    break;
+                },
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                },
+            };
+
+            // Lines starting with a percent sign are escaped and must be ignored.
+            if line.starts_with('%') {
+                continue;
+            }
+
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() {
+                continue;
+            }
+
+            if !in_comment && trimmed.starts_with('[') {
+                if in_movetext {
+                    self.pending = Some(line);
+                    break;
+                }
+
+                has_content = true;
+
+                match parse_tags(trimmed) {
+                    Ok(mut parsed) => tags.append(&mut parsed),
+                    Err(e) => {
+                        if error.is_none() {
+                            error = Some(format!("Line {}: {}", self.line_number, e));
+                        }
+                    },
+                }
+
+                continue;
+            }
+
+            has_content = true;
+            in_movetext = true;
+
+            if !self.headers_only {
+                movetext.push_str(trimmed);
+                movetext.push('\n');
+            }
+
+            if is_terminated(trimmed, &mut in_comment) {
+                break;
+            }
+        }
+
+        if !has_content {
+            return None;
+        }
+
+        self.game_number += 1;
+
+        return match error {
+            Some(e) => Some(Err(format!("Game {}: {}", self.game_number, e))),
+            None => Some(Ok(PgnGame::new(tags, movetext).with_letters(self.letters.clone()))),
+        }
+    }
+}
+
+/// Parses every `[Name "Value"]` tag pair on a line.
+fn parse_tags(line: &str) -> Result<Vec<(String, String)>, String> {
+    let mut tags = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while let Some(c) = chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            chars.next();
+        }
+
+        match chars.next() {
+            Some('[') => (),
+            Some(c) => return Err(format!("Unexpected character '{}' in tag pair: {}", c, line)),
+            None => break,
+        }
+
+        let mut name = String::new();
+
+        while let Some(c) = chars.peek() {
+            if !c.is_alphanumeric() && *c != '_' {
+                break;
+            }
+            name.push(*c);
+            chars.next();
+        }
+
+        if name.is_empty() {
+            return Err(format!("Missing tag name: {}", line));
+        }
+
+        while let Some(c) = chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            chars.next();
+        }
+
+        if chars.next() != Some('"') {
+            return Err(format!("Missing opening quote for tag {}: {}", name, line));
+        }
+
+        let mut value = String::new();
+        let mut closed = false;
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(escaped) => value.push(escaped),
+                    None => break,
+                },
+                '"' => {
+                    closed = true;
+                    break;
+                },
+                _ => value.push(c),
+            }
+        }
+
+        if !closed {
+            return Err(format!("Missing closing quote for tag {}: {}", name, line));
+        }
+
+        while let Some(c) = chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            chars.next();
+        }
+
+        if chars.next() != Some(']') {
+            return Err(format!("Missing closing bracket for tag {}: {}", name, line));
+        }
+
+        tags.push((name, value));
+    }
+
+    return Ok(tags);
+}
+
+/// Returns true when the movetext line ends with a game termination marker outside of a comment.
+fn is_terminated(line: &str, in_comment: &mut bool) -> bool {
+    let mut last_token = String::new();
+    let mut token = String::new();
+
+    for c in line.chars() {
+        if *in_comment {
+            if c == '}' {
+                *in_comment = false;
+            }
+            continue;
+        }
+
+        match c {
+            '{' => *in_comment = true,
+            ';' => break,
+            _ if c.is_whitespace() => (),
+            _ => {
+                token.push(c);
+                continue;
+            },
+        }
+
+        if !token.is_empty() {
+            last_token = std::mem::take(&mut token);
+        }
+    }
+
+    if !token.is_empty() {
+        last_token = token;
+    }
+
+    if *in_comment {
+        return false;
+    }
+
+    return matches!(last_token.as_str(), "1-0" | "0-1" | "1/2-1/2" | "*");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGN : &str = "[Event \"Casual Game\"]
+[White \"Anderssen, Adolf\"]
+[Black \"Kieseritzky, Lionel\"]
+[Result \"1-0\"]
+
+1.e4 e5 2.f4 exf4 3.Bc4 {The Bishop's Gambit} Qh4+ 4.Kf1 b5 (4...Nf6) 5.Bxb5 Nf6
+1-0
+
+[Event \"Broken\"]
+[White \"Nobody]
+
+1.e4 *
+
+[Event \"Short\"]
+[Result \"*\"]
+
+1.d4 d5 *
+";
+
+    #[test]
+    fn reads_every_game(){
+        let games : Vec<Result<PgnGame, String>> = PgnReader::new(PGN.as_bytes()).collect();
+
+        assert_eq!(games.len(), 3);
+
+        let first = games[0].as_ref().unwrap();
+        assert_eq!(first.tag("White"), Some("Anderssen, Adolf"));
+        assert_eq!(first.tags().len(), 4);
+
+        assert!(games[1].is_err());
+
+        let last = games[2].as_ref().unwrap();
+        assert_eq!(last.tag("Event"), Some("Short"));
+        assert_eq!(last.movetext().trim(), "1.d4 d5 *");
+    }
+
+    #[test]
+    fn skips_movetext_in_headers_only_mode(){
+        let games : Vec<Result<PgnGame, String>> = PgnReader::new(PGN.as_bytes()).headers_only().collect();
+
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].as_ref().unwrap().movetext(), "");
+        assert_eq!(games[2].as_ref().unwrap().tag("Result"), Some("*"));
+    }
+
+    #[test]
+    fn plays_the_movetext(){
+        let mut reader = PgnReader::new(PGN.as_bytes());
+
+        let game = reader.next().unwrap().unwrap().game();
+
+        assert!(game.is_ok(), "{}", game.err().unwrap());
+    }
+
+    #[test]
+    fn ends_games_without_tags_on_termination_marker(){
+        let pgn = "1.e4 e5 { a comment 1-0 } 2.Nf3 *\n1.d4 d5 1/2-1/2\n";
+
+        let games : Vec<Result<PgnGame, String>> = PgnReader::new(pgn.as_bytes()).collect();
+
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1].as_ref().unwrap().movetext().trim(), "1.d4 d5 1/2-1/2");
+    }
+
+    #[test]
+    fn reads_localized_movetext(){
+        let pgn = "[White \"Spielmann\"]\n\n1.e4 e5 2.Sf3 Sc6 3.Lb5 a6 4.La4 Sf6 5.O-O Le7 6.Te1 b5 7.Lb3 d6 8.c3 O-O *\n";
+
+        let game = PgnReader::new(pgn.as_bytes()).with_letters(PieceLetters::german()).next().unwrap().unwrap().game().unwrap();
+
+        assert!(game.to_pgn().unwrap().contains("6. Re1 b5 7. Bb3 d6 8. c3\nO-O *"), "{}", game.to_pgn().unwrap());
+    }
+}
@@ -0,0 +1,570 @@
+use crate::{color::Color, game::{Game, movement::Movement, annotation::Annotation}};
+
+use super::{pgn::{wrap_tokens, write_tags}, pgn_reader::PgnReader, san::SanParser};
+
+/// A move of a game tree, with its annotations and the variations that could have been played instead.
+pub struct MoveNode {
+    san: String,
+    movement: Movement,
+    nags: Vec<u8>,
+    comments: Vec<String>,
+    variations: Vec<Variation>,
+    variation_comments: Vec<Vec<String>>,
+}
+
+impl MoveNode {
+    pub fn new(san: String, movement: Movement) -> MoveNode {
+        MoveNode {
+            san,
+            movement,
+            nags: Vec::new(),
+            comments: Vec::new(),
+            variations: Vec::new(),
+            variation_comments: Vec::new(),
+        }
+    }
+
+    /// The move as it was written in the PGN.
+    pub fn san(&self) -> &str {
+        return &self.san;
+    }
+
+    pub fn movement(&self) -> &Movement {
+        return &self.movement;
+    }
+
+    /// Numeric Annotation Glyphs, suffixes like `!?` are stored as their NAG equivalent.
+    pub fn nags(&self) -> &Vec<u8> {
+        return &self.nags;
+    }
+
+    /// Comments written after the move, before any of its variations.
+    pub fn comments(&self) -> &Vec<String> {
+        return &self.comments;
+    }
+
+    /// The commands embedded in the comments of the move, like `[%clk 0:03:12]`.
+    pub fn annotation(&self) -> Annotation {
+        let mut annotation = Annotation::new();
+
+        for comment in self.comments.iter().chain(self.variation_comments.iter().flatten()) {
+            annotation.merge(Annotation::parse(comment));
+        }
+
+        return annotation;
+    }
+
+    /// Alternatives to this move, each starting from the position before it.
+    pub fn variations(&self) -> &Vec<Variation> {
+        return &self.variations;
+    }
+
+    /// Comments written after each variation, in the same order as the variations.
+    pub fn variation_comments(&self) -> &Vec<Vec<String>> {
+        return &self.variation_comments;
+    }
+}
+
+/// A line of moves, along with the comments written before its first move.
+pub struct Variation {
+    comments: Vec<String>,
+    moves: Vec<MoveNode>,
+}
+
+impl Variation {
+    pub fn new() -> Variation {
+        Variation {
+            comments: Vec::new(),
+            moves: Vec::new(),
+        }
+    }
+
+    pub fn comments(&self) -> &Vec<String> {
+        return &self.comments;
+    }
+
+    pub fn moves(&self) -> &Vec<MoveNode> {
+        return &self.moves;
+    }
+}
+
+impl Default for Variation {
+    fn default() -> Variation {
+        return Variation::new();
+    }
+}
+
+/// A game with its mainline and every nested variation, comment and NAG.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::parser::pgn_tree::GameTree;
+///
+/// let tree = GameTree::from_pgn("1.e4 {Best by test} (1.d4 d5 $1) e5 *").unwrap();
+///
+/// let first = &tree.mainline().moves()[0];
+///
+/// assert_eq!(first.comments()[0], "Best by test");
+/// assert_eq!(first.variations()[0].moves()[1].nags(), &vec![1]);
+/// assert_eq!(tree.to_pgn().unwrap().lines().last(), Some("1. e4 {Best by test} (1. d4 d5 $1) 1... e5 *"));
+/// ```
+pub struct GameTree {
+    tags: Vec<(String, String)>,
+    start_fen: String,
+    mainline: Variation,
+    result: String,
+}
+
+impl GameTree {
+    /// Parses the first game of a PGN string.
+    pub fn from_pgn(pgn: &str) -> Result<GameTree, String> {
+        let mut reader = PgnReader::new(pgn.as_bytes());
+
+        return match reader.next() {
+            Some(Ok(game)) => game.tree(),
+            Some(Err(e)) => Err(e),
+            None => Err(String::from("No game found in PGN")),
+        }
+    }
+
+    /// Parses the movetext of a game starting from the given position, reading each move with the given parser.
+    pub fn from_movetext(tags: Vec<(String, String)>, start: &Game, movetext: &str, parser: &SanParser) -> Result<GameTree, String> {
+        let (mainline, _, result) = parse_movetext(start, movetext, parser)?;
+
+        let result = match result {
+            Some(result) => result,
+            None => String::from("*"),
+        };
+
+        return Ok(GameTree {
+            tags,
+            start_fen: start.to_fen(),
+            mainline,
+            result,
+        });
+    }
+
+    pub fn tags(&self) -> &Vec<(String, String)> {
+        return &self.tags;
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        return self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str());
+    }
+
+    pub fn start_fen(&self) -> &str {
+        return &self.start_fen;
+    }
+
+    pub fn mainline(&self) -> &Variation {
+        return &self.mainline;
+    }
+
+    /// The game termination marker, `*` when the game has none.
+    pub fn result(&self) -> &str {
+        return &self.result;
+    }
+
+    /// Plays the mainline, dropping every variation.
+    pub fn game(&self) -> Result<Game, String> {
+        let mut game = Game::from_fen(&self.start_fen)?;
+
+        for (name, value) in &self.tags {
+            game.set_tag(name, value);
+        }
+
+        for node in self.mainline.moves() {
+            game.play(node.movement().clone())?;
+            game.annotate(node.annotation())?;
+        }
+
+        return Ok(game);
+    }
+
+    /// Writes the tree back as PGN, keeping every variation, comment and NAG.
+    pub fn to_pgn(&self) -> Result<String, String> {
+        let start = Game::from_fen(&self.start_fen)?;
+
+        let mut tokens = Vec::new();
+
+        write_variation(&self.mainline, start.fullmove_number(), start.turn(), &mut tokens);
+        tokens.push(self.result.clone());
+
+        let mut pgn = write_tags(&self.tags, &self.start_fen, &self.result);
+
+        pgn.push('\n');
+        pgn.push_str(&wrap_tokens(tokens));
+
+        return Ok(pgn);
+    }
+}
+
+pub(crate) enum Token {
+    Move(String),
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+/// Splits movetext into moves, NAGs, comments, variation delimiters and the game termination marker.
+pub(crate) fn tokenize(movetext: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = movetext.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let mut comment = String::new();
+                let mut closed = false;
+
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    comment.push(c);
+                }
+
+                if !closed {
+                    return Err(format!("Unclosed comment {{{}", comment));
+                }
+
+                // Line breaks inside comments are only wrapping.
+                tokens.push(Token::Comment(comment.split_whitespace().collect::<Vec<&str>>().join(" ")));
+            },
+            ';' => {
+                let mut comment = String::new();
+
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                    comment.push(c);
+                }
+
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            },
+            '(' => tokens.push(Token::VariationStart),
+            ')' => tokens.push(Token::VariationEnd),
+            '$' => {
+                let mut nag = String::new();
+
+                while let Some(c) = chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    nag.push(*c);
+                    chars.next();
+                }
+
+                match nag.parse::<u8>() {
+                    Ok(nag) => tokens.push(Token::Nag(nag)),
+                    Err(_) => return Err(format!("Invalid NAG ${}", nag)),
+                }
+            },
+            _ if c.is_whitespace() => {},
+            _ => {
+                let mut symbol = String::from(c);
+
+                while let Some(&c) = chars.peek() {
+                    // Some files write promotions as `e8(Q)`, which must not be read as a variation.
+                    if c == '(' {
+                        let ahead : String = chars.clone().take(3).collect();
+
+                        if ahead.len() == 3 && "QRBN".contains(&ahead[1..2]) && ahead.ends_with(')') {
+                            symbol.push_str(&ahead);
+                            chars.nth(2);
+                            continue;
+                        }
+                    }
+
+                    if c.is_whitespace() || "{};()$".contains(c) {
+                        break;
+                    }
+                    symbol.push(c);
+                    chars.next();
+                }
+
+                read_symbol(&symbol, &mut tokens)?;
+            },
+        }
+    }
+
+    return Ok(tokens);
+}
+
+/// Reads a symbol, which may be a termination marker or a move with its number and suffix glyphs attached.
+fn read_symbol(symbol: &str, tokens: &mut Vec<Token>) -> Result<(), String> {
+    if ["1-0", "0-1", "1/2-1/2", "*"].contains(&symbol) {
+        tokens.push(Token::Result(symbol.to_string()));
+        return Ok(());
+    }
+
+    // En passant captures are sometimes followed by a separate `e.p.` marker.
+    if symbol == "e.p." {
+        return Ok(());
+    }
+
+    let mut san = symbol;
+
+    let number_length = san.chars().take_while(|c| c.is_ascii_digit()).count();
+
+    if number_length > 0 && san[number_length..].starts_with('.') {
+        san = san[number_length..].trim_start_matches('.');
+    }
+
+    let glyphs_start = san.trim_end_matches(['!', '?']).len();
+    let glyphs = &san[glyphs_start..];
+    let san = &san[..glyphs_start];
+
+    if !san.is_empty() {
+        tokens.push(Token::Move(san.to_string()));
+    }
+
+    if !glyphs.is_empty() {
+        let nag = match glyphs {
+            "!" => 1,
+            "?" => 2,
+            "!!" => 3,
+            "??" => 4,
+            "!?" => 5,
+            "?!" => 6,
+            _ => return Err(format!("Unknown annotation glyph {}", glyphs)),
+        };
+
+        tokens.push(Token::Nag(nag));
+    }
+
+    return Ok(());
+}
+
+/// Parses movetext from the given position, returning the mainline, the game at its end and the termination marker.
+pub(crate) fn parse_movetext(start: &Game, movetext: &str, parser: &SanParser) -> Result<(Variation, Game, Option<String>), String> {
+    let tokens = tokenize(movetext)?;
+
+    let mut index = 0;
+    let mut result = None;
+
+    let (mainline, game) = parse_variation(&tokens, &mut index, start, parser, false, &mut result)?;
+
+    return Ok((mainline, game, result));
+}
+
+fn parse_variation(tokens: &[Token], index: &mut usize, start: &Game, parser: &SanParser, nested: bool, result: &mut Option<String>) -> Result<(Variation, Game), String> {
+    let mut variation = Variation::new();
+    let mut game = start.clone();
+
+    while *index < tokens.len() {
+        let token = &tokens[*index];
+        *index += 1;
+
+        match token {
+            Token::Move(san) => {
+                let movement = match parser.parse(&game, san) {
+                    Ok(movement) => movement,
+                    Err(e) => return Err(format!("Invalid movement {} on move {}: {}", san, game.fullmove_number(), e)),
+                };
+
+                if let Err(e) = game.play(movement.clone()) {
+                    return Err(format!("Invalid movement {} on move {}: {}", san, game.fullmove_number(), e));
+                }
+
+                variation.moves.push(MoveNode::new(san.clone(), movement));
+            },
+            Token::Nag(nag) => match variation.moves.last_mut() {
+                Some(node) => node.nags.push(*nag),
+                None => return Err(format!("NAG ${} is not attached to any move", nag)),
+            },
+            Token::Comment(comment) => match variation.moves.last_mut() {
+                Some(node) => {
+                    // A comment following a variation stays after it, so that it is written back in the same place.
+                    match node.variation_comments.last_mut() {
+                        Some(comments) => comments.push(comment.clone()),
+                        None => node.comments.push(comment.clone()),
+                    }
+                    game.annotate(Annotation::parse(comment))?;
+                },
+                None => variation.comments.push(comment.clone()),
+            },
+            Token::VariationStart => {
+                let played = match variation.moves.len() {
+                    0 => return Err(String::from("A variation must follow the move it replaces")),
+                    length => &variation.moves[..length - 1],
+                };
+
+                let mut before = start.clone();
+
+                for node in played {
+                    before.play(node.movement().clone())?;
+                }
+
+                let (alternative, _) = parse_variation(tokens, index, &before, parser, true, result)?;
+
+                if let Some(node) = variation.moves.last_mut() {
+                    node.variations.push(alternative);
+                    node.variation_comments.push(Vec::new());
+                }
+            },
+            Token::VariationEnd => {
+                if !nested {
+                    return Err(String::from("Unexpected end of variation"));
+                }
+
+                return Ok((variation, game));
+            },
+            Token::Result(marker) => *result = Some(marker.clone()),
+        }
+    }
+
+    if nested {
+        return Err(String::from("Unclosed variation"));
+    }
+
+    return Ok((variation, game));
+}
+
+fn write_variation(variation: &Variation, fullmove_number: u32, turn: &Color, tokens: &mut Vec<String>) {
+    let mut fullmove_number = fullmove_number;
+    let mut turn = *turn;
+
+    for comment in variation.comments() {
+        tokens.push(format!("{{{}}}", comment));
+    }
+
+    let mut needs_number = true;
+
+    for node in variation.moves() {
+        match turn {
+            Color::White => tokens.push(format!("{}.", fullmove_number)),
+            Color::Black => if needs_number {
+                tokens.push(format!("{}...", fullmove_number));
+            },
+        }
+
+        tokens.push(node.san().to_string());
+
+        for nag in node.nags() {
+            tokens.push(format!("${}", nag));
+        }
+
+        for comment in node.comments() {
+            tokens.push(format!("{{{}}}", comment));
+        }
+
+        for (alternative, comments) in node.variations().iter().zip(node.variation_comments()) {
+            let mut alternative_tokens = Vec::new();
+
+            write_variation(alternative, fullmove_number, &turn, &mut alternative_tokens);
+
+            match alternative_tokens.first_mut() {
+                Some(first) => first.insert(0, '('),
+                None => alternative_tokens.push(String::from("(")),
+            }
+
+            if let Some(last) = alternative_tokens.last_mut() {
+                last.push(')');
+            }
+
+            tokens.append(&mut alternative_tokens);
+
+            for comment in comments {
+                tokens.push(format!("{{{}}}", comment));
+            }
+        }
+
+        needs_number = !node.comments().is_empty() || !node.variations().is_empty();
+
+        if turn == Color::Black {
+            fullmove_number += 1;
+        }
+
+        turn = turn.opposite();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGN : &str = "[Event \"Lesson\"]
+[Result \"1-0\"]
+
+{Coach notes} 1. e4 e5 2. Nf3 Nc6 3. Bb5 $1 {The Ruy Lopez} (3. Bc4 Bc5!? (3... Nf6 4. Ng5)
+; the Italian
+4. c3) (3. d4 exd4) 3... a6?! 4. Ba4 Nf6 1-0";
+
+    #[test]
+    fn builds_the_move_tree(){
+        let tree = GameTree::from_pgn(PGN).unwrap();
+
+        assert_eq!(tree.result(), "1-0");
+        assert_eq!(tree.tag("Event"), Some("Lesson"));
+        assert_eq!(tree.mainline().comments(), &vec![String::from("Coach notes")]);
+        assert_eq!(tree.mainline().moves().len(), 8);
+
+        let bishop = &tree.mainline().moves()[4];
+
+        assert_eq!(bishop.san(), "Bb5");
+        assert_eq!(bishop.nags(), &vec![1]);
+        assert_eq!(bishop.comments(), &vec![String::from("The Ruy Lopez")]);
+        assert_eq!(bishop.variations().len(), 2);
+
+        let italian = &bishop.variations()[0];
+
+        assert_eq!(italian.moves().len(), 3);
+        assert_eq!(italian.moves()[1].nags(), &vec![5]);
+        assert!(italian.moves()[1].comments().is_empty());
+        assert_eq!(italian.moves()[1].variation_comments(), &vec![vec![String::from("the Italian")]]);
+        assert_eq!(italian.moves()[1].variations()[0].moves()[1].san(), "Ng5");
+
+        assert_eq!(tree.mainline().moves()[5].nags(), &vec![6]);
+    }
+
+    #[test]
+    fn writes_the_tree_back(){
+        let tree = GameTree::from_pgn(PGN).unwrap();
+
+        let pgn = tree.to_pgn().unwrap();
+
+        assert!(pgn.contains("{Coach notes} 1. e4 e5 2. Nf3 Nc6 3. Bb5 $1 {The Ruy Lopez} (3. Bc4 Bc5 $5"), "{}", pgn);
+        assert!(pgn.contains("(3... Nf6 4. Ng5) {the Italian} 4. c3) (3. d4 exd4) 3... a6 $6 4. Ba4 Nf6 1-0"), "{}", pgn);
+
+        assert_eq!(GameTree::from_pgn(&pgn).unwrap().to_pgn().unwrap(), pgn);
+    }
+
+    #[test]
+    fn keeps_comments_after_variations(){
+        let tree = GameTree::from_pgn("1. e4 {a} (1. d4) {c} (1. c4) {d} 1... e5 *").unwrap();
+
+        let first = &tree.mainline().moves()[0];
+
+        assert_eq!(first.comments(), &vec![String::from("a")]);
+        assert_eq!(first.variation_comments(), &vec![vec![String::from("c")], vec![String::from("d")]]);
+
+        assert_eq!(tree.to_pgn().unwrap().lines().last(), Some("1. e4 {a} (1. d4) {c} (1. c4) {d} 1... e5 *"));
+
+        let pgn = GameTree::from_pgn("1. e4 (1. d4) {c} 1... e5 *").unwrap().to_pgn().unwrap();
+
+        assert_eq!(pgn.lines().last(), Some("1. e4 (1. d4) {c} 1... e5 *"));
+        assert_eq!(GameTree::from_pgn(&pgn).unwrap().to_pgn().unwrap(), pgn);
+    }
+
+    #[test]
+    fn plays_the_mainline(){
+        let game = GameTree::from_pgn(PGN).unwrap().game().unwrap();
+
+        assert_eq!(game.movements().len(), 8);
+        assert_eq!(game.tag("Event"), Some("Lesson"));
+    }
+
+    #[test]
+    fn rejects_broken_variations(){
+        assert!(GameTree::from_pgn("1. e4 (1. d4 e5 *").is_err());
+        assert!(GameTree::from_pgn("1. e4 ) e5 *").is_err());
+        assert!(GameTree::from_pgn("1. e4 {unclosed *").is_err());
+        assert!(GameTree::from_pgn("1. e4 (1. e5) *").is_err());
+    }
+}
@@ -0,0 +1,368 @@
+use std::{fmt::Display, sync::LazyLock};
+
+use regex::Regex;
+
+use crate::{board::position::Position, color::Color, game::{Game, movement::Movement}, piece::{pieces::pawn::Pawn, piece_factory}};
+
+/// SAN as the PGN standard writes it.
+static STRICT_SAN : LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([KQRBN])?([a-h])?([1-8])?(x)?([a-h][1-8])(?:=([QRBN]))?$").unwrap());
+/// Also accepts an explicit Pawn letter and promotions without the equal sign.
+static LENIENT_SAN : LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([KQRBNP])?([a-h])?([1-8])?(x)?([a-h][1-8])=?([QRBN])?$").unwrap());
+
+/// Why a SAN string could not be turned into a movement.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum SanError{
+    /// The string is not written in a notation the parser understands.
+    Invalid(String),
+    /// No legal movement matches the string.
+    Impossible(String),
+    /// Several legal movements match the string, from each of the given tiles.
+    Ambiguous(String, Vec<Position>),
+}
+
+impl Display for SanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            SanError::Invalid(san) => write!(f, "Invalid movement {}", san),
+            SanError::Impossible(san) => write!(f, "No legal movement matches {}", san),
+            SanError::Ambiguous(san, candidates) => {
+                let candidates : Vec<String> = candidates.iter().map(|position| position.to_string()).collect();
+                write!(f, "Ambiguous movement {}! It can be played from {}", san, candidates.join(", "))
+            },
+        }
+    }
+}
+
+impl From<SanError> for String {
+    fn from(error: SanError) -> String {
+        return error.to_string();
+    }
+}
+
+/// The letters standing for each piece in SAN, so movements can be read and written in other languages.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::{game::Game, parser::san::{PieceLetters, SanParser}};
+///
+/// let game = Game::new_classical();
+/// let german = SanParser::new().with_letters(PieceLetters::german());
+///
+/// let movement = german.parse(&game, "Sf3").unwrap();
+///
+/// assert_eq!(movement.to_san(&game).unwrap(), "Nf3");
+/// assert_eq!(movement.to_san_with(&game, &PieceLetters::figurine()).unwrap(), "♞f3");
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PieceLetters{
+    king: String,
+    queen: String,
+    rook: String,
+    bishop: String,
+    knight: String,
+}
+
+impl PieceLetters {
+    pub fn new(king: &str, queen: &str, rook: &str, bishop: &str, knight: &str) -> PieceLetters {
+        PieceLetters {
+            king: king.to_string(),
+            queen: queen.to_string(),
+            rook: rook.to_string(),
+            bishop: bishop.to_string(),
+            knight: knight.to_string(),
+        }
+    }
+
+    pub fn english() -> PieceLetters {
+        return PieceLetters::new("K", "Q", "R", "B", "N");
+    }
+
+    pub fn german() -> PieceLetters {
+        return PieceLetters::new("K", "D", "T", "L", "S");
+    }
+
+    pub fn french() -> PieceLetters {
+        return PieceLetters::new("R", "D", "T", "F", "C");
+    }
+
+    pub fn spanish() -> PieceLetters {
+        return PieceLetters::new("R", "D", "T", "A", "C");
+    }
+
+    /// Figurine Algebraic Notation, using the icon of each piece.
+    pub fn figurine() -> PieceLetters {
+        let icon = |prefix: &str| piece_factory(prefix, Color::White).icon().to_string();
+
+        return PieceLetters::new(&icon("K"), &icon("Q"), &icon("R"), &icon("B"), &icon("N"));
+    }
+
+    /// The letter standing for the piece with the given English prefix. Pawns have none.
+    pub fn letter(&self, prefix: &str) -> &str {
+        return match prefix {
+            "K" => &self.king,
+            "Q" => &self.queen,
+            "R" => &self.rook,
+            "B" => &self.bishop,
+            "N" => &self.knight,
+            _ => "",
+        }
+    }
+
+    /// Rewrites every piece letter of the movement with its English prefix.
+    fn to_english(&self, san: &str) -> String {
+        let table = [(&self.king, "K"), (&self.queen, "Q"), (&self.rook, "R"), (&self.bishop, "B"), (&self.knight, "N")];
+
+        let mut english = String::new();
+        let mut rest = san;
+
+        while let Some(c) = rest.chars().next() {
+            match table.iter().find(|(letter, _)| !letter.is_empty() && rest.starts_with(letter.as_str())) {
+                Some((letter, prefix)) => {
+                    english.push_str(prefix);
+                    rest = &rest[letter.len()..];
+                },
+                None => {
+                    english.push(c);
+                    rest = &rest[c.len_utf8()..];
+                },
+            }
+        }
+
+        return english;
+    }
+}
+
+impl Default for PieceLetters {
+    fn default() -> PieceLetters {
+        return PieceLetters::english();
+    }
+}
+
+/// Reads movements written in Standard Algebraic Notation.
+///
+/// The strict mode only accepts SAN as the PGN standard writes it. The lenient mode also reads the variants found
+/// in real world files: zeros in castles (`0-0`), promotions without `=` (`e8Q`, `e8(Q)`), the long form (`Ng1-f3`),
+/// en passant suffixes (`exd6 e.p.`), captures without their `x` and trailing annotation glyphs (`Nf3!?`).
+///
+/// Pieces are read with English letters unless another [`PieceLetters`] table is given.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::{game::Game, parser::san::{SanParser, SanError}};
+///
+/// let game = Game::new_classical();
+///
+/// assert!(SanParser::new().parse(&game, "Ng1-f3").is_err());
+/// assert!(SanParser::new().lenient().parse(&game, "Ng1-f3").is_ok());
+/// assert_eq!(SanParser::new().parse(&game, "Nd2").err(), Some(SanError::Impossible(String::from("Nd2"))));
+/// ```
+pub struct SanParser{
+    lenient: bool,
+    letters: PieceLetters,
+}
+
+impl SanParser {
+    pub fn new() -> SanParser {
+        SanParser {
+            lenient: false,
+            letters: PieceLetters::english(),
+        }
+    }
+
+    /// Accepts the common variants of SAN found in real world PGN files.
+    pub fn lenient(mut self) -> SanParser {
+        self.lenient = true;
+        return self;
+    }
+
+    /// Reads pieces with the letters of another language, or as figurines.
+    pub fn with_letters(mut self, letters: PieceLetters) -> SanParser {
+        self.letters = letters;
+        return self;
+    }
+
+    /// Finds the legal movement of the side to move the string refers to.
+    pub fn parse(&self, game: &Game, san: &str) -> Result<Movement, SanError> {
+        let english = self.letters.to_english(san);
+
+        let normalized = match self.lenient {
+            true => SanParser::normalize(&english),
+            false => english.trim().trim_end_matches(['+', '#']).to_string(),
+        };
+
+        if normalized == "O-O" || normalized == "O-O-O" {
+            let castles = game.legal_movements().into_iter().find(|movement| {
+                return match movement {
+                    Movement::CastleKingSide(_) => normalized == "O-O",
+                    Movement::CastleQueenSide(_) => normalized == "O-O-O",
+                    _ => false,
+                }
+            });
+
+            return match castles {
+                Some(castles) => Ok(castles),
+                None => Err(SanError::Impossible(san.to_string())),
+            }
+        }
+
+        let pattern = match self.lenient {
+            true => &LENIENT_SAN,
+            false => &STRICT_SAN,
+        };
+
+        let captures = match pattern.captures(&normalized) {
+            Some(captures) => captures,
+            None => return Err(SanError::Invalid(san.to_string())),
+        };
+
+        let prefix = captures.get(1).map_or(Pawn::prefix(), |prefix| prefix.as_str());
+        let file = captures.get(2).and_then(|file| file.as_str().chars().next());
+        let rank = captures.get(3).and_then(|rank| rank.as_str().parse::<u8>().ok());
+        let is_capture = captures.get(4).is_some();
+        let to = Position::from_string(&captures[5]).map_err(|_| SanError::Invalid(san.to_string()))?;
+        let promotion = captures.get(6).map(|promotion| promotion.as_str());
+
+        let candidates : Vec<Movement> = game.legal_movements().into_iter().filter(|movement| {
+            let from = match (movement.from(), movement.to()) {
+                (Some(from), Some(movement_to)) if *movement_to == to => from,
+                _ => return false,
+            };
+
+            if movement.piece().prefix() != prefix {
+                return false;
+            }
+
+            if file.is_some_and(|file| file != from.file_char()) || rank.is_some_and(|rank| rank != from.rank()) {
+                return false;
+            }
+
+            if movement.promotion().map(|promotion| promotion.prefix()) != promotion {
+                return false;
+            }
+
+            // A lenient capture may omit its `x`, but a quiet move can never be written as one.
+            let movement_is_capture = matches!(movement, Movement::Capture(..));
+
+            return movement_is_capture == is_capture || (self.lenient && !is_capture);
+        }).collect();
+
+        return match candidates.len() {
+            0 => Err(SanError::Impossible(san.to_string())),
+            1 => Ok(candidates.into_iter().next().unwrap()),
+            _ => Err(SanError::Ambiguous(san.to_string(), candidates.iter().filter_map(|movement| movement.from().cloned()).collect())),
+        }
+    }
+
+    /// Rewrites the lenient variants into plain SAN without check marks.
+    fn normalize(san: &str) -> String {
+        let mut san = san.trim();
+
+        for suffix in ["e.p.", "ep"] {
+            if let Some(stripped) = san.strip_suffix(suffix) {
+                san = stripped.trim_end();
+            }
+        }
+
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+        if san.chars().all(|c| c == '0' || c == 'O' || c == '-') {
+            return san.replace('0', "O");
+        }
+
+        return san.chars().filter(|c| !"-()".contains(*c)).map(|c| if c == ':' { 'x' } else { c }).collect();
+    }
+}
+
+impl Default for SanParser {
+    fn default() -> SanParser {
+        return SanParser::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::pgn::from_movetext;
+
+    use super::*;
+
+    fn parse(game: &Game, san: &str) -> String {
+        return SanParser::new().lenient().parse(game, san).unwrap().to_san(game).unwrap();
+    }
+
+    #[test]
+    fn reads_lenient_variants(){
+        let castles = from_movetext("1.e4 e5 2.Nf3 Nc6 3.Bc4 Bc5").unwrap();
+        assert_eq!(parse(&castles, "0-0"), "O-O");
+        assert_eq!(parse(&castles, "Nf3-g5"), "Ng5");
+        assert_eq!(parse(&castles, "Bc4xf7+!?"), "Bxf7+");
+        assert_eq!(parse(&castles, "Nf3:e5"), "Nxe5");
+
+        let en_passant = Game::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        assert_eq!(parse(&en_passant, "exd6 e.p."), "exd6");
+        assert_eq!(parse(&en_passant, "exd6ep"), "exd6");
+
+        let promotion = Game::from_fen("7k/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(parse(&promotion, "e8Q#"), "e8=Q+");
+        assert_eq!(parse(&promotion, "e8(N)"), "e8=N");
+        assert_eq!(parse(&promotion, "e7-e8=R"), "e8=R+");
+    }
+
+    #[test]
+    fn strict_mode_rejects_variants(){
+        let game = Game::new_classical();
+        let parser = SanParser::new();
+
+        assert!(parser.parse(&game, "Nf3+").is_ok());
+        assert_eq!(parser.parse(&game, "Nf3!").err(), Some(SanError::Invalid(String::from("Nf3!"))));
+        assert_eq!(parser.parse(&game, "e2-e4").err(), Some(SanError::Invalid(String::from("e2-e4"))));
+        assert_eq!(parser.parse(&game, "0-0").err(), Some(SanError::Invalid(String::from("0-0"))));
+    }
+
+    #[test]
+    fn reports_ambiguous_and_impossible_movements(){
+        let game = Game::from_fen("r3k3/8/8/8/8/8/4K3/R6R w - - 0 1").unwrap();
+        let parser = SanParser::new();
+
+        match parser.parse(&game, "Rd1") {
+            Err(SanError::Ambiguous(_, candidates)) => assert_eq!(candidates.len(), 2),
+            other => panic!("Expected an ambiguous movement, got {:?}", other.map(|movement| movement.to_string())),
+        }
+
+        assert!(parser.parse(&game, "Rad1").is_ok());
+        assert_eq!(parser.parse(&game, "Rbd1").err(), Some(SanError::Impossible(String::from("Rbd1"))));
+        assert_eq!(parser.parse(&game, "Ra8").err(), Some(SanError::Impossible(String::from("Ra8"))));
+        assert_eq!(parse(&game, "Ra8"), "Rxa8+");
+    }
+
+    #[test]
+    fn reads_localized_letters(){
+        let game = from_movetext("1.e4 e5 2.Nf3 Nc6 3.Bc4 Bc5").unwrap();
+
+        let german = SanParser::new().with_letters(PieceLetters::german());
+        assert_eq!(german.parse(&game, "Lxf7+").unwrap().to_san(&game).unwrap(), "Bxf7+");
+        assert_eq!(german.parse(&game, "De2").unwrap().to_san(&game).unwrap(), "Qe2");
+
+        let french = SanParser::new().with_letters(PieceLetters::french());
+        assert_eq!(french.parse(&game, "Re2").unwrap().to_san(&game).unwrap(), "Ke2");
+        assert_eq!(french.parse(&game, "Cg5").unwrap().to_san(&game).unwrap(), "Ng5");
+
+        let figurine = SanParser::new().with_letters(PieceLetters::figurine());
+        assert_eq!(figurine.parse(&game, "♝xf7+").unwrap().to_san(&game).unwrap(), "Bxf7+");
+    }
+
+    #[test]
+    fn writes_localized_letters(){
+        let game = Game::from_fen("7k/4P3/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        let spanish = PieceLetters::spanish();
+
+        let promotion = SanParser::new().parse(&game, "e8=Q+").unwrap();
+        assert_eq!(promotion.to_san_with(&game, &spanish).unwrap(), "e8=D+");
+        assert_eq!(promotion.to_san_with(&game, &PieceLetters::figurine()).unwrap(), "e8=♛+");
+
+        let rook = SanParser::new().parse(&game, "Ra7").unwrap();
+        assert_eq!(rook.to_san_with(&game, &PieceLetters::german()).unwrap(), "Ta7");
+        assert_eq!(SanParser::new().with_letters(spanish).parse(&game, "O-O-O").unwrap().to_san(&game).unwrap(), "O-O-O");
+    }
+}
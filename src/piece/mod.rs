@@ -17,7 +17,7 @@ pub fn piece_factory(prefix : &str, color : Color) -> Box<dyn Piece>{
     }
 }
 
-pub trait Piece : Display{
+pub trait Piece : Display + Send + Sync{
     fn new(color: Color) -> Self where Self: Sized;
     fn color(&self) -> &Color;
     fn name(&self) -> &str;
@@ -48,16 +48,12 @@ pub trait Piece : Display{
                 let file : u8 = (from.file() as i8 + i * file_signum) as u8;
                 let rank : u8 = (from.rank() as i8 + i * rank_signum) as u8;
 
-                let position = match Position::new(file, rank){
-                    Ok(position) => position,
-                    Err(e) => return Err(e)
-                };
+                let position = Position::new(file, rank)?;
 
                 let piece = board.get_piece_at(&position);
                 
-                match piece {
-                    Some(piece) => return Err(format!("Piece {} at {} will colide with {} at {} to move to {}", self.name(), position, piece.name(), from, to)),
-                    None => {}
+                if let Some(piece) = piece {
+                    return Err(format!("Piece {} at {} will colide with {} at {} to move to {}", self.name(), position, piece.name(), from, to));
                 }
             }
         }
@@ -73,16 +69,12 @@ pub trait Piece : Display{
                 let file : u8 = (from.file() as i8 + i * file_signum) as u8;
                 let rank : u8 = (from.rank() as i8 + i * rank_signum) as u8;
 
-                let position = match Position::new(file, rank){
-                    Ok(position) => position,
-                    Err(e) => return Err(e)
-                };
+                let position = Position::new(file, rank)?;
 
                 let piece = board.get_piece_at(&position);
                 
-                match piece {
-                    Some(piece) => return Err(format!("{} at {} will colide with {} at {} to move to {}", self.name(), from, piece.name(), position, to)),
-                    None => {}
+                if let Some(piece) = piece {
+                    return Err(format!("{} at {} will colide with {} at {} to move to {}", self.name(), from, piece.name(), position, to));
                 }
             }
         }
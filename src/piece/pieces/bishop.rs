@@ -65,7 +65,7 @@ impl DiagonalMovement for Bishop{}
 
 impl Display for Bishop {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut string = format!("B");
+        let mut string = "B".to_string();
 
         match self.color() {
             Color::White => string = string.green().to_string(),
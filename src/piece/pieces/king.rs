@@ -85,7 +85,7 @@ impl LinearMovement for King {
 
 impl Display for King {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut string = format!("K");
+        let mut string = "K".to_string();
 
         match self.color() {
             Color::White => string = string.green().to_string(),
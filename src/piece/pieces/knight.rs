@@ -77,7 +77,7 @@ impl Piece for Knight {
 
 impl Display for Knight {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut string = format!("N");
+        let mut string = "N".to_string();
 
         match self.color() {
             Color::White => string = string.green().to_string(),
@@ -54,7 +54,9 @@ impl Piece for Pawn {
     }
 
     fn is_valid_move(&self, position: &RelativePosition) -> bool {
-        if position.file() == 0 && position.rank() * self.rank_multiplier() <= 2 {
+        let forward = position.rank() * self.rank_multiplier();
+
+        if position.file() == 0 && (forward == 1 || forward == 2) {
             return true;
         }
 
@@ -74,21 +76,17 @@ impl Piece for Pawn {
     }
 
     fn possible_captures(&self) -> Vec<RelativePosition> {
-        let mut captures = Vec::new();
-
-        captures.push(RelativePosition::new(1, self.rank_multiplier()).unwrap());
-        captures.push(RelativePosition::new(-1, self.rank_multiplier()).unwrap());
-
-        return captures;
+        return vec![
+            RelativePosition::new(1, self.rank_multiplier()).unwrap(),
+            RelativePosition::new(-1, self.rank_multiplier()).unwrap(),
+        ];
     }
 
     fn possible_moves(&self) -> Vec<RelativePosition> {
-        let mut moves = Vec::new();
-        
-        moves.push(RelativePosition::new(0, self.rank_multiplier()).unwrap());
-        moves.push(RelativePosition::new(0, self.rank_multiplier() * 2).unwrap());
-
-        return moves;
+        return vec![
+            RelativePosition::new(0, self.rank_multiplier()).unwrap(),
+            RelativePosition::new(0, self.rank_multiplier() * 2).unwrap(),
+        ];
     }
 
     fn possible_plays(&self) -> Vec<RelativePosition> {
@@ -98,7 +96,7 @@ impl Piece for Pawn {
 
 impl Display for Pawn {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut string = format!("P");
+        let mut string = "P".to_string();
 
         match self.color() {
             Color::White => string = string.green().to_string(),
@@ -72,7 +72,7 @@ impl LinearMovement for Queen {}
 
 impl Display for Queen {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut string = format!("Q");
+        let mut string = "Q".to_string();
 
         match self.color() {
             Color::White => string = string.green().to_string(),
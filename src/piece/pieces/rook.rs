@@ -70,7 +70,7 @@ impl LinearMovement for Rook{}
 
 impl Display for Rook {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut string = format!("R");
+        let mut string = "R".to_string();
 
         match self.color() {
             Color::White => string = string.green().to_string(),
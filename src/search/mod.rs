@@ -0,0 +1,913 @@
+use std::{cmp::Reverse, fmt::Display, sync::{Arc, atomic::{AtomicBool, AtomicU64, Ordering}}, time::Duration};
+
+use crate::{book::{encode_move, zobrist::polyglot_key}, eval::{Evaluator, evaluate, nnue::Accumulators}, game::{Game, movement::Movement}};
+
+use self::{ordering::{MoveOrdering, is_tactical}, smp::Helpers, time::{Clock, StandardTimeManager, SystemClock, TimeControl, TimeManager}, tt::{Bound, TranspositionTable}};
+
+mod ordering;
+mod smp;
+pub mod time;
+pub mod tt;
+
+/// Score of being checkmated at the root. Mates found deeper are worth one point less per ply.
+const MATE : i32 = 30000;
+/// Scores beyond this bound are mates.
+const MATE_BOUND : i32 = MATE - 1000;
+const INFINITY : i32 = MATE + 1;
+const MAX_DEPTH : u32 = 64;
+
+/// What a position is worth to the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    /// Mate in that many moves, negative when the side to move gets mated.
+    Mate(i32),
+}
+
+impl Score {
+    fn from_internal(score: i32) -> Score {
+        if score >= MATE_BOUND {
+            return Score::Mate((MATE - score + 1) / 2);
+        }
+
+        if score <= -MATE_BOUND {
+            return Score::Mate(-(MATE + score) / 2);
+        }
+
+        return Score::Centipawns(score);
+    }
+
+    fn to_internal(self) -> i32 {
+        return match self {
+            Score::Centipawns(centipawns) => centipawns,
+            Score::Mate(moves) if moves > 0 => MATE - 2 * moves + 1,
+            Score::Mate(moves) => -MATE - 2 * moves,
+        };
+    }
+}
+
+impl Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Score::Centipawns(centipawns) => write!(f, "cp {}", centipawns),
+            Score::Mate(moves) => write!(f, "mate {}", moves),
+        }
+    }
+}
+
+/// When a search stops. Without any limit, it runs until it reaches the maximum depth or gets stopped.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    depth: Option<u32>,
+    nodes: Option<u64>,
+    time: Option<Duration>,
+    time_control: Option<TimeControl>,
+}
+
+impl Limits {
+    pub fn new() -> Limits {
+        Limits::default()
+    }
+
+    pub fn depth(mut self, depth: u32) -> Limits {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn nodes(mut self, nodes: u64) -> Limits {
+        self.nodes = Some(nodes);
+        self
+    }
+
+    /// Searches for exactly that long, unless another limit is reached first.
+    pub fn time(mut self, time: Duration) -> Limits {
+        self.time = Some(time);
+        self
+    }
+
+    /// Lets a [`StandardTimeManager`] decide how long to search from the clock, unless the search is given another
+    /// time manager.
+    pub fn time_control(mut self, time_control: TimeControl) -> Limits {
+        self.time_control = Some(time_control);
+        self
+    }
+}
+
+/// Stops a running search from another thread. The search then returns the result of its last completed iteration.
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle {
+    stopped: Arc<AtomicBool>,
+}
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        return self.stopped.load(Ordering::Relaxed);
+    }
+}
+
+/// One of the ranked lines of a MultiPV search.
+#[derive(Clone)]
+pub struct Line {
+    score: Score,
+    principal_variation: Vec<Movement>,
+}
+
+impl Line {
+    pub fn score(&self) -> Score {
+        self.score
+    }
+
+    /// The movements both sides are expected to play, starting with the one this line is about.
+    pub fn principal_variation(&self) -> &Vec<Movement> {
+        &self.principal_variation
+    }
+}
+
+/// The outcome of a search, or of one of its iterations.
+#[derive(Clone)]
+pub struct SearchResult {
+    best_move: Option<Movement>,
+    score: Score,
+    principal_variation: Vec<Movement>,
+    lines: Vec<Line>,
+    depth: u32,
+    nodes: u64,
+    elapsed: Duration,
+}
+
+impl SearchResult {
+    /// None when the side to move has no legal movement.
+    pub fn best_move(&self) -> Option<&Movement> {
+        self.best_move.as_ref()
+    }
+
+    pub fn score(&self) -> Score {
+        self.score
+    }
+
+    /// The movements both sides are expected to play, starting with the best one.
+    pub fn principal_variation(&self) -> &Vec<Movement> {
+        &self.principal_variation
+    }
+
+    /// The best lines, from the best one, which is also the principal variation. There are as many as the search was
+    /// asked for with [`Search::multi_pv`], unless the side to move has fewer legal movements.
+    pub fn lines(&self) -> &Vec<Line> {
+        &self.lines
+    }
+
+    /// Depth of the last completed iteration.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// A negamax alpha-beta search with iterative deepening.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::{game::Game, search::{Limits, Score, Search}};
+///
+/// let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+///
+/// let result = Search::new(Limits::new().depth(2)).run(&game);
+///
+/// assert_eq!(result.best_move().unwrap().to_uci(false), "a1a8");
+/// assert_eq!(result.score(), Score::Mate(1));
+/// ```
+pub struct Search {
+    limits: Limits,
+    stop: StopHandle,
+    /// Nodes searched by this thread.
+    nodes: u64,
+    /// Nodes searched by all the threads.
+    shared_nodes: Arc<AtomicU64>,
+    threads: usize,
+    /// Depth of the first iteration, above 1 for some helper threads.
+    first_depth: u32,
+    multi_pv: usize,
+    /// Root movements left out of the search, the ones the previous lines of a MultiPV iteration start with.
+    excluded: Vec<u16>,
+    clock: Arc<dyn Clock>,
+    start: Duration,
+    time_manager: Option<Box<dyn TimeManager>>,
+    /// The time past which the time manager interrupts the search.
+    hard_limit: Option<Duration>,
+    /// Keys of the positions since the last irreversible movement, to detect repetitions.
+    history: Vec<u64>,
+    table: Arc<TranspositionTable>,
+    evaluator: Evaluator,
+    /// The network accumulators along the searched line, with the [`Evaluator::Nnue`] evaluator.
+    accumulators: Option<Accumulators>,
+    ordering: MoveOrdering,
+    /// Whether movements are sorted before being searched, only turned off to measure what sorting saves.
+    #[cfg(test)]
+    sorts_movements: bool,
+}
+
+impl Search {
+    pub fn new(limits: Limits) -> Search {
+        Search {
+            limits,
+            stop: StopHandle::default(),
+            nodes: 0,
+            shared_nodes: Arc::new(AtomicU64::new(0)),
+            threads: 1,
+            first_depth: 1,
+            multi_pv: 1,
+            excluded: Vec::new(),
+            clock: Arc::new(SystemClock::new()),
+            start: Duration::ZERO,
+            time_manager: None,
+            hard_limit: None,
+            history: Vec::new(),
+            table: Arc::new(TranspositionTable::new(1)),
+            evaluator: Evaluator::Handcrafted,
+            accumulators: None,
+            ordering: MoveOrdering::new(),
+            #[cfg(test)]
+            sorts_movements: true,
+        }
+    }
+
+    /// Shares a transposition table, kept between searches. Each search otherwise gets its own table of 1 MB.
+    pub fn table(mut self, table: Arc<TranspositionTable>) -> Search {
+        self.table = table;
+        self
+    }
+
+    /// Searches with that many threads sharing the transposition table, a Lazy SMP search. With a single thread,
+    /// the default, the search is deterministic.
+    pub fn threads(mut self, threads: usize) -> Search {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Ranks that many lines in every iteration instead of only the best one, each line searching the movements the
+    /// previous ones did not start with. The search gets slower with every line.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use chess::{game::Game, search::{Limits, Score, Search}};
+    ///
+    /// // The rook mates on a8, or wins the queen.
+    /// let game = Game::from_fen("q5k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    ///
+    /// let result = Search::new(Limits::new().depth(2)).multi_pv(2).run(&game);
+    /// let lines = result.lines();
+    ///
+    /// assert_eq!(lines.len(), 2);
+    /// assert_eq!(lines[0].score(), Score::Mate(1));
+    /// assert_eq!(lines[0].principal_variation()[0].to_uci(false), "a1a8");
+    /// assert!(matches!(lines[1].score(), Score::Centipawns(_)));
+    /// ```
+    pub fn multi_pv(mut self, lines: usize) -> Search {
+        self.multi_pv = lines.max(1);
+        self
+    }
+
+    /// Scores the positions with a network instead of the handcrafted evaluation.
+    pub fn evaluator(mut self, evaluator: Evaluator) -> Search {
+        self.evaluator = evaluator;
+        self
+    }
+
+    /// Decides how long to search instead of the manager of the time control.
+    pub fn time_manager(mut self, time_manager: Box<dyn TimeManager>) -> Search {
+        self.time_manager = Some(time_manager);
+        self
+    }
+
+    /// Reads the time from another clock than the wall clock.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Search {
+        self.clock = clock;
+        self
+    }
+
+    /// A handle to stop the search while it runs.
+    pub fn stop_handle(&self) -> StopHandle {
+        return self.stop.clone();
+    }
+
+    pub fn run(&mut self, game: &Game) -> SearchResult {
+        return self.run_with(game, |_| ());
+    }
+
+    /// Searches the game, reporting the result of every completed iteration of the main thread.
+    pub fn run_with<F: FnMut(&SearchResult)>(&mut self, game: &Game, mut on_iteration: F) -> SearchResult {
+        self.shared_nodes.store(0, Ordering::Relaxed);
+        self.start = self.clock.now();
+        // Once for all the threads, so they all see each other's entries as part of the current search.
+        self.table.new_search();
+
+        if self.threads == 1 {
+            return self.run_iterations(game, &mut on_iteration);
+        }
+
+        let helpers = Helpers::spawn(self.threads - 1, game, self);
+        let result = self.run_iterations(game, &mut on_iteration);
+
+        let mut results = vec![result.clone()];
+        results.append(&mut helpers.finish());
+
+        // The helpers only search the best line, so they do not vote on the ranking of several.
+        let mut voted = match self.multi_pv {
+            1 => smp::vote(results).unwrap_or(result),
+            _ => result,
+        };
+        voted.nodes = self.shared_nodes.load(Ordering::Relaxed);
+        voted.elapsed = self.elapsed();
+
+        return voted;
+    }
+
+    /// Deepens the search one iteration at a time, until a limit is reached.
+    fn run_iterations(&mut self, game: &Game, on_iteration: &mut dyn FnMut(&SearchResult)) -> SearchResult {
+        self.nodes = 0;
+        self.start = self.clock.now();
+        self.history = Search::game_history(game);
+        self.ordering = MoveOrdering::new();
+        self.accumulators = match &self.evaluator {
+            Evaluator::Handcrafted => None,
+            Evaluator::Nnue(network) => Some(Accumulators::new(network.clone(), game)),
+        };
+
+        let mut result = SearchResult {
+            best_move: game.legal_movements().into_iter().next(),
+            score: Score::Centipawns(0),
+            principal_variation: Vec::new(),
+            lines: Vec::new(),
+            depth: 0,
+            nodes: 0,
+            elapsed: Duration::ZERO,
+        };
+
+        if result.best_move.is_none() {
+            result.score = Score::from_internal(Search::terminal_score(game, 0));
+            return result;
+        }
+
+        let max_depth = self.limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+
+        let has_own_manager = self.time_manager.is_some();
+        let mut time_manager = self.time_manager.take().or_else(|| {
+            self.limits.time_control.map(|time_control| Box::new(StandardTimeManager::new(time_control)) as Box<dyn TimeManager>)
+        });
+
+        if let Some(time_manager) = time_manager.as_mut() {
+            time_manager.start(game.legal_movements().len());
+        }
+
+        self.hard_limit = time_manager.as_ref().map(|time_manager| time_manager.hard_limit());
+
+        let line_count = self.multi_pv.min(game.legal_movements().len());
+
+        for depth in self.first_depth.min(max_depth)..=max_depth {
+            let mut lines = Vec::new();
+
+            for _ in 0..line_count {
+                let mut variation = Vec::new();
+                let score = self.negamax(game, depth, -INFINITY, INFINITY, 0, &mut variation);
+
+                // An interrupted line is only trusted when it already improved on the first movement it searched.
+                if self.should_stop() {
+                    if lines.is_empty() && !variation.is_empty() {
+                        lines.push(Line {
+                            score: Score::from_internal(score),
+                            principal_variation: variation,
+                        });
+                    }
+
+                    break;
+                }
+
+                match variation.first() {
+                    Some(movement) => self.excluded.push(encode_move(movement)),
+                    None => break,
+                }
+
+                lines.push(Line {
+                    score: Score::from_internal(score),
+                    principal_variation: variation,
+                });
+            }
+
+            self.excluded.clear();
+
+            // Only the first iteration may be interrupted, as it has nothing better to fall back on.
+            if self.should_stop() && (depth > self.first_depth || lines.is_empty()) {
+                break;
+            }
+
+            lines.sort_by_key(|line| Reverse(line.score.to_internal()));
+
+            result = SearchResult {
+                best_move: lines[0].principal_variation.first().cloned(),
+                score: lines[0].score,
+                principal_variation: lines[0].principal_variation.clone(),
+                lines,
+                depth,
+                nodes: self.shared_nodes.load(Ordering::Relaxed),
+                elapsed: self.elapsed(),
+            };
+
+            on_iteration(&result);
+
+            let is_resolved = result.lines.iter().all(|line| matches!(line.score, Score::Mate(moves) if depth as i32 >= 2 * moves.abs()));
+
+            if self.should_stop() || is_resolved {
+                break;
+            }
+
+            if time_manager.as_mut().is_some_and(|time_manager| !time_manager.should_continue(&result, self.elapsed())) {
+                break;
+            }
+        }
+
+        if has_own_manager {
+            self.time_manager = time_manager;
+        }
+
+        result.nodes = self.shared_nodes.load(Ordering::Relaxed);
+        result.elapsed = self.elapsed();
+
+        return result;
+    }
+
+    fn negamax(&mut self, game: &Game, depth: u32, mut alpha: i32, beta: i32, ply: usize, variation: &mut Vec<Movement>) -> i32 {
+        self.count_node();
+
+        if ply > 0 && self.is_draw(game) {
+            return 0;
+        }
+
+        if depth == 0 || ply >= MAX_DEPTH as usize {
+            return self.quiescence(game, alpha, beta, ply);
+        }
+
+        let key = *self.history.last().unwrap_or(&0);
+        let entry = self.table.probe(key, ply);
+
+        if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth() >= depth) {
+            let is_usable = match entry.bound() {
+                Bound::Exact => true,
+                Bound::Lower => entry.score() >= beta,
+                Bound::Upper => entry.score() <= alpha,
+            };
+
+            if is_usable {
+                return entry.score();
+            }
+        }
+
+        let mut movements = game.legal_movements();
+
+        if movements.is_empty() {
+            return Search::terminal_score(game, ply);
+        }
+
+        if self.sorts_movements() {
+            let hash_move = entry.and_then(|entry| entry.raw_move());
+            self.ordering.sort(game, &mut movements, hash_move, ply);
+        }
+
+        let original_alpha = alpha;
+        let mut best_move = None;
+
+        for movement in movements {
+            if ply == 0 && !self.excluded.is_empty() && self.excluded.contains(&encode_move(&movement)) {
+                continue;
+            }
+
+            let mut child = game.clone();
+
+            if child.play(movement.clone()).is_err() {
+                continue;
+            }
+
+            self.history.push(polyglot_key(&child));
+            self.make(&child);
+
+            let mut child_variation = Vec::new();
+            let score = -self.negamax(&child, depth - 1, -beta, -alpha, ply + 1, &mut child_variation);
+
+            self.unmake();
+            self.history.pop();
+
+            if self.should_stop() {
+                return alpha;
+            }
+
+            if score > alpha {
+                alpha = score;
+                best_move = Some(encode_move(&movement));
+
+                if alpha >= beta {
+                    if self.sorts_movements() {
+                        self.ordering.add_cutoff(game, &movement, depth, ply);
+                    }
+
+                    break;
+                }
+
+                variation.clear();
+                variation.push(movement);
+                variation.append(&mut child_variation);
+            }
+        }
+
+        let bound = match alpha {
+            alpha if alpha >= beta => Bound::Lower,
+            alpha if alpha > original_alpha => Bound::Exact,
+            _ => Bound::Upper,
+        };
+
+        // The root only sees some of its movements while searching the next lines of a MultiPV iteration.
+        if ply > 0 || self.excluded.is_empty() {
+            self.table.store(key, best_move, alpha, depth, bound, ply);
+        }
+
+        return alpha;
+    }
+
+    /// Searches the captures and promotions until the position is quiet, so the evaluation never stops in the middle
+    /// of an exchange. The side to move may stand pat on the static evaluation instead of capturing, unless it is in
+    /// check, where every evasion is searched.
+    fn quiescence(&mut self, game: &Game, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        let is_check = game.is_check();
+
+        if !is_check {
+            let stand_pat = self.evaluate(game);
+
+            if stand_pat >= beta || ply >= MAX_DEPTH as usize {
+                return stand_pat;
+            }
+
+            alpha = alpha.max(stand_pat);
+        }
+
+        let mut movements = game.legal_movements();
+
+        if movements.is_empty() {
+            return Search::terminal_score(game, ply);
+        }
+
+        if !is_check {
+            movements.retain(is_tactical);
+        }
+
+        if self.sorts_movements() {
+            self.ordering.sort(game, &mut movements, None, ply);
+        }
+
+        for movement in movements {
+            let mut child = game.clone();
+
+            if child.play(movement).is_err() {
+                continue;
+            }
+
+            self.count_node();
+            self.make(&child);
+            let score = -self.quiescence(&child, -beta, -alpha, ply + 1);
+            self.unmake();
+
+            if self.should_stop() {
+                return alpha;
+            }
+
+            if score > alpha {
+                alpha = score;
+
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+
+        return alpha;
+    }
+
+    /// Updates the network accumulators for the position after a movement.
+    fn make(&mut self, child: &Game) {
+        if let (Some(accumulators), Some(movement)) = (self.accumulators.as_mut(), child.movements().last()) {
+            accumulators.push(movement);
+        }
+    }
+
+    fn unmake(&mut self) {
+        if let Some(accumulators) = self.accumulators.as_mut() {
+            accumulators.pop();
+        }
+    }
+
+    fn evaluate(&self, game: &Game) -> i32 {
+        return match &self.accumulators {
+            Some(accumulators) => accumulators.evaluate(),
+            None => evaluate(game),
+        };
+    }
+
+    fn terminal_score(game: &Game, ply: usize) -> i32 {
+        return match game.is_check() {
+            true => -MATE + ply as i32,
+            false => 0,
+        };
+    }
+
+    fn is_draw(&self, game: &Game) -> bool {
+        if game.halfmove_clock() >= 100 {
+            return true;
+        }
+
+        let key = match self.history.last() {
+            Some(key) => *key,
+            None => return false,
+        };
+
+        let reversible = (game.halfmove_clock() as usize + 1).min(self.history.len());
+
+        return self.history[self.history.len() - reversible..self.history.len() - 1].contains(&key);
+    }
+
+    /// Always true outside of tests, so the checks compile away.
+    #[cfg(not(test))]
+    fn sorts_movements(&self) -> bool {
+        return true;
+    }
+
+    #[cfg(test)]
+    fn sorts_movements(&self) -> bool {
+        return self.sorts_movements;
+    }
+
+    fn should_stop(&self) -> bool {
+        if self.stop.is_stopped() {
+            return true;
+        }
+
+        if self.limits.nodes.is_some_and(|nodes| self.shared_nodes.load(Ordering::Relaxed) >= nodes) {
+            return true;
+        }
+
+        let elapsed = self.elapsed();
+
+        return self.limits.time.is_some_and(|time| elapsed >= time) || self.hard_limit.is_some_and(|time| elapsed >= time);
+    }
+
+    fn count_node(&mut self) {
+        self.nodes += 1;
+        self.shared_nodes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn elapsed(&self) -> Duration {
+        return self.clock.now().saturating_sub(self.start);
+    }
+
+    /// Keys of every position of the game, the current one last.
+    fn game_history(game: &Game) -> Vec<u64> {
+        let mut replay = match Game::from_fen(game.start_fen()) {
+            Ok(replay) => replay,
+            Err(_) => return vec![polyglot_key(game)],
+        };
+
+        let mut history = vec![polyglot_key(&replay)];
+
+        for movement in game.movements() {
+            if replay.play(movement.clone()).is_err() {
+                return vec![polyglot_key(game)];
+            }
+
+            history.push(polyglot_key(&replay));
+        }
+
+        return history;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    fn search(fen: &str, limits: Limits) -> SearchResult {
+        return Search::new(limits).run(&Game::from_fen(fen).unwrap());
+    }
+
+    fn uci(movements: &[Movement]) -> Vec<String> {
+        return movements.iter().map(|movement| movement.to_uci(false)).collect();
+    }
+
+    #[test]
+    fn finds_mates(){
+        let result = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", Limits::new().depth(4));
+        assert_eq!(result.score(), Score::Mate(1));
+        assert_eq!(uci(result.principal_variation()), vec!["a1a8"]);
+
+        // The side to move gets mated whatever it plays.
+        let result = search("7k/8/5Q1K/8/8/8/8/8 b - - 0 1", Limits::new().depth(3));
+        assert_eq!(result.score(), Score::Mate(-1));
+        assert_eq!(result.principal_variation().len(), 2);
+
+        let result = search("6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1", Limits::new().depth(1));
+        assert!(matches!(result.score(), Score::Centipawns(_)));
+
+        let mated = search("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", Limits::new().depth(3));
+        assert!(mated.best_move().is_none());
+        assert_eq!(mated.score(), Score::Mate(0));
+    }
+
+    #[test]
+    fn wins_material(){
+        // The queen on d5 is hanging.
+        let result = search("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", Limits::new().depth(2));
+
+        assert_eq!(result.best_move().unwrap().to_uci(false), "d2d5");
+        assert_eq!(result.depth(), 2);
+        assert!(matches!(result.score(), Score::Centipawns(score) if score > 300));
+    }
+
+    #[test]
+    fn respects_limits(){
+        let result = search("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3", Limits::new().nodes(500));
+        assert!(result.best_move().is_some());
+        assert!(result.nodes() < 1000);
+
+        let result = search("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3", Limits::new().time(Duration::from_millis(200)));
+        assert!(result.elapsed() < Duration::from_secs(2));
+        assert!(result.depth() >= 1);
+
+        let mut search = Search::new(Limits::new());
+        let stop = search.stop_handle();
+
+        let stopper = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            stop.stop();
+        });
+
+        let result = search.run(&Game::new_classical());
+        stopper.join().unwrap();
+
+        assert!(result.best_move().is_some());
+        assert!(result.depth() < MAX_DEPTH);
+    }
+
+    #[test]
+    fn resolves_captures_beyond_the_horizon(){
+        // The pawn on d5 is defended, taking it loses the queen.
+        let result = search("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", Limits::new().depth(1));
+
+        assert_ne!(result.best_move().unwrap().to_uci(false), "d1d5");
+    }
+
+    #[test]
+    fn sorting_movements_saves_nodes(){
+        for fen in ["r3k3/ppp2p2/2n5/4p3/4P3/2N5/PPP2P2/4K2R w - - 0 1", "6k1/5ppp/2r5/8/3N4/1Q6/5PPP/6K1 w - - 0 1"] {
+            let game = Game::from_fen(fen).unwrap();
+
+            let sorted = Search::new(Limits::new().depth(3)).run(&game);
+
+            let mut search = Search::new(Limits::new().depth(3));
+            search.sorts_movements = false;
+            let unsorted = search.run(&game);
+
+            assert_eq!(sorted.score(), unsorted.score());
+            assert!(sorted.nodes() * 2 < unsorted.nodes(), "{} nodes sorted, {} unsorted", sorted.nodes(), unsorted.nodes());
+        }
+    }
+
+    #[test]
+    fn reuses_the_transposition_table(){
+        let game = Game::from_fen("r3k3/ppp2p2/2n5/4p3/4P3/2N5/PPP2P2/4K2R w - - 0 1").unwrap();
+        let table = Arc::new(TranspositionTable::new(1));
+
+        let first = Search::new(Limits::new().depth(3)).table(table.clone()).run(&game);
+
+        let second = Search::new(Limits::new().depth(3)).table(table.clone()).run(&game);
+
+        assert_eq!(first.score(), second.score());
+        assert!(second.nodes() * 2 < first.nodes(), "{} nodes then {}", first.nodes(), second.nodes());
+    }
+
+    #[test]
+    fn manages_time(){
+        // A single legal movement is played at once, however long the clock.
+        let limits = Limits::new().time_control(TimeControl::new(Duration::from_secs(600), Duration::ZERO));
+        assert_eq!(search("7k/8/8/8/8/8/1r6/K7 w - - 0 1", limits).depth(), 1);
+
+        struct Iterations(u32, time::FakeClock);
+
+        impl TimeManager for Iterations {
+            fn start(&mut self, _: usize) {}
+
+            fn hard_limit(&self) -> Duration {
+                Duration::from_secs(1)
+            }
+
+            fn should_continue(&mut self, iteration: &SearchResult, elapsed: Duration) -> bool {
+                self.1.advance(Duration::from_millis(300));
+                return iteration.depth() < self.0 && elapsed < self.hard_limit();
+            }
+        }
+
+        let clock = time::FakeClock::new();
+        let game = Game::from_fen("r3k3/ppp2p2/2n5/4p3/4P3/2N5/PPP2P2/4K2R w - - 0 1").unwrap();
+
+        let result = Search::new(Limits::new()).time_manager(Box::new(Iterations(2, clock.clone()))).clock(Arc::new(clock.clone())).run(&game);
+        assert_eq!(result.depth(), 2);
+        assert_eq!(result.elapsed(), Duration::from_millis(600));
+
+        // The fifth iteration starts past the hard limit of the fake clock, and gets interrupted.
+        let result = Search::new(Limits::new()).time_manager(Box::new(Iterations(10, clock.clone()))).clock(Arc::new(clock)).run(&game);
+        assert_eq!(result.depth(), 4);
+    }
+
+    #[test]
+    fn detects_repetitions(){
+        let mut game = Game::from_fen("k7/8/8/8/8/8/8/K6R w - - 0 1").unwrap();
+        let mut search = Search::new(Limits::new().depth(1));
+
+        for (index, uci) in ["h1h2", "a8b8", "h2h1", "b8a8"].iter().enumerate() {
+            let movement = Movement::from_uci(uci, &game).unwrap();
+            game.play(movement).unwrap();
+
+            search.history = Search::game_history(&game);
+            assert_eq!(search.history.len(), index + 2);
+            assert_eq!(search.is_draw(&game), index == 3);
+        }
+    }
+
+    #[test]
+    fn searches_with_threads(){
+        let game = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+
+        let single = Search::new(Limits::new().depth(3)).run(&game);
+        let repeated = Search::new(Limits::new().depth(3)).run(&game);
+
+        // A single thread always searches the same tree.
+        assert_eq!(single.nodes(), repeated.nodes());
+        assert_eq!(uci(single.principal_variation()), uci(repeated.principal_variation()));
+
+        let result = Search::new(Limits::new().depth(3)).threads(4).run(&game);
+        assert_eq!(result.best_move().unwrap().to_uci(false), "d2d5");
+        assert!(result.nodes() >= single.nodes());
+
+        let result = Search::new(Limits::new().depth(4)).threads(3).run(&Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap());
+        assert_eq!(result.score(), Score::Mate(1));
+
+        let result = Search::new(Limits::new().nodes(300)).threads(2).run(&game);
+        assert!(result.best_move().is_some());
+        assert!(result.nodes() < 1000);
+
+        // The threads share one generation of the table, so whatever they stored counts as the current search.
+        let table = Arc::new(TranspositionTable::new(1));
+        let generation = table.generation();
+
+        Search::new(Limits::new().nodes(20000)).threads(4).table(table.clone()).run(&Game::from_fen("4k3/pp6/8/3q4/8/8/PP1R4/4K3 w - - 0 1").unwrap());
+
+        assert_eq!(table.generation(), generation + 1);
+        assert!(table.hashfull() > 10);
+    }
+
+    #[test]
+    fn ranks_several_lines(){
+        let game = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let result = Search::new(Limits::new().depth(2)).multi_pv(3).run(&game);
+
+        assert_eq!(result.lines().len(), 3);
+        assert_eq!(result.lines()[0].principal_variation()[0].to_uci(false), "d2d5");
+        assert_eq!(uci(result.principal_variation()), uci(result.lines()[0].principal_variation()));
+
+        let scores : Vec<i32> = result.lines().iter().map(|line| line.score().to_internal()).collect();
+        assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+
+        let mut first_moves : Vec<String> = result.lines().iter().map(|line| line.principal_variation()[0].to_uci(false)).collect();
+        first_moves.sort();
+        first_moves.dedup();
+        assert_eq!(first_moves.len(), 3);
+
+        // The single line search finds the same best line.
+        let single = Search::new(Limits::new().depth(2)).run(&game);
+        assert_eq!(single.lines().len(), 1);
+        assert_eq!(single.score(), result.score());
+
+        // There are never more lines than legal movements.
+        let result = Search::new(Limits::new().depth(2)).multi_pv(5).run(&Game::from_fen("7k/8/8/8/8/8/1r6/K7 w - - 0 1").unwrap());
+        assert_eq!(result.lines().len(), 1);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{book::encode_move, color::Color, game::{Game, movement::Movement}};
+
+const HASH_MOVE : i32 = 1_000_000;
+const CAPTURE : i32 = 100_000;
+const KILLERS : [i32; 2] = [90_000, 80_000];
+/// Keeps the history scores of quiet movements below the killers.
+const MAX_HISTORY : i32 = 50_000;
+
+/// Sorts movements so the ones most likely to cause a cutoff are searched first: the hash move, then captures by
+/// most valuable victim and least valuable attacker, then the killer moves of the ply, then the quiet movements that
+/// caused the most cutoffs so far.
+pub(super) struct MoveOrdering {
+    /// Two quiet movements per ply that caused a cutoff in a sibling node.
+    killers: Vec<[Option<u16>; 2]>,
+    /// Cutoff counts per side, indexed by the origin and destination of the movement.
+    history: Vec<i32>,
+}
+
+impl MoveOrdering {
+    pub(super) fn new() -> MoveOrdering {
+        MoveOrdering {
+            killers: Vec::new(),
+            history: vec![0; 2 * 4096],
+        }
+    }
+
+    pub(super) fn sort(&self, game: &Game, movements: &mut [Movement], hash_move: Option<u16>, ply: usize) {
+        let killers = self.killers.get(ply).cloned().unwrap_or_default();
+
+        movements.sort_by_cached_key(|movement| {
+            let raw_move = encode_move(movement);
+
+            let score = if Some(raw_move) == hash_move {
+                HASH_MOVE
+            } else if is_tactical(movement) {
+                CAPTURE + mvv_lva(game, movement)
+            } else if killers[0] == Some(raw_move) {
+                KILLERS[0]
+            } else if killers[1] == Some(raw_move) {
+                KILLERS[1]
+            } else {
+                self.history[history_index(game.turn(), raw_move)]
+            };
+
+            return -score;
+        });
+    }
+
+    /// Remembers a quiet movement that caused a cutoff.
+    pub(super) fn add_cutoff(&mut self, game: &Game, movement: &Movement, depth: u32, ply: usize) {
+        if is_tactical(movement) {
+            return;
+        }
+
+        let raw_move = encode_move(movement);
+
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+
+        let killers = &mut self.killers[ply];
+
+        if killers[0] != Some(raw_move) {
+            killers[1] = killers[0];
+            killers[0] = Some(raw_move);
+        }
+
+        let index = history_index(game.turn(), raw_move);
+        self.history[index] += (depth * depth) as i32;
+
+        if self.history[index] > MAX_HISTORY {
+            self.history.iter_mut().for_each(|score| *score /= 2);
+        }
+    }
+}
+
+/// Captures and promotions, the movements searched by the quiescence search.
+pub(super) fn is_tactical(movement: &Movement) -> bool {
+    return matches!(movement, Movement::Capture(..)) || movement.promotion().is_some();
+}
+
+/// Ten times the value of the captured piece minus the value of the capturing one. En passant captures a pawn.
+pub(super) fn mvv_lva(game: &Game, movement: &Movement) -> i32 {
+    let victim = match movement.to().and_then(|to| game.board().get_piece_at(to).as_ref()) {
+        Some(piece) => piece.value() as i32,
+        None if matches!(movement, Movement::Capture(..)) => 1,
+        None => 0,
+    };
+
+    let promotion = match movement.promotion() {
+        Some(piece) => piece.value() as i32,
+        None => 0,
+    };
+
+    return 10 * (victim + promotion) - movement.piece().value() as i32;
+}
+
+fn history_index(color: &Color, raw_move: u16) -> usize {
+    let side = match color {
+        Color::White => 0,
+        Color::Black => 1,
+    };
+
+    return side * 4096 + (raw_move & 0xfff) as usize;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(game: &Game, ordering: &MoveOrdering, hash_move: Option<u16>) -> Vec<String> {
+        let mut movements = game.legal_movements();
+        ordering.sort(game, &mut movements, hash_move, 0);
+
+        return movements.iter().map(|movement| movement.to_uci(false)).collect();
+    }
+
+    #[test]
+    fn sorts_by_heuristics(){
+        // The pawn can take the rook or the knight, the queen only the rook.
+        let game = Game::from_fen("4k3/8/8/2r1n3/3P4/8/8/2Q1K3 w - - 0 1").unwrap();
+        let mut ordering = MoveOrdering::new();
+
+        assert_eq!(sorted(&game, &ordering, None)[..3], ["d4c5", "c1c5", "d4e5"]);
+
+        let killer = Movement::from_uci("c1h6", &game).unwrap();
+        ordering.add_cutoff(&game, &killer, 3, 0);
+        ordering.add_cutoff(&game, &Movement::from_uci("c1c5", &game).unwrap(), 3, 0);
+
+        let movements = sorted(&game, &ordering, Some(encode_move(&Movement::from_uci("e1f2", &game).unwrap())));
+        assert_eq!(movements[0], "e1f2");
+        assert_eq!(movements[1..4], ["d4c5", "c1c5", "d4e5"]);
+        assert_eq!(movements[4], "c1h6");
+    }
+}
@@ -0,0 +1,123 @@
+use std::{collections::HashMap, thread::{self, JoinHandle}};
+
+use crate::{book::encode_move, game::Game};
+
+use super::{Limits, Search, SearchResult, StopHandle};
+
+/// Helper threads of a Lazy SMP search: each one searches the same position on its own, and they only cooperate
+/// through the shared transposition table, where they leave the results the others pick up.
+pub(super) struct Helpers {
+    stop: StopHandle,
+    threads: Vec<JoinHandle<SearchResult>>,
+}
+
+impl Helpers {
+    pub(super) fn spawn(count: usize, game: &Game, main: &Search) -> Helpers {
+        let stop = StopHandle::default();
+
+        let threads = (0..count).map(|index| {
+            let limits = Limits {
+                depth: main.limits.depth,
+                ..Limits::default()
+            };
+
+            let mut helper = Search::new(limits).table(main.table.clone()).evaluator(main.evaluator.clone());
+            helper.stop = stop.clone();
+            helper.shared_nodes = main.shared_nodes.clone();
+            helper.clock = main.clock.clone();
+            // Half the helpers skip the first iteration, so they do not all search the same depth at the same time.
+            helper.first_depth = 1 + (index % 2) as u32;
+
+            let game = game.clone();
+
+            return thread::spawn(move || helper.run_iterations(&game, &mut |_| ()));
+        }).collect();
+
+        Helpers {
+            stop,
+            threads,
+        }
+    }
+
+    /// Stops the helpers and collects what they found.
+    pub(super) fn finish(self) -> Vec<SearchResult> {
+        self.stop.stop();
+
+        return self.threads.into_iter().filter_map(|thread| thread.join().ok()).collect();
+    }
+}
+
+/// Picks the best movement among the results of all threads. Each thread votes for its movement, with a weight
+/// growing with the depth it reached and with how much better its score is than the worst one, and the result of the
+/// deepest thread behind the most voted movement wins. Ties go to the first result, the one of the main thread.
+pub(super) fn vote(results: Vec<SearchResult>) -> Option<SearchResult> {
+    let results : Vec<SearchResult> = results.into_iter().filter(|result| result.best_move.is_some() && result.depth > 0).collect();
+    let worst = results.iter().map(|result| result.score.to_internal()).min()?;
+
+    let mut votes : HashMap<u16, i64> = HashMap::new();
+
+    for result in &results {
+        let weight = (result.score.to_internal() - worst + 14) as i64 * result.depth as i64;
+        *votes.entry(encode_move(result.best_move.as_ref()?)).or_default() += weight;
+    }
+
+    let mut best : Option<(i64, u32, &SearchResult)> = None;
+
+    for result in &results {
+        let vote = votes[&encode_move(result.best_move.as_ref()?)];
+
+        if best.is_none_or(|(best_vote, best_depth, _)| vote > best_vote || (vote == best_vote && result.depth > best_depth)) {
+            best = Some((vote, result.depth, result));
+        }
+    }
+
+    return best.map(|(_, _, result)| result.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{game::movement::Movement, search::Score};
+
+    use super::*;
+
+    fn result(game: &Game, uci: &str, score: Score, depth: u32) -> SearchResult {
+        return SearchResult {
+            best_move: Some(Movement::from_uci(uci, game).unwrap()),
+            score,
+            principal_variation: Vec::new(),
+            lines: Vec::new(),
+            depth,
+            nodes: 0,
+            elapsed: Duration::ZERO,
+        };
+    }
+
+    fn winner(results: Vec<SearchResult>) -> (String, u32) {
+        let result = vote(results).unwrap();
+        return (result.best_move().unwrap().to_uci(false), result.depth());
+    }
+
+    #[test]
+    fn votes_for_the_best_move(){
+        let game = Game::new_classical();
+
+        // Two threads agree on e4, against a deeper thread preferring d4 by a little.
+        assert_eq!(winner(vec![
+            result(&game, "e2e4", Score::Centipawns(30), 6),
+            result(&game, "d2d4", Score::Centipawns(35), 7),
+            result(&game, "e2e4", Score::Centipawns(28), 5),
+        ]), (String::from("e2e4"), 6));
+
+        // A much better score outweighs the number of threads.
+        assert_eq!(winner(vec![
+            result(&game, "e2e4", Score::Centipawns(30), 6),
+            result(&game, "g1f3", Score::Mate(4), 6),
+            result(&game, "e2e4", Score::Centipawns(28), 6),
+        ]), (String::from("g1f3"), 6));
+
+        assert_eq!(winner(vec![result(&game, "c2c4", Score::Centipawns(0), 1)]), (String::from("c2c4"), 1));
+        assert!(vote(Vec::new()).is_none());
+    }
+}
@@ -0,0 +1,284 @@
+use std::{sync::{Arc, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant}};
+
+use crate::book::encode_move;
+
+use super::{Score, SearchResult};
+
+/// Moves the remaining time is split over when the time control does not say how many are left.
+const DEFAULT_MOVES_TO_GO : u32 = 30;
+/// Time kept for the communication with the GUI.
+const MOVE_OVERHEAD : Duration = Duration::from_millis(30);
+/// A score drop, in centipawns, past which the search is given more time.
+const SCORE_DROP : i32 = 30;
+
+/// Where the search reads the time from, so tests can run it against a fake clock.
+pub trait Clock : Send + Sync {
+    /// The time since an arbitrary origin.
+    fn now(&self) -> Duration;
+}
+
+/// The wall clock.
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        return self.origin.elapsed();
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time.
+///
+/// ## Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use chess::search::time::{Clock, FakeClock};
+///
+/// let clock = FakeClock::new();
+/// clock.advance(Duration::from_millis(250));
+///
+/// assert_eq!(clock.clone().now(), Duration::from_millis(250));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FakeClock {
+    nanoseconds: Arc<AtomicU64>,
+}
+
+impl FakeClock {
+    pub fn new() -> FakeClock {
+        FakeClock::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.nanoseconds.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Duration {
+        return Duration::from_nanos(self.nanoseconds.load(Ordering::Relaxed));
+    }
+}
+
+/// The clock of the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    remaining: Duration,
+    increment: Duration,
+    moves_to_go: Option<u32>,
+}
+
+impl TimeControl {
+    pub fn new(remaining: Duration, increment: Duration) -> TimeControl {
+        TimeControl {
+            remaining,
+            increment,
+            moves_to_go: None,
+        }
+    }
+
+    /// Moves left until the next time control, when the clock gets more time.
+    pub fn moves_to_go(mut self, moves_to_go: u32) -> TimeControl {
+        self.moves_to_go = Some(moves_to_go.max(1));
+        self
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.remaining
+    }
+
+    pub fn increment(&self) -> Duration {
+        self.increment
+    }
+}
+
+/// Decides how long a search under a time control runs.
+pub trait TimeManager : Send {
+    /// Called when the search starts, with the number of legal movements.
+    fn start(&mut self, movements: usize);
+
+    /// The search is interrupted past this time, even in the middle of an iteration.
+    fn hard_limit(&self) -> Duration;
+
+    /// Called after every completed iteration. Returns whether the search goes one ply deeper.
+    fn should_continue(&mut self, iteration: &SearchResult, elapsed: Duration) -> bool;
+}
+
+/// Spends an even share of the remaining time on each move, plus most of the increment.
+///
+/// The soft budget is extended when the best movement changes between iterations or its score drops, since the
+/// search has not settled yet, but never past the hard budget. A new iteration is only started while less than half
+/// the budget is spent, as it usually takes longer than all the previous ones. With a single legal movement, the
+/// search stops after the first iteration.
+///
+/// ## Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use chess::search::time::{StandardTimeManager, TimeControl, TimeManager};
+///
+/// let mut manager = StandardTimeManager::new(TimeControl::new(Duration::from_secs(60), Duration::from_secs(1)));
+/// manager.start(20);
+///
+/// assert_eq!(manager.soft_limit(), Duration::from_millis(2749));
+/// assert_eq!(manager.hard_limit(), Duration::from_millis(10996));
+/// ```
+pub struct StandardTimeManager {
+    soft: Duration,
+    hard: Duration,
+    /// Percentage of the soft budget to use, raised while the search is unstable.
+    scale: u32,
+    movements: usize,
+    previous: Option<(u16, Score)>,
+}
+
+impl StandardTimeManager {
+    pub fn new(time_control: TimeControl) -> StandardTimeManager {
+        let available = time_control.remaining.saturating_sub(MOVE_OVERHEAD);
+        let moves_to_go = time_control.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO);
+
+        let soft = available / moves_to_go + time_control.increment * 3 / 4;
+        let hard = (soft * 4).min(available / 2).max(Duration::from_millis(1));
+
+        StandardTimeManager {
+            soft: soft.min(hard),
+            hard,
+            scale: 100,
+            movements: 0,
+            previous: None,
+        }
+    }
+
+    /// The time the search aims at, extended while it is unstable.
+    pub fn soft_limit(&self) -> Duration {
+        return (self.soft * self.scale / 100).min(self.hard);
+    }
+}
+
+impl TimeManager for StandardTimeManager {
+    fn start(&mut self, movements: usize) {
+        self.movements = movements;
+        self.scale = 100;
+        self.previous = None;
+    }
+
+    fn hard_limit(&self) -> Duration {
+        self.hard
+    }
+
+    fn should_continue(&mut self, iteration: &SearchResult, elapsed: Duration) -> bool {
+        if self.movements == 1 {
+            return false;
+        }
+
+        let best_move = match iteration.best_move() {
+            Some(movement) => encode_move(movement),
+            None => return false,
+        };
+
+        if let Some((previous_move, previous_score)) = self.previous {
+            let has_changed = previous_move != best_move;
+            let has_dropped = match (previous_score, iteration.score()) {
+                (Score::Centipawns(previous), Score::Centipawns(score)) => score <= previous - SCORE_DROP,
+                (Score::Mate(previous), Score::Mate(score)) => previous > 0 && score < 0,
+                (Score::Centipawns(_), Score::Mate(score)) => score < 0,
+                (Score::Mate(previous), Score::Centipawns(_)) => previous > 0,
+            };
+
+            // Instability raises the budget, which then decays back while the search agrees with itself.
+            self.scale = match (has_changed, has_dropped) {
+                (false, false) => (self.scale * 9 / 10).max(100),
+                (true, false) => (self.scale + 50).min(300),
+                (false, true) => (self.scale + 30).min(300),
+                (true, true) => (self.scale + 80).min(300),
+            };
+        }
+
+        self.previous = Some((best_move, iteration.score()));
+
+        return elapsed < self.soft_limit() / 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{Game, movement::Movement};
+
+    use super::*;
+
+    fn iteration(game: &Game, uci: &str, score: Score) -> SearchResult {
+        return SearchResult {
+            best_move: Some(Movement::from_uci(uci, game).unwrap()),
+            score,
+            principal_variation: Vec::new(),
+            lines: Vec::new(),
+            depth: 1,
+            nodes: 0,
+            elapsed: Duration::ZERO,
+        };
+    }
+
+    #[test]
+    fn allocates_budgets(){
+        let manager = StandardTimeManager::new(TimeControl::new(Duration::from_millis(3000), Duration::ZERO));
+        assert_eq!((manager.soft_limit(), manager.hard_limit()), (Duration::from_millis(99), Duration::from_millis(396)));
+
+        // The last move before the time control may use half the clock.
+        let manager = StandardTimeManager::new(TimeControl::new(Duration::from_millis(3000), Duration::from_secs(1)).moves_to_go(1));
+        assert_eq!((manager.soft_limit(), manager.hard_limit()), (Duration::from_millis(1485), Duration::from_millis(1485)));
+
+        let manager = StandardTimeManager::new(TimeControl::new(Duration::from_millis(10), Duration::ZERO));
+        assert_eq!(manager.hard_limit(), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn extends_unstable_searches(){
+        let game = Game::new_classical();
+        let clock = FakeClock::new();
+        let mut manager = StandardTimeManager::new(TimeControl::new(Duration::from_secs(30), Duration::ZERO));
+        manager.start(20);
+
+        assert_eq!(manager.soft_limit(), Duration::from_millis(999));
+        assert!(manager.should_continue(&iteration(&game, "e2e4", Score::Centipawns(30)), clock.now()));
+
+        clock.advance(Duration::from_millis(450));
+        assert!(manager.should_continue(&iteration(&game, "e2e4", Score::Centipawns(25)), clock.now()));
+
+        // Past half the budget, but the best movement changed and its score dropped.
+        clock.advance(Duration::from_millis(400));
+        assert!(manager.should_continue(&iteration(&game, "d2d4", Score::Centipawns(-10)), clock.now()));
+        assert_eq!(manager.soft_limit(), Duration::from_micros(1798200));
+
+        clock.advance(Duration::from_millis(200));
+        assert!(!manager.should_continue(&iteration(&game, "d2d4", Score::Centipawns(-10)), clock.now()));
+    }
+
+    #[test]
+    fn stops_on_forced_moves(){
+        let game = Game::from_fen("7k/8/8/8/8/8/1r6/K7 w - - 0 1").unwrap();
+        assert_eq!(game.legal_movements().len(), 1);
+
+        let mut manager = StandardTimeManager::new(TimeControl::new(Duration::from_secs(60), Duration::ZERO));
+        manager.start(game.legal_movements().len());
+
+        assert!(!manager.should_continue(&iteration(&game, "a1b2", Score::Centipawns(0)), Duration::ZERO));
+    }
+}
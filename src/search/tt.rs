@@ -0,0 +1,313 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use super::MATE_BOUND;
+
+/// Bytes of an entry: the key mixed with the data, then the data.
+const ENTRY_SIZE : usize = 16;
+/// The generation is stored on 6 bits.
+const GENERATIONS : u8 = 64;
+
+/// How the stored score relates to the real score of the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is exact.
+    Exact,
+    /// The real score is at least the stored one, the search failed high.
+    Lower,
+    /// The real score is at most the stored one, the search failed low.
+    Upper,
+}
+
+impl Bound {
+    fn to_bits(self) -> u64 {
+        return match self {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+    }
+
+    fn from_bits(bits: u64) -> Option<Bound> {
+        return match bits {
+            1 => Some(Bound::Exact),
+            2 => Some(Bound::Lower),
+            3 => Some(Bound::Upper),
+            _ => None,
+        };
+    }
+}
+
+/// What a previous search found about a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    raw_move: Option<u16>,
+    score: i32,
+    depth: u32,
+    bound: Bound,
+    generation: u8,
+}
+
+impl Entry {
+    /// The best movement found, encoded like [`crate::book::encode_move`].
+    pub fn raw_move(&self) -> Option<u16> {
+        self.raw_move
+    }
+
+    /// The score, with mates counted from the probed position.
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn bound(&self) -> Bound {
+        self.bound
+    }
+
+    /// Packs the entry in 64 bits: the movement, the score, the depth, the bound and the generation.
+    fn to_bits(self) -> u64 {
+        return self.raw_move.unwrap_or(0) as u64
+            | (self.score as i16 as u16 as u64) << 16
+            | (self.depth.min(u8::MAX as u32) as u64) << 32
+            | self.bound.to_bits() << 40
+            | ((self.generation % GENERATIONS) as u64) << 42;
+    }
+
+    fn from_bits(bits: u64) -> Option<Entry> {
+        let raw_move = (bits & 0xffff) as u16;
+
+        return Some(Entry {
+            raw_move: if raw_move == 0 { None } else { Some(raw_move) },
+            score: (bits >> 16) as u16 as i16 as i32,
+            depth: ((bits >> 32) & 0xff) as u32,
+            bound: Bound::from_bits((bits >> 40) & 3)?,
+            generation: ((bits >> 42) & 0x3f) as u8,
+        });
+    }
+}
+
+/// A fixed-size hash table of searched positions, keyed by their Zobrist key.
+///
+/// The number of entries is a power of two so the low bits of the key give the slot. Each slot keeps the key mixed
+/// with the entry next to the entry itself, so several threads can share the table without locks: an entry torn by
+/// concurrent writes no longer matches its key and is ignored.
+///
+/// An entry is replaced by one of another position unless it comes from the current search and is deeper by more
+/// than two plies.
+///
+/// ## Examples
+///
+/// ```
+/// use chess::search::tt::{Bound, TranspositionTable};
+///
+/// let table = TranspositionTable::new(1);
+/// assert_eq!(table.len(), 65536);
+///
+/// table.store(0x463b96181691fc9c, Some(0x031c), 35, 4, Bound::Exact, 0);
+///
+/// let entry = table.probe(0x463b96181691fc9c, 0).unwrap();
+/// assert_eq!(entry.score(), 35);
+/// assert_eq!(entry.raw_move(), Some(0x031c));
+/// assert!(table.probe(0x1234, 0).is_none());
+/// ```
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// A table of at most `size_mb` megabytes, and at least one entry.
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let bytes = size_mb.saturating_mul(1024 * 1024);
+        let mut entries = 1;
+
+        while entries * 2 * ENTRY_SIZE <= bytes {
+            entries *= 2;
+        }
+
+        TranspositionTable {
+            slots: (0..entries).map(|_| [AtomicU64::new(0), AtomicU64::new(0)]).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        return self.slots.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.slots.is_empty();
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot[0].store(0, Ordering::Relaxed);
+            slot[1].store(0, Ordering::Relaxed);
+        }
+
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    /// Marks the entries stored so far as older, to be replaced first. Called at the start of every search.
+    pub fn new_search(&self) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.generation.store((generation + 1) % GENERATIONS, Ordering::Relaxed);
+    }
+
+    #[cfg(test)]
+    pub(super) fn generation(&self) -> u8 {
+        return self.generation.load(Ordering::Relaxed);
+    }
+
+    /// The entry of a position, with mate scores adjusted to the ply it is probed at.
+    pub fn probe(&self, key: u64, ply: usize) -> Option<Entry> {
+        let slot = self.slot(key);
+        let data = slot[1].load(Ordering::Relaxed);
+
+        if slot[0].load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+
+        let mut entry = Entry::from_bits(data)?;
+        entry.score = score_from_table(entry.score, ply);
+
+        return Some(entry);
+    }
+
+    /// Stores what the search found about a position, at the given ply from the root.
+    pub fn store(&self, key: u64, raw_move: Option<u16>, score: i32, depth: u32, bound: Bound, ply: usize) {
+        let slot = self.slot(key);
+        let generation = self.generation.load(Ordering::Relaxed);
+
+        let previous_data = slot[1].load(Ordering::Relaxed);
+        let previous = match slot[0].load(Ordering::Relaxed) ^ previous_data {
+            previous_key if previous_key == key => Entry::from_bits(previous_data).map(|entry| (true, entry)),
+            _ => Entry::from_bits(previous_data).map(|entry| (false, entry)),
+        };
+
+        let raw_move = match previous {
+            // Keeps the movement of the position when the new search did not find one.
+            Some((true, entry)) if raw_move.is_none() => entry.raw_move,
+            Some((false, entry)) if entry.generation == generation && entry.depth > depth + 2 && bound != Bound::Exact => return,
+            _ => raw_move,
+        };
+
+        let data = Entry {
+            raw_move,
+            score: score_to_table(score, ply),
+            depth,
+            bound,
+            generation,
+        }.to_bits();
+
+        slot[0].store(key ^ data, Ordering::Relaxed);
+        slot[1].store(data, Ordering::Relaxed);
+    }
+
+    /// How full the table is, in permille, from the entries of the current search among the first thousand.
+    pub fn hashfull(&self) -> u32 {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let sample = self.slots.len().min(1000);
+
+        let used = self.slots[..sample].iter().filter(|slot| {
+            match Entry::from_bits(slot[1].load(Ordering::Relaxed)) {
+                Some(entry) => entry.generation == generation,
+                None => false,
+            }
+        }).count();
+
+        return (used * 1000 / sample) as u32;
+    }
+
+    fn slot(&self, key: u64) -> &[AtomicU64; 2] {
+        return &self.slots[key as usize & (self.slots.len() - 1)];
+    }
+}
+
+/// Mates are stored as the distance from the stored position rather than from the root, so they stay right when the
+/// position is reached at another ply.
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    return match score {
+        score if score >= MATE_BOUND => score + ply as i32,
+        score if score <= -MATE_BOUND => score - ply as i32,
+        score => score,
+    };
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    return match score {
+        score if score >= MATE_BOUND => score - ply as i32,
+        score if score <= -MATE_BOUND => score + ply as i32,
+        score => score,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::search::MATE;
+
+    #[test]
+    fn packs_entries(){
+        let table = TranspositionTable::new(3);
+        assert_eq!(table.len(), 131072);
+        assert_eq!(TranspositionTable::new(0).len(), 1);
+
+        table.store(42, Some(0x7fff), -1234, 12, Bound::Upper, 3);
+
+        let entry = table.probe(42, 3).unwrap();
+        assert_eq!((entry.raw_move(), entry.score(), entry.depth(), entry.bound()), (Some(0x7fff), -1234, 12, Bound::Upper));
+
+        // Mated five plies from a position stored at ply 3, probed at ply 7.
+        table.store(43, None, -MATE + 8, 6, Bound::Exact, 3);
+        assert_eq!(table.probe(43, 7).unwrap().score(), -MATE + 12);
+        assert_eq!(table.probe(43, 7).unwrap().raw_move(), None);
+    }
+
+    #[test]
+    fn replaces_entries(){
+        let table = TranspositionTable::new(1);
+        let other = 7 + table.len() as u64;
+
+        table.store(7, Some(1), 10, 8, Bound::Lower, 0);
+
+        // A shallow entry of another position does not replace a deep one of the current search.
+        table.store(other, Some(2), 20, 2, Bound::Lower, 0);
+        assert!(table.probe(other, 0).is_none());
+
+        // The same position keeps its movement.
+        table.store(7, None, 15, 3, Bound::Upper, 0);
+        assert_eq!(table.probe(7, 0).unwrap().raw_move(), Some(1));
+
+        table.store(other, Some(2), 20, 2, Bound::Lower, 0);
+        assert_eq!(table.probe(other, 0).unwrap().score(), 20);
+        assert!(table.probe(7, 0).is_none());
+
+        table.store(7, Some(1), 10, 8, Bound::Lower, 0);
+        table.new_search();
+        table.store(other, Some(2), 20, 2, Bound::Lower, 0);
+        assert!(table.probe(other, 0).is_some());
+    }
+
+    #[test]
+    fn counts_the_entries_of_the_search(){
+        let table = TranspositionTable::new(1);
+        assert_eq!(table.hashfull(), 0);
+
+        for key in 0..500 {
+            table.store(key, None, 0, 1, Bound::Exact, 0);
+        }
+
+        assert_eq!(table.hashfull(), 500);
+
+        table.new_search();
+        assert_eq!(table.hashfull(), 0);
+
+        table.clear();
+        assert!(table.probe(1, 0).is_none());
+    }
+}
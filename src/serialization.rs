@@ -0,0 +1,331 @@
+//! Serialize and Deserialize implementations, enabled by the `serde` feature.
+//!
+//! Pieces are written by kind and color rather than as trait objects, so any self-describing format such as JSON
+//! or MessagePack can store them. The field names below are part of the public format and will not change.
+//!
+//! | Type | Format |
+//! | --- | --- |
+//! | `Color` | `"White"` or `"Black"` |
+//! | `Position` | The tile in algebraic notation, like `"e4"` |
+//! | `CastleRights` | `"KingSide"`, `"QueenSide"`, `"Both"` or `"None"` |
+//! | Piece | `{"kind": "Knight", "color": "White"}`, the kind being `Pawn`, `Knight`, `Bishop`, `Rook`, `Queen` or `King` |
+//! | `Movement` | `{"type": "Move", "piece": …, "from": "g1", "to": "f3", "promotion": null}`, the type being `Move` or `Capture`, or `{"type": "CastleKingSide", "color": "White"}` and `CastleQueenSide` |
+//! | `Board` | `{"pieces": [{"position": "e1", "kind": "King", "color": "White"}, …], "white_castle_rights": …, "black_castle_rights": …, "en_passant": null}` |
+//! | `Game` | `{"start_fen": …, "tags": [["Event", "…"]], "movements": […], "annotations": […], "board": …, "turn": …, "halfmove_clock": 0, "fullmove_number": 1}` |
+//!
+//! A deserialized game replays its movements from `start_fen`, so illegal movements are rejected. Its `board`,
+//! `turn` and clocks must match the position the movements lead to.
+//!
+//! ## Examples
+//!
+//! ```
+//! use chess::{game::Game, parser::pgn::from_movetext};
+//!
+//! let game = from_movetext("1.e4 e5 2.Nf3").unwrap();
+//!
+//! let json = serde_json::to_string(&game).unwrap();
+//! let restored : Game = serde_json::from_str(&json).unwrap();
+//!
+//! assert_eq!(restored.to_fen(), game.to_fen());
+//! ```
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+use crate::{board::{Board, position::Position}, color::Color, game::{Game, annotation::Annotation, castle_rights::CastleRights, movement::Movement}, piece::{Piece, piece_factory, pieces::king::King}};
+
+impl Serialize for Position {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_str(&self.to_string());
+    }
+}
+
+impl<'de> Deserialize<'de> for Position {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Position, D::Error> {
+        let position = String::deserialize(deserializer)?;
+
+        return Position::from_string(&position).map_err(D::Error::custom);
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+enum PieceKind{
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+impl PieceKind {
+    fn from_piece(piece: &dyn Piece) -> PieceKind {
+        return match piece.prefix() {
+            "N" => PieceKind::Knight,
+            "B" => PieceKind::Bishop,
+            "R" => PieceKind::Rook,
+            "Q" => PieceKind::Queen,
+            "K" => PieceKind::King,
+            _ => PieceKind::Pawn,
+        }
+    }
+
+    fn to_piece(self, color: Color) -> Box<dyn Piece> {
+        let prefix = match self {
+            PieceKind::Pawn => "P",
+            PieceKind::Knight => "N",
+            PieceKind::Bishop => "B",
+            PieceKind::Rook => "R",
+            PieceKind::Queen => "Q",
+            PieceKind::King => "K",
+        };
+
+        return piece_factory(prefix, color);
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct PieceData{
+    kind: PieceKind,
+    color: Color,
+}
+
+impl PieceData {
+    fn from_piece(piece: &dyn Piece) -> PieceData {
+        PieceData {
+            kind: PieceKind::from_piece(piece),
+            color: *piece.color(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum MovementData{
+    CastleKingSide{
+        color: Color,
+    },
+    CastleQueenSide{
+        color: Color,
+    },
+    Move{
+        piece: PieceData,
+        from: Position,
+        to: Position,
+        promotion: Option<PieceKind>,
+    },
+    Capture{
+        piece: PieceData,
+        from: Position,
+        to: Position,
+        promotion: Option<PieceKind>,
+    },
+}
+
+impl Serialize for Movement {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = match self {
+            Movement::CastleKingSide(king) => MovementData::CastleKingSide { color: *king.color() },
+            Movement::CastleQueenSide(king) => MovementData::CastleQueenSide { color: *king.color() },
+            Movement::Move(piece, from, to, promotion) => MovementData::Move {
+                piece: PieceData::from_piece(piece.as_ref()),
+                from: *from,
+                to: *to,
+                promotion: promotion.as_deref().map(PieceKind::from_piece),
+            },
+            Movement::Capture(piece, from, to, promotion) => MovementData::Capture {
+                piece: PieceData::from_piece(piece.as_ref()),
+                from: *from,
+                to: *to,
+                promotion: promotion.as_deref().map(PieceKind::from_piece),
+            },
+        };
+
+        return data.serialize(serializer);
+    }
+}
+
+impl<'de> Deserialize<'de> for Movement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Movement, D::Error> {
+        let movement = match MovementData::deserialize(deserializer)? {
+            MovementData::CastleKingSide { color } => Some(Movement::CastleKingSide(Box::new(King::new(color)))),
+            MovementData::CastleQueenSide { color } => Some(Movement::CastleQueenSide(Box::new(King::new(color)))),
+            MovementData::Move { piece, from, to, promotion } => {
+                Movement::new_move(piece.kind.to_piece(piece.color), from, to, promotion.map(|promotion| promotion.to_piece(piece.color)))
+            },
+            MovementData::Capture { piece, from, to, promotion } => {
+                Movement::new_capture(piece.kind.to_piece(piece.color), from, to, promotion.map(|promotion| promotion.to_piece(piece.color)))
+            },
+        };
+
+        return movement.ok_or_else(|| D::Error::custom("Invalid movement"));
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct PlacedPiece{
+    position: Position,
+    kind: PieceKind,
+    color: Color,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct BoardData{
+    pieces: Vec<PlacedPiece>,
+    white_castle_rights: CastleRights,
+    black_castle_rights: CastleRights,
+    en_passant: Option<Position>,
+}
+
+impl BoardData {
+    fn from_board(board: &Board) -> BoardData {
+        let pieces = board.pieces().into_iter().map(|(position, piece)| PlacedPiece {
+            position,
+            kind: PieceKind::from_piece(piece),
+            color: *piece.color(),
+        }).collect();
+
+        BoardData {
+            pieces,
+            white_castle_rights: *board.get_castle_rights(&Color::White),
+            black_castle_rights: *board.get_castle_rights(&Color::Black),
+            en_passant: board.en_passant().cloned(),
+        }
+    }
+
+    fn to_board(&self) -> Result<Board, String> {
+        let mut board = Board::new(self.white_castle_rights, self.black_castle_rights);
+
+        for piece in &self.pieces {
+            board.set_piece_at(piece.position, piece.kind.to_piece(piece.color));
+        }
+
+        board.set_en_passant(self.en_passant)?;
+
+        return Ok(board);
+    }
+}
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return BoardData::from_board(self).serialize(serializer);
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Board, D::Error> {
+        return BoardData::deserialize(deserializer)?.to_board().map_err(D::Error::custom);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct GameData{
+    start_fen: String,
+    tags: Vec<(String, String)>,
+    movements: Vec<Movement>,
+    #[serde(default)]
+    annotations: Vec<Annotation>,
+    board: Board,
+    turn: Color,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+}
+
+impl Serialize for Game {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = GameData {
+            start_fen: self.start_fen().to_string(),
+            tags: self.tags().clone(),
+            movements: self.movements().clone(),
+            annotations: self.annotations().clone(),
+            board: self.board().clone(),
+            turn: *self.turn(),
+            halfmove_clock: self.halfmove_clock(),
+            fullmove_number: self.fullmove_number(),
+        };
+
+        return data.serialize(serializer);
+    }
+}
+
+impl<'de> Deserialize<'de> for Game {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Game, D::Error> {
+        let data = GameData::deserialize(deserializer)?;
+
+        if data.annotations.len() > data.movements.len() {
+            return Err(D::Error::custom("There are more annotations than movements"));
+        }
+
+        let mut game = Game::from_fen(&data.start_fen).map_err(D::Error::custom)?;
+
+        for (index, movement) in data.movements.into_iter().enumerate() {
+            game.play(movement).map_err(|e| D::Error::custom(format!("Movement {}: {}", index + 1, e)))?;
+
+            if let Some(annotation) = data.annotations.get(index) {
+                game.annotate(annotation.clone()).map_err(D::Error::custom)?;
+            }
+        }
+
+        for (name, value) in &data.tags {
+            game.set_tag(name, value);
+        }
+
+        let matches = BoardData::from_board(game.board()) == BoardData::from_board(&data.board)
+            && game.turn() == &data.turn
+            && game.halfmove_clock() == data.halfmove_clock
+            && game.fullmove_number() == data.fullmove_number;
+
+        if !matches {
+            return Err(D::Error::custom("The board, turn and clocks do not match the position the movements lead to"));
+        }
+
+        return Ok(game);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::pgn::from_movetext;
+
+    use super::*;
+
+    #[test]
+    fn writes_stable_field_names(){
+        let game = Game::new_classical();
+        let movement = Movement::from_uci("g1f3", &game).unwrap();
+
+        assert_eq!(serde_json::to_string(&movement).unwrap(), r#"{"type":"Move","piece":{"kind":"Knight","color":"White"},"from":"g1","to":"f3","promotion":null}"#);
+
+        let castles = Movement::CastleQueenSide(Box::new(King::new(Color::Black)));
+        assert_eq!(serde_json::to_string(&castles).unwrap(), r#"{"type":"CastleQueenSide","color":"Black"}"#);
+
+        let board = Game::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap().board().clone();
+        assert_eq!(serde_json::to_string(&board).unwrap(), r#"{"pieces":[{"position":"e1","kind":"King","color":"White"},{"position":"e8","kind":"King","color":"Black"},{"position":"h1","kind":"Rook","color":"White"}],"white_castle_rights":"KingSide","black_castle_rights":"None","en_passant":null}"#);
+    }
+
+    #[test]
+    fn round_trips_a_game(){
+        let mut game = from_movetext("1.e4 {[%clk 0:05:00]} Nf6 2.e5 d5 3.exd6 Qxd6 4.Nf3 Qxd2+ 5.Bxd2 Bg4 6.Be2 Nc6 7.O-O O-O-O *").unwrap();
+        game.set_tag("White", "Alice");
+
+        let json = serde_json::to_string(&game).unwrap();
+        let restored : Game = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.to_fen(), game.to_fen());
+        assert_eq!(restored.to_pgn().unwrap(), game.to_pgn().unwrap());
+        assert_eq!(restored.annotations(), game.annotations());
+    }
+
+    #[test]
+    fn rejects_inconsistent_games(){
+        let game = from_movetext("1.e4 e5").unwrap();
+        let json = serde_json::to_string(&game).unwrap();
+
+        let tampered = json.replace(r#""turn":"White""#, r#""turn":"Black""#);
+        assert!(serde_json::from_str::<Game>(&tampered).is_err());
+
+        let illegal = json.replace(r#""to":"e5""#, r#""to":"e4""#);
+        assert!(serde_json::from_str::<Game>(&illegal).is_err());
+
+        assert!(serde_json::from_str::<Position>(r#""i9""#).is_err());
+    }
+}
@@ -0,0 +1,528 @@
+use std::{collections::HashMap, fs, path::PathBuf, sync::{Arc, Mutex}};
+
+use crate::{color::Color, game::{Game, castle_rights::CastleRights, movement::Movement}, piece::pieces::pawn::Pawn};
+
+use self::table::{Probe, Table, TableKind, TablePosition};
+
+mod table;
+
+/// Outcome of a position for the side to move, with perfect play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    /// A loss which is a draw under the fifty-move rule.
+    BlessedLoss,
+    Draw,
+    /// A win which is a draw under the fifty-move rule.
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_score(score: i32) -> Wdl {
+        match score {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            1 => Wdl::CursedWin,
+            2 => Wdl::Win,
+            _ => Wdl::Draw,
+        }
+    }
+}
+
+/// Syzygy endgame tablebases, probed from the `.rtbw` and `.rtbz` files of a directory.
+///
+/// Tables are read when a position first needs them and kept in memory afterwards. Positions with castle rights are
+/// never in the tables.
+///
+/// ## Examples
+///
+/// ```no_run
+/// use chess::{game::Game, tablebase::{Tablebase, Wdl}};
+///
+/// let tablebase = Tablebase::open("/path/to/syzygy").unwrap();
+/// let game = Game::from_fen("8/8/8/4k3/8/8/8/KQ6 w - - 0 1").unwrap();
+///
+/// assert_eq!(tablebase.probe_wdl(&game).unwrap(), Wdl::Win);
+/// println!("{}", tablebase.best_move(&game).unwrap().unwrap());
+/// ```
+pub struct Tablebase {
+    directory: PathBuf,
+    max_pieces: usize,
+    tables: Mutex<HashMap<String, Option<Arc<Table>>>>,
+}
+
+impl Tablebase {
+    pub fn open(directory: &str) -> Result<Tablebase, String> {
+        let entries = fs::read_dir(directory).map_err(|e| format!("Cannot read tablebase directory {}: {}", directory, e))?;
+
+        let mut max_pieces = 2;
+
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if let Some(material) = name.strip_suffix(".rtbw") {
+                max_pieces = max_pieces.max(material.chars().filter(|c| *c != 'v').count());
+            }
+        }
+
+        return Ok(Tablebase {
+            directory: PathBuf::from(directory),
+            max_pieces,
+            tables: Mutex::new(HashMap::new()),
+        });
+    }
+
+    /// Most pieces of the WDL tables found in the directory.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// The outcome of the position for the side to move.
+    pub fn probe_wdl(&self, game: &Game) -> Result<Wdl, String> {
+        self.check(game)?;
+
+        return Ok(Wdl::from_score(self.search(game, false)?.0));
+    }
+
+    /// The distance to zero of the position: how many plies until a capture or a Pawn move, when the side to move
+    /// wins, as a negative number when it loses and 0 for a draw.
+    ///
+    /// A win or a loss beyond 100 plies is a cursed win or a blessed loss. The distance may be one ply more than the
+    /// actual one, except right after a capture or a Pawn move.
+    pub fn probe_dtz(&self, game: &Game) -> Result<i32, String> {
+        self.check(game)?;
+
+        return self.dtz(game);
+    }
+
+    /// The legal movement which keeps the best outcome and, among those, reaches a capture or a Pawn move the
+    /// fastest when winning, or the slowest when losing. Returns None when there is no legal movement.
+    pub fn best_move(&self, game: &Game) -> Result<Option<Movement>, String> {
+        self.check(game)?;
+
+        let clock = game.halfmove_clock() as i32;
+        let mut best : Option<(Movement, (i32, i32))> = None;
+
+        for movement in game.legal_movements() {
+            let mut next = game.clone();
+            next.play(movement.clone())?;
+
+            let dtz = if next.is_checkmate() {
+                1
+            } else if next.halfmove_clock() == 0 {
+                dtz_before_zeroing(-self.search(&next, false)?.0)
+            } else {
+                let dtz = -self.dtz(&next)?;
+                dtz + dtz.signum()
+            };
+
+            // Certain wins rank the same, and so do losses unless the fifty-move rule can save them.
+            let rank = match dtz {
+                dtz if dtz > 0 && dtz + clock <= 99 => 1000,
+                dtz if dtz > 0 => 1000 - (dtz + clock),
+                dtz if dtz < 0 && -dtz * 2 + clock < 100 => -1000,
+                dtz if dtz < 0 => -1000 + (-dtz + clock),
+                _ => 0,
+            };
+
+            let key = (rank, -dtz);
+
+            if best.as_ref().is_none_or(|(_, best_key)| key > *best_key) {
+                best = Some((movement, key));
+            }
+        }
+
+        return Ok(best.map(|(movement, _)| movement));
+    }
+
+    fn check(&self, game: &Game) -> Result<(), String> {
+        let pieces = game.board().pieces().len();
+
+        if pieces > self.max_pieces {
+            return Err(format!("Cannot probe a position with {} pieces! The tablebase has up to {}", pieces, self.max_pieces));
+        }
+
+        for color in [Color::White, Color::Black] {
+            if game.board().get_castle_rights(&color) != &CastleRights::None {
+                return Err(String::from("Cannot probe a position with castle rights"));
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// The outcome of the position, looking at the captures (and the Pawn moves for DTZ tables) the tables do not
+    /// store reliably. Also tells whether the best movement is one of those.
+    fn search(&self, game: &Game, with_pawn_moves: bool) -> Result<(i32, bool), String> {
+        let movements = game.legal_movements();
+        let total = movements.len();
+
+        let mut searched = 0;
+        let mut best = -2;
+
+        for movement in movements {
+            let capture = matches!(movement, Movement::Capture(..));
+
+            if !capture && (!with_pawn_moves || movement.piece().prefix() != Pawn::prefix()) {
+                continue;
+            }
+
+            searched += 1;
+
+            let mut next = game.clone();
+            next.play(movement)?;
+
+            let value = -self.search(&next, false)?.0;
+
+            if value > best {
+                best = value;
+
+                if value >= 2 {
+                    return Ok((value, true));
+                }
+            }
+        }
+
+        // When every movement was searched, the tables are not needed, and may even be wrong with en passant.
+        let every_movement = searched > 0 && searched == total;
+
+        let value = match every_movement {
+            true => best,
+            false => match self.probe_table(game, TableKind::Wdl, 0)? {
+                Probe::Value(value) => value,
+                Probe::ChangeSide => 0,
+            },
+        };
+
+        if best >= value {
+            return Ok((best, best > 0 || every_movement));
+        }
+
+        return Ok((value, false));
+    }
+
+    fn dtz(&self, game: &Game) -> Result<i32, String> {
+        let (wdl, zeroing) = self.search(game, true)?;
+
+        if wdl == 0 {
+            return Ok(0);
+        }
+
+        if zeroing {
+            return Ok(dtz_before_zeroing(wdl));
+        }
+
+        match self.probe_table(game, TableKind::Dtz, wdl)? {
+            Probe::Value(dtz) => {
+                let cursed = match wdl {
+                    1 | -1 => 100,
+                    _ => 0,
+                };
+
+                return Ok((dtz + cursed) * wdl.signum());
+            },
+            Probe::ChangeSide => (),
+        }
+
+        // The table stores the other side to move, so the best movement is looked for one ply deeper.
+        let mut min_dtz = i32::MAX;
+
+        for movement in game.legal_movements() {
+            let zeroing = matches!(movement, Movement::Capture(..)) || movement.piece().prefix() == Pawn::prefix();
+
+            let mut next = game.clone();
+            next.play(movement)?;
+
+            let mut dtz = match zeroing {
+                true => -dtz_before_zeroing(self.search(&next, false)?.0),
+                false => -self.dtz(&next)?,
+            };
+
+            if dtz == 1 && next.is_checkmate() {
+                min_dtz = 1;
+            }
+
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+        }
+
+        return Ok(match min_dtz {
+            i32::MAX => -1,
+            min_dtz => min_dtz,
+        });
+    }
+
+    fn probe_table(&self, game: &Game, kind: TableKind, wdl: i32) -> Result<Probe, String> {
+        let pieces = game.board().pieces();
+
+        // Two Kings.
+        if pieces.len() == 2 {
+            return Ok(Probe::Value(0));
+        }
+
+        let mut position = TablePosition {
+            pieces: Vec::new(),
+            white_to_move: game.turn() == &Color::White,
+        };
+
+        let mut white = String::new();
+        let mut black = String::new();
+
+        for prefix in ["K", "Q", "R", "B", "N", "P"] {
+            for (_, piece) in &pieces {
+                if piece.prefix() == prefix {
+                    match piece.color() {
+                        Color::White => white.push_str(prefix),
+                        Color::Black => black.push_str(prefix),
+                    }
+                }
+            }
+        }
+
+        for (position_on_board, piece) in &pieces {
+            let code = match piece.prefix() {
+                "P" => 1,
+                "N" => 2,
+                "B" => 3,
+                "R" => 4,
+                "Q" => 5,
+                _ => 6,
+            } + match piece.color() {
+                Color::White => 0,
+                Color::Black => 8,
+            };
+
+            let square = (position_on_board.rank() as usize - 1) * 8 + position_on_board.file() as usize - 1;
+
+            position.pieces.push((square, code));
+        }
+
+        position.pieces.sort();
+
+        // Tables are named with the stronger side first, which is not always White.
+        for (material, black_stronger) in [(format!("{}v{}", white, black), false), (format!("{}v{}", black, white), white != black)] {
+            if let Some(table) = self.table(&material, kind)? {
+                return table.probe(&position, black_stronger, wdl);
+            }
+        }
+
+        return Err(format!("Missing table {}v{}.{}", white, black, kind.extension()));
+    }
+
+    fn table(&self, material: &str, kind: TableKind) -> Result<Option<Arc<Table>>, String> {
+        let name = format!("{}.{}", material, kind.extension());
+
+        let mut tables = self.tables.lock().map_err(|_| String::from("Tablebase cache is poisoned"))?;
+
+        if let Some(table) = tables.get(&name) {
+            return Ok(table.clone());
+        }
+
+        let path = self.directory.join(&name);
+
+        let table = match path.exists() {
+            true => Some(Arc::new(Table::open(&path, kind, material)?)),
+            false => None,
+        };
+
+        tables.insert(name, table.clone());
+
+        return Ok(table);
+    }
+}
+
+/// DTZ tables do not store the distance of positions whose best movement is a capture or a Pawn move, but it is
+/// known from the outcome.
+fn dtz_before_zeroing(wdl: i32) -> i32 {
+    match wdl {
+        2 => 1,
+        1 => 101,
+        -1 => -101,
+        -2 => -1,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    const WDL_MAGIC : [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+    const DTZ_MAGIC : [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+    /// A directory with KQvK tables storing a single value: a win for the side with the Queen to move, a loss for
+    /// the other side, and a DTZ of 9 plies for White to move.
+    fn single_value_tables(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("chess-syzygy-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        // Split flag, the order byte, the King, Queen and King of both sides, a padding byte and then each side is a
+        // single value.
+        let mut wdl = WDL_MAGIC.to_vec();
+        wdl.extend_from_slice(&[0x01, 0x00, 0x66, 0x55, 0xEE, 0x00, 0x80, 4, 0x80, 0]);
+        fs::write(directory.join("KQvK.rtbw"), wdl).unwrap();
+
+        // Values are stored in moves for White to move only.
+        let mut dtz = DTZ_MAGIC.to_vec();
+        dtz.extend_from_slice(&[0x01, 0x00, 0x06, 0x05, 0x0E, 0x00, 0x80, 4]);
+        fs::write(directory.join("KQvK.rtbz"), dtz).unwrap();
+
+        return directory;
+    }
+
+    fn game(fen: &str) -> Game {
+        return Game::from_fen(fen).unwrap();
+    }
+
+    #[test]
+    fn probes_wdl_and_dtz(){
+        let directory = single_value_tables("probe");
+        let tablebase = Tablebase::open(directory.to_str().unwrap()).unwrap();
+
+        assert_eq!(tablebase.max_pieces(), 3);
+
+        assert_eq!(tablebase.probe_wdl(&game("8/8/8/4k3/8/8/8/KQ6 w - - 0 1")).unwrap(), Wdl::Win);
+        assert_eq!(tablebase.probe_wdl(&game("8/8/8/4k3/8/8/8/KQ6 b - - 0 1")).unwrap(), Wdl::Loss);
+
+        // The Queen is the stronger side's, whatever its color.
+        assert_eq!(tablebase.probe_wdl(&game("8/8/8/4K3/8/8/8/kq6 b - - 0 1")).unwrap(), Wdl::Win);
+
+        // Taking the Queen draws, whatever the table says.
+        assert_eq!(tablebase.probe_wdl(&game("8/8/8/8/8/8/1kQ5/4K3 b - - 0 1")).unwrap(), Wdl::Draw);
+
+        assert_eq!(tablebase.probe_dtz(&game("8/8/8/4k3/8/8/8/KQ6 w - - 0 1")).unwrap(), 9);
+        assert_eq!(tablebase.probe_dtz(&game("8/8/8/4k3/8/8/8/KQ6 b - - 0 1")).unwrap(), -10);
+        assert_eq!(tablebase.probe_dtz(&game("8/8/8/8/8/8/1kQ5/4K3 b - - 0 1")).unwrap(), 0);
+
+        assert!(tablebase.probe_wdl(&game("8/8/8/4k3/8/8/8/KR6 w - - 0 1")).is_err());
+        assert!(tablebase.probe_wdl(&Game::new_classical()).is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    /// A KQvK WDL table where White to move wins, except with the White King on b1, the Queen on d5 and the Black
+    /// King on h8, and Black to move is a draw.
+    fn compressed_table() -> Vec<u8> {
+        let mut wdl = WDL_MAGIC.to_vec();
+        wdl.extend_from_slice(&[0x01, 0x00, 0x66, 0x55, 0xEE, 0x00]);
+
+        // White to move: blocks of 32 bytes and a sparse entry every 256 values, for the 31332 indices. Both symbols
+        // have one bit: 0 for a win and 1 for a loss.
+        wdl.extend_from_slice(&[0x00, 5, 8, 0, 123, 0, 0, 0, 1, 1, 0, 0, 2, 0, 4, 0xF0, 0xFF, 0, 0xF0, 0xFF]);
+
+        // Black to move is a single value.
+        wdl.extend_from_slice(&[0x80, 0]);
+
+        for block in 0..123u32 {
+            wdl.extend_from_slice(&block.to_le_bytes());
+            wdl.extend_from_slice(&128u16.to_le_bytes());
+        }
+
+        for _ in 0..123 {
+            wdl.extend_from_slice(&255u16.to_le_bytes());
+        }
+
+        wdl.resize(1024, 0);
+
+        let mut data = vec![0u8; 123 * 32];
+        // White King on b1, Queen on d5 and Black King on h8: (0 * 63 + 34) * 62 + 61.
+        data[2169 / 8] |= 0x80 >> (2169 % 8);
+        wdl.extend_from_slice(&data);
+
+        return wdl;
+    }
+
+    #[test]
+    fn decodes_compressed_tables(){
+        let directory = env::temp_dir().join(format!("chess-syzygy-compressed-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        fs::write(directory.join("KQvK.rtbw"), compressed_table()).unwrap();
+
+        let tablebase = Tablebase::open(directory.to_str().unwrap()).unwrap();
+
+        assert_eq!(tablebase.probe_wdl(&game("7k/8/8/3Q4/8/8/8/1K6 w - - 0 1")).unwrap(), Wdl::Loss);
+        assert_eq!(tablebase.probe_wdl(&game("7k/8/8/2Q5/8/8/8/1K6 w - - 0 1")).unwrap(), Wdl::Win);
+
+        // The same position mirrored from the h file.
+        assert_eq!(tablebase.probe_wdl(&game("k7/8/8/4Q3/8/8/8/6K1 w - - 0 1")).unwrap(), Wdl::Loss);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn rejects_corrupt_tables(){
+        let directory = env::temp_dir().join(format!("chess-syzygy-corrupt-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("KQvK.rtbw");
+
+        let corrupt = |changes: &[(usize, u8)]| {
+            let mut wdl = compressed_table();
+            changes.iter().for_each(|(offset, byte)| wdl[*offset] = *byte);
+            return wdl;
+        };
+
+        // The block size and span shifts, then the longest and shortest symbol lengths.
+        let tables = [
+            corrupt(&[(11, 200)]),
+            corrupt(&[(12, 64)]),
+            corrupt(&[(18, 200), (19, 200)]),
+            corrupt(&[(18, 1), (19, 100)]),
+            compressed_table()[..40].to_vec(),
+            compressed_table()[..1100].to_vec(),
+        ];
+
+        for wdl in tables {
+            fs::write(&path, wdl).unwrap();
+            assert!(Table::open(&path, TableKind::Wdl, "KQvK").is_err());
+        }
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    /// Checks real tables from the Syzygy generator, which are not shipped with the sources, so this only runs when
+    /// `SYZYGY_PATH` points to a directory holding KQvK.rtbw and KQvK.rtbz.
+    #[test]
+    fn probes_generated_tables(){
+        let directory = match env::var("SYZYGY_PATH") {
+            Ok(directory) => directory,
+            Err(_) => return,
+        };
+
+        let tablebase = Tablebase::open(&directory).unwrap();
+
+        assert_eq!(tablebase.probe_wdl(&game("8/8/8/4k3/8/8/8/KQ6 w - - 0 1")).unwrap(), Wdl::Win);
+        assert_eq!(tablebase.probe_wdl(&game("8/8/8/4k3/8/8/8/KQ6 b - - 0 1")).unwrap(), Wdl::Loss);
+        assert_eq!(tablebase.probe_wdl(&game("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1")).unwrap(), Wdl::Draw);
+        assert_eq!(tablebase.probe_wdl(&game("8/8/8/8/8/8/1kQ5/4K3 b - - 0 1")).unwrap(), Wdl::Draw);
+
+        assert_eq!(tablebase.probe_dtz(&game("k7/8/1K6/8/8/8/8/7Q w - - 0 1")).unwrap(), 1);
+        assert!(tablebase.probe_dtz(&game("8/8/8/4k3/8/8/8/KQ6 b - - 0 1")).unwrap() < 0);
+    }
+
+    #[test]
+    fn picks_the_best_move(){
+        let directory = single_value_tables("best");
+        let tablebase = Tablebase::open(directory.to_str().unwrap()).unwrap();
+
+        let game = game("k7/8/1K6/8/8/8/8/7Q w - - 0 1");
+        let movement = tablebase.best_move(&game).unwrap().unwrap();
+
+        let mut next = game.clone();
+        next.play(movement).unwrap();
+        assert!(next.is_checkmate());
+
+        // Two Kings are a draw without any table.
+        assert_eq!(tablebase.probe_wdl(&self::game("8/8/8/4k3/8/8/8/K7 w - - 0 1")).unwrap(), Wdl::Draw);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
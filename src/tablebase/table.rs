@@ -0,0 +1,1035 @@
+// The table layout, the index encoding and the decompression are ported from Fathom's src/tbcore.c and
+// src/tbprobe.c (https://github.com/jdart1/Fathom), which build on the original Syzygy probing code by Ronald de Man.
+// Fathom is distributed under the MIT license:
+//
+//   Copyright (c) Ronald de Man, basil00 and Jon Dart
+//
+//   Permission is hereby granted, free of charge, to any person obtaining a copy of this software and associated
+//   documentation files (the "Software"), to deal in the Software without restriction, including without limitation
+//   the rights to use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of the Software, and
+//   to permit persons to whom the Software is furnished to do so, subject to the following conditions:
+//
+//   The above copyright notice and this permission notice shall be included in all copies or substantial portions
+//   of the Software.
+//
+//   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO
+//   THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//   AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF
+//   CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS
+//   IN THE SOFTWARE.
+
+use std::{fs, path::Path, sync::OnceLock};
+
+/// First bytes of a WDL table file.
+const WDL_MAGIC : [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+/// First bytes of a DTZ table file.
+const DTZ_MAGIC : [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+/// Most pieces a table can hold.
+const MAX_PIECES : usize = 7;
+
+// Flags of the compressed data of a table.
+const STM : u8 = 1;
+const MAPPED : u8 = 2;
+const WIN_PLIES : u8 = 4;
+const LOSS_PLIES : u8 = 8;
+const WIDE : u8 = 16;
+const SINGLE_VALUE : u8 = 128;
+
+/// Index of the squares of the a1-d1-d4 triangle, where the leading piece of a table without Pawns is moved to. The
+/// squares on the a1-h8 diagonal come last.
+const TRIANGLE : [usize; 64] = [
+    6, 0, 1, 2, 2, 1, 0, 6,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    6, 0, 1, 2, 2, 1, 0, 6,
+];
+
+/// Index of the squares below the a1-h8 diagonal, and of their reflection above it. The diagonal comes last.
+const LOWER : [u64; 64] = [
+    28,  0,  1,  2,  3,  4,  5,  6,
+     0, 29,  7,  8,  9, 10, 11, 12,
+     1,  7, 30, 13, 14, 15, 16, 17,
+     2,  8, 13, 31, 18, 19, 20, 21,
+     3,  9, 14, 18, 32, 22, 23, 24,
+     4, 10, 15, 19, 22, 33, 25, 26,
+     5, 11, 16, 20, 23, 25, 34, 27,
+     6, 12, 17, 21, 24, 26, 27, 35,
+];
+
+/// Index of the squares the leading Pawn can stand on, file by file from the edge and then rank by rank.
+const FLAP : [usize; 64] = [
+    0,  0,  0,  0,  0,  0,  0, 0,
+    0,  6, 12, 18, 18, 12,  6, 0,
+    1,  7, 13, 19, 19, 13,  7, 1,
+    2,  8, 14, 20, 20, 14,  8, 2,
+    3,  9, 15, 21, 21, 15,  9, 3,
+    4, 10, 16, 22, 22, 16, 10, 4,
+    5, 11, 17, 23, 23, 17, 11, 5,
+    0,  0,  0,  0,  0,  0,  0, 0,
+];
+
+/// Index of the squares the other Pawns of the leading color can stand on, from 47 on a2 down.
+const PTWIST : [usize; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    47, 35, 23, 11, 10, 22, 34, 46,
+    45, 33, 21,  9,  8, 20, 32, 44,
+    43, 31, 19,  7,  6, 18, 30, 42,
+    41, 29, 17,  5,  4, 16, 28, 40,
+    39, 27, 15,  3,  2, 14, 26, 38,
+    37, 25, 13,  1,  0, 12, 24, 36,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+/// The file of the table a leading Pawn on each file is stored in.
+const FILE_TO_FILE : [usize; 8] = [0, 1, 2, 3, 3, 2, 1, 0];
+
+/// Number of placements of the leading pieces of a table without Pawns, by encoding type: three pieces including a
+/// unique one, or the two Kings.
+const PIVFAC : [u64; 3] = [31332, 28056, 462];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+impl TableKind {
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            TableKind::Wdl => "rtbw",
+            TableKind::Dtz => "rtbz",
+        }
+    }
+}
+
+/// A position the way tables index it: pieces coded 1 to 6 from Pawn to King for White and 9 to 14 for Black, on
+/// squares numbered from 0 for a1 to 63 for h8, sorted by square.
+pub(crate) struct TablePosition {
+    pub(crate) pieces: Vec<(usize, u8)>,
+    pub(crate) white_to_move: bool,
+}
+
+pub(crate) enum Probe {
+    Value(i32),
+    /// DTZ tables only store one side to move, and this is the other one.
+    ChangeSide,
+}
+
+/// Positive above the a1-h8 diagonal, negative below it and 0 on it.
+fn offdiag(square: usize) -> i32 {
+    return (square / 8) as i32 - (square % 8) as i32;
+}
+
+/// Mirrors a square along the a1-h8 diagonal.
+fn flipdiag(square: usize) -> usize {
+    return ((square >> 3) | (square << 3)) & 63;
+}
+
+/// Number of ways to choose `k` squares out of `n`.
+fn subfactor(k: usize, n: usize) -> u64 {
+    let mut f = n as u64;
+    let mut l = 1;
+
+    for i in 1..k {
+        f *= n.saturating_sub(i) as u64;
+        l *= i as u64 + 1;
+    }
+
+    return f / l;
+}
+
+/// Index tables shared by every table, built on first use.
+struct Indices {
+    /// `binomial[k][n]` is the number of ways to choose `k + 1` squares out of `n`.
+    binomial: [[u64; 64]; 5],
+    /// Index of the leading Pawn by number of leading Pawns less one and square index.
+    pawnidx: [[u64; 24]; 5],
+    /// Number of placements of the leading Pawns by number of leading Pawns less one and file.
+    pfactor: [[u64; 4]; 5],
+    /// Index of two Kings, the first one in the a1-d1-d4 triangle.
+    kk_idx: [[u64; 64]; 10],
+}
+
+fn indices() -> &'static Indices {
+    static INDICES : OnceLock<Indices> = OnceLock::new();
+
+    return INDICES.get_or_init(|| {
+        let mut indices = Indices {
+            binomial: [[0; 64]; 5],
+            pawnidx: [[0; 24]; 5],
+            pfactor: [[0; 4]; 5],
+            kk_idx: [[0; 64]; 10],
+        };
+
+        for k in 0..5 {
+            for n in 0..64 {
+                indices.binomial[k][n] = subfactor(k + 1, n);
+            }
+        }
+
+        let mut invflap = [0; 24];
+        for square in 8..56 {
+            if square % 8 < 4 {
+                invflap[FLAP[square]] = square;
+            }
+        }
+
+        for i in 0..5 {
+            for file in 0..4 {
+                let mut s = 0;
+
+                for j in file * 6..(file + 1) * 6 {
+                    indices.pawnidx[i][j] = s;
+                    s += match i {
+                        0 => 1,
+                        _ => indices.binomial[i - 1][PTWIST[invflap[j]]],
+                    };
+                }
+
+                indices.pfactor[i][file] = s;
+            }
+        }
+
+        // The first King goes through the triangle in the order of its index. Placements with both Kings on the
+        // diagonal come after all the others.
+        let triangle : [usize; 10] = [1, 2, 3, 10, 11, 19, 0, 9, 18, 27];
+        let mut both_on_diagonal = Vec::new();
+        let mut code = 0;
+
+        for (index, first) in triangle.into_iter().enumerate() {
+            for second in 0..64 {
+                let touching = (first % 8).abs_diff(second % 8) <= 1 && (first / 8).abs_diff(second / 8) <= 1;
+
+                if touching || (offdiag(first) == 0 && offdiag(second) > 0) {
+                    continue;
+                }
+
+                if offdiag(first) == 0 && offdiag(second) == 0 {
+                    both_on_diagonal.push((index, second));
+                } else {
+                    indices.kk_idx[index][second] = code;
+                    code += 1;
+                }
+            }
+        }
+
+        for (index, second) in both_on_diagonal {
+            indices.kk_idx[index][second] = code;
+            code += 1;
+        }
+
+        return indices;
+    });
+}
+
+/// Decoding information of the compressed values of one side to move and file of a table, pointing into the table
+/// bytes.
+#[derive(Default)]
+struct PairsData {
+    flags: u8,
+    /// Blocks hold `1 << block_size` bytes.
+    block_size: u32,
+    /// The index table has an entry every `1 << idx_bits` values.
+    idx_bits: u32,
+    index_table: usize,
+    size_table: usize,
+    data: usize,
+    /// The first symbol of each code length.
+    offset: usize,
+    /// The pair of symbols each symbol stands for.
+    sympat: usize,
+    /// Shortest code length, or the value of a table storing a single value.
+    min_len: usize,
+    /// How many values each symbol expands to, less one.
+    symlen: Vec<u8>,
+    /// The lowest code of each length, padded to 64 bits.
+    base: Vec<u64>,
+    /// Bytes of the index table, the size table and the blocks.
+    sizes: [usize; 3],
+}
+
+/// How one side to move and file of a table orders its pieces and groups them into an index.
+#[derive(Default)]
+struct Encoding {
+    pieces: [u8; MAX_PIECES],
+    /// Length of each group, at the index of its first piece.
+    norm: [usize; MAX_PIECES],
+    /// Multiplier of each group index, at the index of its first piece.
+    factor: [u64; MAX_PIECES],
+    /// Number of values the table stores.
+    size: u64,
+    precomp: PairsData,
+}
+
+/// A Syzygy table file, read at once.
+pub(crate) struct Table {
+    kind: TableKind,
+    bytes: Vec<u8>,
+    num: usize,
+    symmetric: bool,
+    has_pawns: bool,
+    /// 0 when a piece besides the Kings is unique, 2 otherwise.
+    enc_type: usize,
+    /// Pawns of the leading color, then of the other one.
+    pawns: [usize; 2],
+    /// The encodings of each side to move for every file of the leading Pawn, or a single file without Pawns. DTZ
+    /// tables only store one side to move.
+    files: Vec<Vec<Encoding>>,
+    /// Start of the DTZ value maps.
+    map: usize,
+    /// Where the map of each outcome starts, for every file.
+    map_idx: Vec<[usize; 4]>,
+}
+
+impl Table {
+    /// Reads a table for the material of its name, like `KRPvKR`.
+    pub(crate) fn open(path: &Path, kind: TableKind, material: &str) -> Result<Table, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Cannot read table {}: {}", path.display(), e))?;
+
+        let magic = match kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+
+        if bytes.len() < 5 || bytes[0..4] != magic {
+            return Err(format!("Invalid table {}! Wrong magic number", path.display()));
+        }
+
+        let (white, black) = match material.split_once('v') {
+            Some(sides) => sides,
+            None => return Err(format!("Invalid table name {}", material)),
+        };
+
+        let count = |side: &str, piece: char| side.chars().filter(|c| *c == piece).count();
+
+        let has_unique_pieces = [white, black].iter().any(|side| "QRBNP".chars().any(|piece| count(side, piece) == 1));
+
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+
+        // The leading color has the fewest Pawns, since it compresses better.
+        let pawns = match black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns) {
+            true => [white_pawns, black_pawns],
+            false => [black_pawns, white_pawns],
+        };
+
+        let num = white.len() + black.len();
+
+        if num > MAX_PIECES {
+            return Err(format!("Invalid table {}! Tables have up to {} pieces", material, MAX_PIECES));
+        }
+
+        let mut table = Table {
+            kind,
+            bytes,
+            num,
+            symmetric: white == black,
+            has_pawns: white_pawns + black_pawns > 0,
+            enc_type: match has_unique_pieces {
+                true => 0,
+                false => 2,
+            },
+            pawns,
+            files: Vec::new(),
+            map: 0,
+            map_idx: Vec::new(),
+        };
+
+        table.init()?;
+
+        return Ok(table);
+    }
+
+    fn byte(&self, offset: usize) -> Result<u8, String> {
+        return self.bytes.get(offset).copied().ok_or_else(|| String::from("Truncated table"));
+    }
+
+    fn array<const N: usize>(&self, offset: usize) -> Result<[u8; N], String> {
+        let bytes = self.bytes.get(offset..offset.saturating_add(N)).ok_or_else(|| String::from("Truncated table"))?;
+
+        return Ok(bytes.try_into().unwrap());
+    }
+
+    fn u16_le(&self, offset: usize) -> Result<u16, String> {
+        return Ok(u16::from_le_bytes(self.array(offset)?));
+    }
+
+    fn u32_le(&self, offset: usize) -> Result<u32, String> {
+        return Ok(u32::from_le_bytes(self.array(offset)?));
+    }
+
+    fn u32_be(&self, offset: usize) -> Result<u32, String> {
+        return Ok(u32::from_be_bytes(self.array(offset)?));
+    }
+
+    fn u64_be(&self, offset: usize) -> Result<u64, String> {
+        return Ok(u64::from_be_bytes(self.array(offset)?));
+    }
+
+    /// Reads the pieces of every side to move and file, then where their compressed values lie.
+    fn init(&mut self) -> Result<(), String> {
+        let flags = self.byte(4)?;
+        let split = flags & 1 != 0;
+        let files = match flags & 2 != 0 {
+            true => 4,
+            false => 1,
+        };
+
+        if (files == 4) != self.has_pawns || (self.kind == TableKind::Wdl && split == self.symmetric) {
+            return Err(String::from("Invalid table! Its header does not match its material"));
+        }
+
+        let sides = match self.kind == TableKind::Wdl && split {
+            true => 2,
+            false => 1,
+        };
+
+        let order_bytes = 1 + (self.has_pawns && self.pawns[1] > 0) as usize;
+        let mut offset = 5;
+
+        for file in 0..files {
+            let mut encodings = Vec::new();
+
+            for side in 0..sides {
+                encodings.push(self.setup_pieces(offset, order_bytes, side, file)?);
+            }
+
+            self.files.push(encodings);
+            offset += self.num + order_bytes;
+        }
+
+        offset += offset & 1;
+
+        for file in 0..files {
+            for side in 0..sides {
+                let (precomp, next) = self.setup_pairs(offset, self.files[file][side].size)?;
+                self.files[file][side].precomp = precomp;
+                offset = next;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            self.map = offset;
+
+            for file in 0..files {
+                let flags = self.files[file][0].precomp.flags;
+                let mut map_idx = [0; 4];
+
+                if flags & MAPPED != 0 {
+                    for idx in map_idx.iter_mut() {
+                        match flags & WIDE {
+                            0 => {
+                                *idx = offset - self.map + 1;
+                                offset += 1 + self.byte(offset)? as usize;
+                            },
+                            _ => {
+                                offset += offset & 1;
+                                *idx = (offset - self.map) / 2 + 1;
+                                offset += 2 + 2 * self.u16_le(offset)? as usize;
+                            },
+                        }
+                    }
+                }
+
+                self.map_idx.push(map_idx);
+            }
+
+            offset += offset & 1;
+        }
+
+        for encoding in self.files.iter_mut().flatten() {
+            encoding.precomp.index_table = offset;
+            offset = offset.saturating_add(encoding.precomp.sizes[0]);
+        }
+
+        for encoding in self.files.iter_mut().flatten() {
+            encoding.precomp.size_table = offset;
+            offset = offset.saturating_add(encoding.precomp.sizes[1]);
+        }
+
+        for encoding in self.files.iter_mut().flatten() {
+            offset = offset.saturating_add(0x3F) & !0x3F;
+            encoding.precomp.data = offset;
+            offset = offset.saturating_add(encoding.precomp.sizes[2]);
+
+            if encoding.precomp.flags & SINGLE_VALUE == 0 && offset > self.bytes.len() {
+                return Err(String::from("Truncated table"));
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Reads the order of the pieces of a side to move and file, and how they are grouped into the index.
+    fn setup_pieces(&self, offset: usize, order_bytes: usize, side: usize, file: usize) -> Result<Encoding, String> {
+        let nibble = |byte: u8| match side {
+            0 => byte & 0x0F,
+            _ => byte >> 4,
+        };
+
+        let mut encoding = Encoding::default();
+
+        let order = nibble(self.byte(offset)?) as usize;
+        let order2 = match order_bytes {
+            2 => nibble(self.byte(offset + 1)?) as usize,
+            _ => 0x0F,
+        };
+
+        for i in 0..self.num {
+            encoding.pieces[i] = nibble(self.byte(offset + order_bytes + i)?);
+        }
+
+        encoding.norm = self.set_norm(&encoding.pieces);
+        (encoding.factor, encoding.size) = self.calc_factors(&encoding.norm, order, order2, file)?;
+
+        return Ok(encoding);
+    }
+
+    /// Groups the pieces: the leading group holds the three first pieces when one is unique, the two Kings
+    /// otherwise, or the leading Pawns, and every other group holds pieces of the same kind and color.
+    fn set_norm(&self, pieces: &[u8; MAX_PIECES]) -> [usize; MAX_PIECES] {
+        let mut norm = [0; MAX_PIECES];
+
+        let mut i = match self.has_pawns {
+            true => {
+                norm[0] = self.pawns[0];
+
+                if self.pawns[1] > 0 {
+                    norm[self.pawns[0]] = self.pawns[1];
+                }
+
+                self.pawns[0] + self.pawns[1]
+            },
+            false => {
+                norm[0] = match self.enc_type {
+                    0 => 3,
+                    _ => 2,
+                };
+
+                norm[0]
+            },
+        };
+
+        while i < self.num {
+            let mut j = i;
+
+            while j < self.num && pieces[j] == pieces[i] {
+                norm[i] += 1;
+                j += 1;
+            }
+
+            i += norm[i];
+        }
+
+        return norm;
+    }
+
+    /// Computes the multiplier of each group in the order the table gives them, returning the multipliers and the
+    /// number of values the table stores.
+    fn calc_factors(&self, norm: &[usize; MAX_PIECES], order: usize, order2: usize, file: usize) -> Result<([u64; MAX_PIECES], u64), String> {
+        let indices = indices();
+        let mut factor = [0; MAX_PIECES];
+
+        let mut i = norm[0];
+
+        if order2 < 0x0F {
+            i += norm[i];
+        }
+
+        let mut n = 64 - i;
+        let mut f : u64 = 1;
+        let mut k = 0;
+
+        while i < self.num || k == order || k == order2 {
+            let size = if k == order {
+                factor[0] = f;
+
+                match self.has_pawns {
+                    true => indices.pfactor[norm[0] - 1][file],
+                    false => PIVFAC[self.enc_type],
+                }
+            } else if k == order2 {
+                factor[norm[0]] = f;
+                subfactor(norm[norm[0]], 48 - norm[0])
+            } else {
+                factor[i] = f;
+                let size = subfactor(norm[i], n);
+                n -= norm[i];
+                i += norm[i];
+                size
+            };
+
+            f = f.checked_mul(size).ok_or_else(|| String::from("Invalid table! Wrong piece order"))?;
+            k += 1;
+        }
+
+        return Ok((factor, f));
+    }
+
+    /// Reads the sizes and the Huffman code of the compressed values of a side to move and file, returning them and
+    /// the offset following them.
+    fn setup_pairs(&self, offset: usize, table_size: u64) -> Result<(PairsData, usize), String> {
+        let flags = self.byte(offset)?;
+
+        if flags & SINGLE_VALUE != 0 {
+            let precomp = PairsData {
+                flags,
+                min_len: self.byte(offset + 1)? as usize,
+                ..Default::default()
+            };
+
+            return Ok((precomp, offset + 2));
+        }
+
+        let header : [u8; 10] = self.array(offset)?;
+
+        let block_size = header[1] as u32;
+        let idx_bits = header[2] as u32;
+
+        // Real tables use blocks of up to 2^20 bytes and an index entry every 2^20 values at most.
+        if block_size >= 32 || idx_bits == 0 || idx_bits >= 32 {
+            return Err(String::from("Invalid table! Wrong block size"));
+        }
+
+        let real_num_blocks = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+        let num_blocks = real_num_blocks + header[3] as usize;
+        let max_len = header[8] as usize;
+        let min_len = header[9] as usize;
+
+        // The codes are read from a 64 bit buffer refilled whenever it holds 32 bits or less.
+        if min_len == 0 || max_len < min_len || max_len > 32 {
+            return Err(String::from("Invalid table! Wrong symbol lengths"));
+        }
+
+        let h = max_len - min_len + 1;
+        let num_syms = self.u16_le(offset + 10 + 2 * h)? as usize;
+        let sympat = offset + 12 + 2 * h;
+        let next = sympat + 3 * num_syms + (num_syms & 1);
+
+        if next > self.bytes.len() {
+            return Err(String::from("Truncated table"));
+        }
+
+        let mut symlen = vec![0u8; num_syms];
+        let mut done = vec![false; num_syms];
+
+        for symbol in 0..num_syms {
+            if !done[symbol] {
+                self.calc_symlen(sympat, symbol, &mut symlen, &mut done)?;
+            }
+        }
+
+        // Longer codes have lower values, so each length starts after the codes of the longer ones.
+        let mut base = vec![0u64; h];
+
+        for i in (0..h - 1).rev() {
+            let first = self.u16_le(offset + 10 + 2 * i)? as u64;
+            let next_first = self.u16_le(offset + 10 + 2 * (i + 1))? as u64;
+
+            base[i] = base[i + 1].wrapping_add(first).wrapping_sub(next_first) / 2;
+        }
+
+        for (i, base) in base.iter_mut().enumerate() {
+            *base <<= 64 - (min_len + i);
+        }
+
+        let num_indices = table_size.div_ceil(1 << idx_bits) as usize;
+
+        let precomp = PairsData {
+            flags,
+            block_size,
+            idx_bits,
+            offset: offset + 10,
+            sympat,
+            min_len,
+            symlen,
+            base,
+            sizes: [6 * num_indices, 2 * num_blocks, real_num_blocks << block_size],
+            ..Default::default()
+        };
+
+        return Ok((precomp, next));
+    }
+
+    fn calc_symlen(&self, sympat: usize, symbol: usize, symlen: &mut Vec<u8>, done: &mut Vec<bool>) -> Result<(), String> {
+        done[symbol] = true;
+
+        let (left, right) = self.children(sympat, symbol)?;
+
+        if right == 0xFFF {
+            symlen[symbol] = 0;
+            return Ok(());
+        }
+
+        if left >= symlen.len() || right >= symlen.len() {
+            return Err(String::from("Invalid table! Wrong symbol tree"));
+        }
+
+        for child in [left, right] {
+            if !done[child] {
+                self.calc_symlen(sympat, child, symlen, done)?;
+            }
+        }
+
+        symlen[symbol] = symlen[left].wrapping_add(symlen[right]).wrapping_add(1);
+
+        return Ok(());
+    }
+
+    /// The pair of symbols a symbol stands for. A single value is stored as the left symbol.
+    fn children(&self, sympat: usize, symbol: usize) -> Result<(usize, usize), String> {
+        let w : [u8; 3] = self.array(sympat + 3 * symbol)?;
+
+        let left = ((w[1] as usize & 0x0F) << 8) | w[0] as usize;
+        let right = ((w[2] as usize) << 4) | (w[1] as usize >> 4);
+
+        return Ok((left, right));
+    }
+
+    /// Looks the position up. `black_stronger` tells the table stores the position with the colors swapped, and
+    /// `wdl` is the outcome of the position when reading a DTZ table.
+    pub(crate) fn probe(&self, position: &TablePosition, black_stronger: bool, wdl: i32) -> Result<Probe, String> {
+        // Symmetric tables only store White to move.
+        let flip = black_stronger || (self.symmetric && !position.white_to_move);
+        let cmirror = flip as u8 * 8;
+        let bside = match self.symmetric {
+            true => 0,
+            false => (flip == position.white_to_move) as usize,
+        };
+
+        // Tables without Pawns are the same upside down, so only the colors are swapped.
+        let mirror = (flip && self.has_pawns) as usize * 0x38;
+
+        let mut squares = [0usize; MAX_PIECES];
+        let mut i = 0;
+        let mut file = 0;
+
+        if self.has_pawns {
+            i = self.place(position, self.files[0][0].pieces[0] ^ cmirror, mirror, &mut squares, 0)?;
+
+            // The leading Pawn is the one nearest the edge, then the lowest.
+            for j in 1..i {
+                if FLAP[squares[0]] > FLAP[squares[j]] {
+                    squares.swap(0, j);
+                }
+            }
+
+            file = FILE_TO_FILE[squares[0] % 8];
+        }
+
+        let side = match self.kind {
+            TableKind::Wdl => bside,
+            TableKind::Dtz => 0,
+        };
+
+        let encoding = match self.files[file].get(side) {
+            Some(encoding) => encoding,
+            None => return Err(String::from("Invalid table! Missing side to move")),
+        };
+
+        if self.kind == TableKind::Dtz && (!self.symmetric || self.has_pawns) && (encoding.precomp.flags & STM) as usize != bside {
+            return Ok(Probe::ChangeSide);
+        }
+
+        while i < self.num {
+            i = self.place(position, encoding.pieces[i] ^ cmirror, mirror, &mut squares, i)?;
+        }
+
+        let index = match self.has_pawns {
+            true => self.encode_pawn(encoding, &mut squares),
+            false => self.encode_piece(encoding, &mut squares),
+        };
+
+        let value = self.decompress_pairs(&encoding.precomp, index)?;
+
+        return match self.kind {
+            TableKind::Wdl => Ok(Probe::Value(value - 2)),
+            TableKind::Dtz => Ok(Probe::Value(self.map_dtz(file, value, wdl)?)),
+        };
+    }
+
+    /// Puts the squares of the pieces of a code after the `start` ones already placed, returning how many are placed.
+    fn place(&self, position: &TablePosition, code: u8, mirror: usize, squares: &mut [usize; MAX_PIECES], start: usize) -> Result<usize, String> {
+        let mut i = start;
+
+        for (square, piece) in &position.pieces {
+            if *piece == code && i < self.num {
+                squares[i] = square ^ mirror;
+                i += 1;
+            }
+        }
+
+        if i == start {
+            return Err(String::from("Invalid table! Its pieces do not match the position"));
+        }
+
+        return Ok(i);
+    }
+
+    /// Index of a position without Pawns.
+    fn encode_piece(&self, encoding: &Encoding, pos: &mut [usize; MAX_PIECES]) -> u64 {
+        let indices = indices();
+        let n = self.num;
+
+        // The leading piece goes to the a to d files, the first ranks and below the a1-h8 diagonal.
+        if pos[0] & 0x04 != 0 {
+            pos[..n].iter_mut().for_each(|square| *square ^= 0x07);
+        }
+
+        if pos[0] & 0x20 != 0 {
+            pos[..n].iter_mut().for_each(|square| *square ^= 0x38);
+        }
+
+        let first_off_diagonal = pos[..n].iter().position(|square| offdiag(*square) != 0).unwrap_or(n);
+        let leading = match self.enc_type {
+            0 => 3,
+            _ => 2,
+        };
+
+        if first_off_diagonal < leading && offdiag(pos[first_off_diagonal]) > 0 {
+            pos[..n].iter_mut().for_each(|square| *square = flipdiag(*square));
+        }
+
+        let (mut index, mut i) = match self.enc_type {
+            0 => {
+                let i = (pos[1] > pos[0]) as u64;
+                let j = (pos[2] > pos[0]) as u64 + (pos[2] > pos[1]) as u64;
+                let diag = |square: usize| (square / 8) as u64;
+                let (p1, p2) = (pos[1] as u64, pos[2] as u64);
+
+                let index = if offdiag(pos[0]) != 0 {
+                    TRIANGLE[pos[0]] as u64 * 63 * 62 + (p1 - i) * 62 + (p2 - j)
+                } else if offdiag(pos[1]) != 0 {
+                    6 * 63 * 62 + diag(pos[0]) * 28 * 62 + LOWER[pos[1]] * 62 + p2 - j
+                } else if offdiag(pos[2]) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 + diag(pos[0]) * 7 * 28 + (diag(pos[1]) - i) * 28 + LOWER[pos[2]]
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + diag(pos[0]) * 7 * 6 + (diag(pos[1]) - i) * 6 + (diag(pos[2]) - j)
+                };
+
+                (index, 3)
+            },
+            _ => (indices.kk_idx[TRIANGLE[pos[0]]][pos[1]], 2),
+        };
+
+        index *= encoding.factor[0];
+
+        while i < n {
+            index += Table::group_index(encoding, pos, i, 0) * encoding.factor[i];
+            i += encoding.norm[i];
+        }
+
+        return index;
+    }
+
+    /// Index of a position with Pawns.
+    fn encode_pawn(&self, encoding: &Encoding, pos: &mut [usize; MAX_PIECES]) -> u64 {
+        let indices = indices();
+        let n = self.num;
+
+        if pos[0] & 0x04 != 0 {
+            pos[..n].iter_mut().for_each(|square| *square ^= 0x07);
+        }
+
+        let lead = self.pawns[0];
+        pos[1..lead].sort_by(|a, b| PTWIST[*b].cmp(&PTWIST[*a]));
+
+        let t = lead - 1;
+        let mut index = indices.pawnidx[t][FLAP[pos[0]]];
+
+        for i in (1..=t).rev() {
+            index += indices.binomial[t - i][PTWIST[pos[i]]];
+        }
+
+        index *= encoding.factor[0];
+
+        let mut i = lead;
+
+        // The Pawns of the other color, which cannot stand on the first rank either.
+        if self.pawns[1] > 0 {
+            index += Table::group_index(encoding, pos, i, 8) * encoding.factor[i];
+            i += self.pawns[1];
+        }
+
+        while i < n {
+            index += Table::group_index(encoding, pos, i, 0) * encoding.factor[i];
+            i += encoding.norm[i];
+        }
+
+        return index;
+    }
+
+    /// Index of the group starting at `start` among the squares the pieces before it leave free.
+    fn group_index(encoding: &Encoding, pos: &mut [usize; MAX_PIECES], start: usize, skipped: usize) -> u64 {
+        let indices = indices();
+        let end = start + encoding.norm[start];
+
+        pos[start..end].sort();
+
+        let mut s = 0;
+
+        for m in start..end {
+            let p = pos[m];
+            let below = pos[..start].iter().filter(|square| p > **square).count();
+
+            s += indices.binomial[m - start][p - below - skipped];
+        }
+
+        return s;
+    }
+
+    /// Decodes the value stored at an index.
+    ///
+    /// Values are compressed by recursive pairing: each symbol stands for a pair of symbols, down to single values.
+    /// Symbols are then Huffman coded in blocks, with an index table telling in which block an index lies.
+    fn decompress_pairs(&self, d: &PairsData, idx: u64) -> Result<i32, String> {
+        let corrupt = || String::from("Invalid table! Corrupt compressed data");
+
+        if d.flags & SINGLE_VALUE != 0 {
+            return Ok(d.min_len as i32);
+        }
+
+        let main_idx = (idx >> d.idx_bits) as usize;
+        let mut lit_idx = (idx & ((1 << d.idx_bits) - 1)) as i64 - (1i64 << (d.idx_bits - 1));
+
+        let entry = d.index_table + 6 * main_idx;
+        let mut block = self.u32_le(entry)? as usize;
+        lit_idx += self.u16_le(entry + 4)? as i64;
+
+        let block_length = |block: usize| self.u16_le(d.size_table + 2 * block).map(|length| length as i64);
+
+        while lit_idx < 0 {
+            block = block.checked_sub(1).ok_or_else(corrupt)?;
+            lit_idx += block_length(block)? + 1;
+        }
+
+        while lit_idx > block_length(block)? {
+            lit_idx -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        let mut ptr = d.data + (block << d.block_size);
+        let mut code = self.u64_be(ptr)?;
+        let mut bitcnt = 0;
+        ptr += 8;
+
+        let m = d.min_len;
+        let mut sym;
+
+        loop {
+            let mut l = m;
+
+            while code < *d.base.get(l - m).ok_or_else(corrupt)? {
+                l += 1;
+            }
+
+            sym = self.u16_le(d.offset + 2 * (l - m))? as usize + ((code - d.base[l - m]) >> (64 - l)) as usize;
+
+            let length = *d.symlen.get(sym).ok_or_else(corrupt)? as i64;
+
+            if lit_idx < length + 1 {
+                break;
+            }
+
+            lit_idx -= length + 1;
+            code <<= l;
+            bitcnt += l;
+
+            if bitcnt >= 32 {
+                bitcnt -= 32;
+                code |= (self.u32_be(ptr)? as u64) << bitcnt;
+                ptr += 4;
+            }
+        }
+
+        while d.symlen[sym] != 0 {
+            let (left, right) = self.children(d.sympat, sym)?;
+            let length = *d.symlen.get(left).ok_or_else(corrupt)? as i64;
+
+            if lit_idx < length + 1 {
+                sym = left;
+            } else {
+                lit_idx -= length + 1;
+                sym = right;
+            }
+
+            if sym >= d.symlen.len() {
+                return Err(corrupt());
+            }
+        }
+
+        return Ok(self.children(d.sympat, sym)?.0 as i32);
+    }
+
+    /// Turns a value of a DTZ table into a distance in plies, plus one.
+    fn map_dtz(&self, file: usize, value: i32, wdl: i32) -> Result<i32, String> {
+        let flags = self.files[file][0].precomp.flags;
+
+        // Stored values are ranked by frequency for each outcome: win, loss, cursed win and blessed loss.
+        let outcome = match wdl {
+            2 => 0,
+            -2 => 1,
+            1 => 2,
+            -1 => 3,
+            _ => 0,
+        };
+
+        let mut value = value as usize;
+
+        if flags & MAPPED != 0 {
+            let idx = self.map_idx[file][outcome];
+
+            value = match flags & WIDE {
+                0 => self.byte(self.map + idx + value)? as usize,
+                _ => self.u16_le(self.map + 2 * (idx + value))? as usize,
+            };
+        }
+
+        let in_plies = match wdl {
+            2 => flags & WIN_PLIES != 0,
+            -2 => flags & LOSS_PLIES != 0,
+            _ => false,
+        };
+
+        if !in_plies {
+            value *= 2;
+        }
+
+        return Ok(value as i32 + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_the_index_tables(){
+        let indices = indices();
+
+        let king_codes = indices.kk_idx.iter().flatten().max().unwrap();
+        assert_eq!(*king_codes, 461);
+
+        assert_eq!(indices.binomial[1][5], 10);
+        assert_eq!(indices.binomial[4][63], 7028847);
+
+        assert_eq!(indices.pfactor[0], [6, 6, 6, 6]);
+        assert_eq!(indices.pawnidx[0][7], 1);
+
+        // Every square a Pawn can stand on has its own index.
+        let mut twisted : Vec<usize> = (8..56).map(|square| PTWIST[square]).collect();
+        twisted.sort();
+        assert_eq!(twisted, (0..48).collect::<Vec<usize>>());
+
+        // The squares below the diagonal come first, in order.
+        let below : Vec<u64> = (0..64).filter(|square| offdiag(*square) < 0).map(|square| LOWER[square]).collect();
+        assert_eq!(below, (0..28).collect::<Vec<u64>>());
+
+        assert_eq!(TRIANGLE[0], 6);
+        assert_eq!(TRIANGLE[1], 0);
+    }
+}